@@ -0,0 +1,196 @@
+use crate::camera::View;
+use crate::matrix::Matrix4;
+use crate::vec::Vec3;
+
+// a transform that changes over time, given by its value at a few key times.
+// every key is a scale, then a rotation and then a translation (the usual order for placing
+// an object: size it, turn it, move it). between two keys the translation and the scale move
+// in a straight line and the rotation turns at a steady rate about a fixed axis (slerp), the
+// shorter way round: keys more than 180 degrees apart need one in between to turn the long
+// way. before the first key and after the last the object holds still.
+pub struct Animation {
+    keys: Vec<Keyframe> // sorted by time
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vec3,
+    pub rotation: Quaternion,
+    pub scale: Vec3
+}
+
+impl Keyframe {
+    pub fn new(time: f64, translation: Vec3, rotation: Quaternion, scale: Vec3) -> Keyframe {
+        Keyframe { time, translation, rotation, scale }
+    }
+
+    pub fn matrix(&self) -> Matrix4 {
+        Matrix4::scaling(self.scale)
+            .then(self.rotation.matrix())
+            .translate(self.translation)
+    }
+
+    // t from 0 (self) to 1 (next)
+    fn lerp(&self, next: &Keyframe, t: f64) -> Keyframe {
+        Keyframe {
+            time: self.time + t * (next.time - self.time),
+            translation: self.translation + t * (next.translation - self.translation),
+            rotation: self.rotation.slerp(next.rotation, t),
+            scale: self.scale + t * (next.scale - self.scale)
+        }
+    }
+}
+
+impl Animation {
+    // panics without keys
+    pub fn new(mut keys: Vec<Keyframe>) -> Animation {
+        assert!(!keys.is_empty(), "an animation needs at least one key");
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Animation { keys }
+    }
+
+    pub fn keys(&self) -> &[Keyframe] {
+        &self.keys
+    }
+
+    pub fn at(&self, time: f64) -> Keyframe {
+        let next = self.keys.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return self.keys[0];
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1];
+        }
+        let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+        a.lerp(b, (time - a.time) / (b.time - a.time))
+    }
+
+    pub fn matrix(&self, time: f64) -> Matrix4 {
+        self.at(time).matrix()
+    }
+}
+
+// how a camera moves: from key to key, everything in a straight line (the up direction too,
+// kept a unit vector), or once round and round what it looks at
+pub enum CameraAnimation {
+    Keys(Vec<(f64, View)>), // sorted by time
+    // view's lookfrom turning about the line through lookat along vup, counterclockwise seen
+    // from above, one full turn from start to end
+    Turntable { view: View, start: f64, end: f64 }
+}
+
+impl CameraAnimation {
+    // panics without keys
+    pub fn keys(mut keys: Vec<(f64, View)>) -> CameraAnimation {
+        assert!(!keys.is_empty(), "an animation needs at least one key");
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        CameraAnimation::Keys(keys)
+    }
+
+    pub fn turntable(view: View, start: f64, end: f64) -> CameraAnimation {
+        CameraAnimation::Turntable { view, start, end }
+    }
+
+    pub fn at(&self, time: f64) -> View {
+        match self {
+            CameraAnimation::Keys(keys) => {
+                let next = keys.partition_point(|key| key.0 <= time);
+                if next == 0 {
+                    return keys[0].1;
+                }
+                if next == keys.len() {
+                    return keys[next - 1].1;
+                }
+                let ((t0, a), (t1, b)) = (keys[next - 1], keys[next]);
+                let t = (time - t0) / (t1 - t0);
+                let lerp = |a: f64, b: f64| a + t * (b - a);
+                View {
+                    lookfrom: a.lookfrom + t * (b.lookfrom - a.lookfrom),
+                    lookat: a.lookat + t * (b.lookat - a.lookat),
+                    vup: (a.vup + t * (b.vup - a.vup)).normalized(),
+                    vfov: lerp(a.vfov, b.vfov),
+                    aperture: lerp(a.aperture, b.aperture),
+                    focus_dist: lerp(a.focus_dist, b.focus_dist)
+                }
+            }
+            CameraAnimation::Turntable { view, start, end } => {
+                let degrees = 360.0 * (time - start) / (end - start);
+                let orbit = Matrix4::rotation(view.vup, degrees);
+                View {
+                    lookfrom: view.lookat + orbit.vector(view.lookfrom - view.lookat),
+                    ..*view
+                }
+            }
+        }
+    }
+}
+
+// a rotation as a unit quaternion: w = cos(angle / 2), v = sin(angle / 2) * axis
+#[derive(Clone, Copy, Debug)]
+pub struct Quaternion {
+    pub w: f64,
+    pub v: Vec3
+}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion { w: 1.0, v: Vec3 { e: [0.0, 0.0, 0.0] } };
+
+    // right handed like Matrix4::rotation
+    pub fn rotation(axis: Vec3, degrees: f64) -> Quaternion {
+        let (sin, cos) = (degrees.to_radians() / 2.0).sin_cos();
+        Quaternion { w: cos, v: sin * axis.normalized() }
+    }
+
+    pub fn dot(self, other: Quaternion) -> f64 {
+        self.w * other.w + self.v.dot(other.v)
+    }
+
+    pub fn normalized(self) -> Quaternion {
+        let length = self.dot(self).sqrt();
+        Quaternion { w: self.w / length, v: self.v / length }
+    }
+
+    // the angle in radians of the rotation that takes self to other, the shorter way
+    pub fn angle_to(self, other: Quaternion) -> f64 {
+        2.0 * self.dot(other).abs().min(1.0).acos()
+    }
+
+    // from self at t = 0 to other at t = 1 at a steady rate. q and -q are the same rotation,
+    // other is flipped if that makes the way shorter
+    pub fn slerp(self, other: Quaternion, t: f64) -> Quaternion {
+        let mut cos = self.dot(other);
+        let mut other = other;
+        if cos < 0.0 {
+            cos = -cos;
+            other = Quaternion { w: -other.w, v: -1.0 * other.v };
+        }
+        let (a, b) = if cos > 0.9995 {
+            // too close to divide by the sine, a straight line is as good
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Quaternion { w: a * self.w + b * other.w, v: a * self.v + b * other.v }.normalized()
+    }
+
+    pub fn matrix(self) -> Matrix4 {
+        let Quaternion { w, v: Vec3 { e: [x, y, z] } } = self;
+        Matrix4::new(
+            [
+                [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)],
+                [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x)],
+                [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y)]
+            ],
+            Vec3::default()
+        )
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Quaternion {
+        Quaternion::IDENTITY
+    }
+}
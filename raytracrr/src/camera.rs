@@ -1,73 +1,241 @@
+pub mod perspective;
+pub mod orthographic;
+pub mod fisheye;
+pub mod equirectangular;
+pub mod exposure;
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::sampler;
 use crate::vec::Colour;
+use exposure::Exposure;
 
 use super::vec::{Point3, Vec3};
 use super::ray::{Ray};
-use rand::Rng;
 
+// where the camera is and how it sees: what Camera::new takes apart from the shape of the
+// image and the background, kept so the camera can be moved (see animation.rs)
+#[derive(Clone, Copy, Debug)]
+pub struct View {
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub vfov: f64, // degrees
+    pub aperture: f64,
+    pub focus_dist: f64
+}
+
+// how the image is made from the rays leaving the camera, in the camera's own space: the
+// camera at the origin looking down -z, x to the right of the image and y up it.
+// ray gives the origin and direction of the ray through the point (s, t) of the image, both
+// from 0 to 1 starting at the bottom left, or None if nothing is seen there (the corners
+// around a fisheye's circle). lens is a point of the aperture, inside the unit circle, for
+// projections with a lens to start the ray from.
+// eye is for stereo pairs: how far right of the middle the eye is (negative for the left
+// one). projections place it however suits them, with no stereo it is 0.
+pub trait Projection : Send + Sync {
+    fn ray(&self, s: f64, t: f64, lens: (f64, f64), eye: f64) -> Option<(Point3, Vec3)>;
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ProjectionKind {
+    #[default]
+    Perspective, // a thin lens, vfov the angle from the bottom of the image to the top
+    // parallel rays, height the height of the view in scene units. without one, what vfov
+    // sees at lookat
+    Orthographic { height: Option<f64> },
+    // all of fov (degrees, up to 360) in a circle filling the shorter side of the image
+    Fisheye { fov: f64, mapping: fisheye::Mapping },
+    Equirectangular // every direction, longitude along the image and latitude up it
+}
+
+impl ProjectionKind {
+    pub fn create(self, view: &View, aspect_ratio: f64) -> Arc<dyn Projection> {
+        match self {
+            ProjectionKind::Perspective => Arc::new(perspective::Perspective::new(view, aspect_ratio)),
+            ProjectionKind::Orthographic { height } => {
+                let height = height.unwrap_or_else(|| {
+                    2.0 * (view.vfov.to_radians() / 2.0).tan() * (view.lookat - view.lookfrom).length()
+                });
+                Arc::new(orthographic::Orthographic::new(view, aspect_ratio, height))
+            }
+            ProjectionKind::Fisheye { fov, mapping } => Arc::new(fisheye::Fisheye::new(fov, mapping, aspect_ratio)),
+            ProjectionKind::Equirectangular => Arc::new(equirectangular::Equirectangular)
+        }
+    }
+}
+
+// the shape of the opening light comes through the lens by, which is the shape out of focus
+// highlights take: round, or the polygon blades (3 or more) straight blades leave, turned
+// rotation degrees
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Diaphragm {
+    pub blades: u32,
+    pub rotation: f64
+}
+
+impl Diaphragm {
+    // a point of the opening from a uniform point in the unit square
+    pub fn sample(&self, u: (f64, f64)) -> (f64, f64) {
+        if self.blades < 3 {
+            sampler::disk(u)
+        } else {
+            sampler::polygon(u, self.blades, self.rotation.to_radians())
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Camera {
-    origin: Point3,
-    lower_left_corner: Point3,
-    horizontal: Vec3, 
-    vertical: Vec3, 
-    cu: Vec3, 
-    cv: Vec3, 
-    lens_radius: f64, 
+    view: View,
+    aspect_ratio: f64,
+    shutter: Range<f64>, // when the shutter opens and closes, in the time of the scene
+    kind: ProjectionKind,
+    projection: Arc<dyn Projection>,
+    // the distance between the eyes of a stereo pair, rendered over/under: the left eye's
+    // image in the top half, the right eye's in the bottom half
+    stereo: Option<f64>,
+    diaphragm: Diaphragm,
+    exposure: Option<Exposure>,
+    white_balance: Option<f64>, // kelvin
+    sensor: Colour, // what the exposure and white balance multiply radiance by
+    // the camera's axes: right, up and backwards
+    cu: Vec3,
+    cv: Vec3,
+    cw: Vec3,
     pub background: Colour
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         lookfrom: Point3,
-        lookat: Point3, 
+        lookat: Point3,
         vup: Vec3,
-        vfov: f64, 
+        vfov: f64,
         aspect_ratio: f64,
-        aperture: f64, 
+        aperture: f64,
         focus_dist: f64,
         background: Colour
     ) -> Camera {
-        // CAMERA
-        // viewport's aspect ratio should be the same as rendered image. 
-        // pick a viewport two units in height. 
-        // We'll also set the distance between the projection plane and the projection point to be one unit.
-        
-        // vertical field-of-view in degrees
-        let theta = std::f64::consts::PI / 180.0 * vfov;
-        let viewport_height = 2.0 * (theta / 2.0).tan();
-        let viewport_width = aspect_ratio * viewport_height;
-
-        let cw = (lookfrom - lookat).normalized();
-        let cu = vup.cross(cw).normalized();
-        let cv = cw.cross(cu);
-
-        // y coordinate points up
-        // x coordinate points left
-        // z coordinate points towards the plane
-        let horizontal = focus_dist * viewport_width * cu;
-        let vertical = focus_dist * viewport_height * cv;
-        let lower_left_corner = lookfrom - horizontal / 2.0 - vertical / 2.0 - focus_dist * cw;
+        let view = View { lookfrom, lookat, vup, vfov, aperture, focus_dist };
+        Camera::from_view(view, aspect_ratio, background)
+    }
 
+    // a perspective camera with a round aperture, radiance straight into the image
+    pub fn from_view(view: View, aspect_ratio: f64, background: Colour) -> Camera {
+        let kind = ProjectionKind::Perspective;
+        let zero = Vec3::new(0.0, 0.0, 0.0);
         Camera {
-            origin: lookfrom,
-            lower_left_corner,
-            horizontal,
-            vertical,
-            cu: cu, 
-            background: background,
-            cv: cv, 
-            lens_radius: aperture / 2.0
+            view,
+            aspect_ratio,
+            shutter: 0.0..1.0,
+            kind,
+            projection: kind.create(&view, aspect_ratio),
+            stereo: None,
+            diaphragm: Diaphragm::default(),
+            exposure: None,
+            white_balance: None,
+            sensor: Colour::new(1.0, 1.0, 1.0),
+            cu: zero,
+            cv: zero,
+            cw: zero,
+            background
+        }.rebuilt()
+    }
+
+    // works out everything that follows from the settings after one of them changed
+    fn rebuilt(mut self) -> Camera {
+        self.cw = (self.view.lookfrom - self.view.lookat).normalized();
+        self.cu = self.view.vup.cross(self.cw).normalized();
+        self.cv = self.cw.cross(self.cu);
+
+        // each eye of a stereo pair gets half the height
+        let eye_aspect_ratio = if self.stereo.is_some() { 2.0 * self.aspect_ratio } else { self.aspect_ratio };
+        self.projection = self.kind.create(&self.view, eye_aspect_ratio);
+
+        let scale = self.exposure.map_or(1.0, |exposure| exposure.scale());
+        let balance = self.white_balance.map_or(Colour::new(1.0, 1.0, 1.0), exposure::white_balance);
+        self.sensor = scale * balance;
+        self
+    }
+
+    pub fn view(&self) -> View {
+        self.view
+    }
+
+    // the same camera (image shape, background, projection, shutter, ...) moved to view
+    pub fn with_view(&self, view: View) -> Camera {
+        Camera { view, ..self.clone() }.rebuilt()
+    }
+
+    pub fn projection(&self) -> ProjectionKind {
+        self.kind
+    }
+
+    pub fn with_projection(&self, kind: ProjectionKind) -> Camera {
+        Camera { kind, ..self.clone() }.rebuilt()
+    }
+
+    // None for a single image, or the distance between the eyes of an over/under stereo pair
+    pub fn with_stereo(&self, eye_distance: Option<f64>) -> Camera {
+        Camera { stereo: eye_distance, ..self.clone() }.rebuilt()
+    }
+
+    pub fn with_diaphragm(&self, diaphragm: Diaphragm) -> Camera {
+        Camera { diaphragm, ..self.clone() }
+    }
+
+    pub fn exposure(&self) -> Option<Exposure> {
+        self.exposure
+    }
+
+    // None leaves radiance as it is. an exposure also opens the shutter for its shutter speed
+    // from when it opened before
+    pub fn with_exposure(&self, exposure: Option<Exposure>) -> Camera {
+        let mut shutter = self.shutter.clone();
+        if let Some(exposure) = exposure {
+            shutter.end = shutter.start + exposure.shutter_speed;
         }
+        Camera { exposure, shutter, ..self.clone() }.rebuilt()
+    }
+
+    // None leaves the colours as they are, or kelvin (see exposure::white_balance)
+    pub fn with_white_balance(&self, kelvin: Option<f64>) -> Camera {
+        Camera { white_balance: kelvin, ..self.clone() }.rebuilt()
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_sphere();
-        let offset = self.cu * rd.x() + self.cv * rd.y();
+    // what radiance arriving at the camera is multiplied by to get the colour in the image
+    pub fn sensor(&self) -> Colour {
+        self.sensor
+    }
+
+    // the shutter is open from 0 to 1 unless set otherwise
+    pub fn with_shutter(mut self, shutter: Range<f64>) -> Camera {
+        self.shutter = shutter;
+        self
+    }
+
+    pub fn shutter(&self) -> Range<f64> {
+        self.shutter.clone()
+    }
 
-        let mut rng = rand::thread_rng();
-        let random_time : f64 = rng.gen();
+    // lens and time are uniform numbers from the sampler, for the point on the lens the ray
+    // starts from and when while the shutter is open it does.
+    // None where the image sees nothing
+    pub fn get_ray(&self, s: f64, t: f64, lens: (f64, f64), time: f64) -> Option<Ray> {
+        let (t, eye) = match self.stereo {
+            Some(distance) if t >= 0.5 => (2.0 * t - 1.0, -distance / 2.0),
+            Some(distance) => (2.0 * t, distance / 2.0),
+            None => (t, 0.0)
+        };
+        let (origin, direction) = self.projection.ray(s, t, self.diaphragm.sample(lens), eye)?;
 
-        Ray::new_(self.origin + offset,
-                 self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
-                random_time)
+        Some(Ray::new_(
+            self.view.lookfrom + origin.x() * self.cu + origin.y() * self.cv + origin.z() * self.cw,
+            direction.x() * self.cu + direction.y() * self.cv + direction.z() * self.cw,
+            self.shutter.start + time * (self.shutter.end - self.shutter.start)
+        ))
     }
 }
@@ -0,0 +1,28 @@
+use std::f64::consts::PI;
+
+use crate::camera::Projection;
+use crate::vec::{Point3, Vec3};
+
+// every direction at once, for 360 degree panoramas: s goes once round from behind on the
+// left to behind on the right (straight ahead in the middle), t from straight down to
+// straight up. twice as wide as high keeps the pixels square. no lens, everything is sharp.
+//
+// a stereo pair can't just put the eyes side by side, looking behind they'd swap over. the
+// eyes turn with the direction instead (omni-directional stereo): whichever way a column of
+// the panorama looks, its eye stands eye to the right of the centre, seen facing that way.
+pub struct Equirectangular;
+
+impl Projection for Equirectangular {
+    fn ray(&self, s: f64, t: f64, _lens: (f64, f64), eye: f64) -> Option<(Point3, Vec3)> {
+        let longitude = (s - 0.5) * 2.0 * PI; // right of straight ahead
+        let latitude = (t - 0.5) * PI; // above the horizon
+        let (sin_long, cos_long) = longitude.sin_cos();
+        let direction = Vec3::new(
+            sin_long * latitude.cos(),
+            latitude.sin(),
+            -cos_long * latitude.cos()
+        );
+        let origin = eye * Vec3::new(cos_long, 0.0, sin_long);
+        Some((origin, direction))
+    }
+}
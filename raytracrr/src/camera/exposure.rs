@@ -0,0 +1,70 @@
+use crate::vec::Colour;
+
+// the settings of a camera that decide how bright the picture comes out, as on a real one:
+// light comes in through an opening f_stop times smaller than the focal length, for
+// shutter_speed seconds, onto a sensor iso sensitive.
+// radiance is scaled so what a light meter would call just too bright (saturation based
+// sensitivity, with the usual lens and vignetting losses) comes out as 1. lights have to be
+// about as bright as real ones for that to look right: luminance in cd/m², a white wall in
+// sunlight is around 10000 and f/16, 1/100 s, iso 100 is the exposure for it.
+// compensation brightens (or darkens, negative) the picture by that many stops.
+//
+// the f-stop only sets brightness: the scene has no units to tell how wide the lens is from
+// it, so depth of field still comes from the camera's aperture
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Exposure {
+    pub f_stop: f64,
+    pub shutter_speed: f64, // seconds
+    pub iso: f64,
+    pub compensation: f64 // stops
+}
+
+impl Exposure {
+    pub fn new(f_stop: f64, shutter_speed: f64, iso: f64) -> Exposure {
+        Exposure { f_stop, shutter_speed, iso, compensation: 0.0 }
+    }
+
+    // the exposure value at iso 100: each step up is half as much light
+    pub fn ev100(&self) -> f64 {
+        (self.f_stop * self.f_stop / self.shutter_speed * 100.0 / self.iso).log2()
+    }
+
+    // what radiance is multiplied by on its way to the sensor. 1.2 is 78 / (100 * 0.65), the
+    // iso standard's saturation constant over the light reaching the sensor through the lens
+    pub fn scale(&self) -> f64 {
+        (self.compensation - self.ev100()).exp2() / 1.2
+    }
+}
+
+// the colours white balance and blackbody are good for: outside these the approximation of
+// the blackbody's colour below stops working
+pub const KELVIN: std::ops::RangeInclusive<f64> = 2000.0..=25000.0;
+
+// the colour of a blackbody (an ideal hot object: the sun, a tungsten filament) at kelvin,
+// in linear srgb with a luminance of 1. kim et al.'s fit of the planckian locus gives the
+// chromaticity, 2000 K is orange, 6500 K about white and 10000 K blue
+pub fn blackbody(kelvin: f64) -> Colour {
+    let t = kelvin.clamp(*KELVIN.start(), *KELVIN.end());
+    let x = if t <= 4000.0 {
+        -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t.powi(3) + 2.1070379e6 / t.powi(2) + 0.2226347e3 / t + 0.240390
+    };
+    let y = if t <= 2222.0 {
+        -1.1063814 * x.powi(3) - 1.34811020 * x.powi(2) + 2.18555832 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x.powi(3) - 1.37418593 * x.powi(2) + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x.powi(3) - 5.87338670 * x.powi(2) + 3.75112997 * x - 0.37001483
+    };
+    Colour::from_xyy(x, y, 1.0)
+}
+
+// what each channel is multiplied by so white lit by a blackbody at kelvin comes out as
+// white lit at 6500 K does, and just as bright: 3200 for a room of light bulbs, 5500 for
+// daylight
+pub fn white_balance(kelvin: f64) -> Colour {
+    let light = blackbody(kelvin);
+    let white = blackbody(6500.0);
+    Colour::new(white[0] / light[0], white[1] / light[1], white[2] / light[2])
+}
@@ -0,0 +1,54 @@
+use crate::camera::Projection;
+use crate::vec::{Point3, Vec3};
+
+// a circle in the middle of the image holding every direction up to fov / 2 away from
+// straight ahead, at a distance from the centre that grows with the angle (mapping).
+// outside the circle is black. it has no lens, everything is sharp.
+// the eyes of a stereo pair are side by side, looking the same way.
+pub struct Fisheye {
+    half_fov: f64, // radians
+    mapping: Mapping,
+    scale: (f64, f64) // from s and t around the centre to the circle of radius 1
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Mapping {
+    #[default]
+    Equidistant, // distance from the centre proportional to the angle
+    Equisolid // area proportional to solid angle: every pixel sees as much of the sphere
+}
+
+impl Fisheye {
+    pub fn new(fov: f64, mapping: Mapping, aspect_ratio: f64) -> Fisheye {
+        let scale = if aspect_ratio >= 1.0 {
+            (2.0 * aspect_ratio, 2.0)
+        } else {
+            (2.0, 2.0 / aspect_ratio)
+        };
+        Fisheye {
+            half_fov: fov.min(360.0).to_radians() / 2.0,
+            mapping,
+            scale
+        }
+    }
+}
+
+impl Projection for Fisheye {
+    fn ray(&self, s: f64, t: f64, _lens: (f64, f64), eye: f64) -> Option<(Point3, Vec3)> {
+        let x = (s - 0.5) * self.scale.0;
+        let y = (t - 0.5) * self.scale.1;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+
+        // angle away from straight ahead
+        let theta = match self.mapping {
+            Mapping::Equidistant => r * self.half_fov,
+            Mapping::Equisolid => 2.0 * (r * (self.half_fov / 2.0).sin()).asin()
+        };
+        let phi = if r > 0.0 { y.atan2(x) } else { 0.0 };
+        let direction = Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), -theta.cos());
+        Some((Point3::new(eye, 0.0, 0.0), direction))
+    }
+}
@@ -0,0 +1,35 @@
+use crate::camera::{Projection, View};
+use crate::vec::{Point3, Vec3};
+
+// all rays parallel, straight ahead, so things don't get smaller further away: plans and
+// elevations. the image is a window of height scene units.
+// an aperture blurs everything away from the focus plane as in Perspective, the eyes of a
+// stereo pair stand either side of the window and look through it at the focus plane.
+pub struct Orthographic {
+    width: f64,
+    height: f64,
+    focus_dist: f64,
+    lens_radius: f64
+}
+
+impl Orthographic {
+    pub fn new(view: &View, aspect_ratio: f64, height: f64) -> Orthographic {
+        Orthographic {
+            width: aspect_ratio * height,
+            height,
+            focus_dist: view.focus_dist,
+            lens_radius: view.aperture / 2.0
+        }
+    }
+}
+
+impl Projection for Orthographic {
+    fn ray(&self, s: f64, t: f64, lens: (f64, f64), eye: f64) -> Option<(Point3, Vec3)> {
+        let (dx, dy) = lens;
+        let x = (s - 0.5) * self.width;
+        let y = (t - 0.5) * self.height;
+        let origin = Point3::new(x + eye + self.lens_radius * dx, y + self.lens_radius * dy, 0.0);
+        let target = Point3::new(x, y, -self.focus_dist);
+        Some((origin, target - origin))
+    }
+}
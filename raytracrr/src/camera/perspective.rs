@@ -0,0 +1,42 @@
+use crate::camera::{Projection, View};
+use crate::vec::{Point3, Vec3};
+
+// a thin lens: every ray starts from a point on the lens and passes through the point of the
+// image on the plane focus_dist away, which is where everything is sharp. with no aperture
+// the lens is a pinhole and everything is.
+//
+// viewport's aspect ratio should be the same as rendered image: with a viewport one unit
+// away, its height follows from vfov and its width from the height.
+// the eyes of a stereo pair look through the same viewport from either side, so they agree
+// about where the focus plane is and things there sit at the same place in both images.
+pub struct Perspective {
+    width: f64, // of the image on the focus plane
+    height: f64,
+    focus_dist: f64,
+    lens_radius: f64
+}
+
+impl Perspective {
+    pub fn new(view: &View, aspect_ratio: f64) -> Perspective {
+        // vertical field-of-view in degrees
+        let theta = view.vfov.to_radians();
+        let viewport_height = 2.0 * (theta / 2.0).tan();
+        let viewport_width = aspect_ratio * viewport_height;
+
+        Perspective {
+            width: view.focus_dist * viewport_width,
+            height: view.focus_dist * viewport_height,
+            focus_dist: view.focus_dist,
+            lens_radius: view.aperture / 2.0
+        }
+    }
+}
+
+impl Projection for Perspective {
+    fn ray(&self, s: f64, t: f64, lens: (f64, f64), eye: f64) -> Option<(Point3, Vec3)> {
+        let (dx, dy) = lens;
+        let origin = Point3::new(eye + self.lens_radius * dx, self.lens_radius * dy, 0.0);
+        let target = Point3::new((s - 0.5) * self.width, (t - 0.5) * self.height, -self.focus_dist);
+        Some((origin, target - origin))
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::render::{Accumulator, RenderSettings};
+use crate::Error;
+
+// a render in progress, saved so it can carry on after the process is gone.
+// every sample's random numbers follow from the seed, the pixel and the sample's index (see
+// sampler.rs), so the sums and counts of the pixels are all the state there is: carrying on
+// from them gives the same image as never stopping.
+// that only holds for the same scene and the same settings, so the checkpoint keeps a hash of
+// both and refuses to be resumed with different ones. the settings that don't change the image
+// (threads, tile size, passes) are left out of the hash and can change.
+//
+// layout: MAGIC, the format version (u32), the settings hash and the scene hash (u64), then
+// the accumulator (Accumulator::write). numbers are little endian.
+
+const MAGIC: &[u8; 21] = b"raytracrr checkpoint\n";
+const VERSION: u32 = 1;
+
+// what the render is of, as far as the checkpoint can tell: a hash of where the scene came
+// from (e.g. the text of the scene file, or the name and seed of a built-in scene)
+pub fn scene_hash(source: &[u8]) -> u64 {
+    fnv1a(source)
+}
+
+pub fn settings_hash(settings: &RenderSettings) -> u64 {
+    let adaptive = settings.adaptive.map(|adaptive| (adaptive.min_samples, adaptive.threshold.to_bits(), adaptive.max_samples));
+    let description = format!(
+        "{}x{} spp {} adaptive {:?} depth {} seed {} sampler {:?} bvh {:?} light sampling {}",
+        settings.width, settings.height, settings.samples_per_pixel, adaptive, settings.max_depth,
+        settings.seed, settings.sampler, settings.bvh, settings.light_sampling
+    );
+    fnv1a(description.as_bytes())
+}
+
+// written next to path and renamed over it, so a crash while saving leaves the last
+// checkpoint as it was
+pub fn save(path: &Path, state: &Accumulator, settings: &RenderSettings, scene_hash: u64) -> Result<(), Error> {
+    let mut temporary = PathBuf::from(path);
+    temporary.as_mut_os_string().push(".partial");
+
+    let written = (|| -> Result<(), Error> {
+        let mut out = BufWriter::new(File::create(&temporary)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&settings_hash(settings).to_le_bytes())?;
+        out.write_all(&scene_hash.to_le_bytes())?;
+        state.write(&mut out)?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    })();
+    written.map_err(|e| format!("can't save checkpoint {}: {}", path.display(), e).into())
+}
+
+pub fn load(path: &Path, settings: &RenderSettings, scene_hash: u64) -> Result<Accumulator, Error> {
+    let loaded = (|| -> Result<Accumulator, Error> {
+        let mut input = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err("not a checkpoint".into());
+        }
+        let mut version = [0; 4];
+        input.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(format!("checkpoint format {} is not supported, expected {}", version, VERSION).into());
+        }
+
+        let mut hash = [0; 8];
+        input.read_exact(&mut hash)?;
+        if u64::from_le_bytes(hash) != settings_hash(settings) {
+            return Err("it was made with different render settings".into());
+        }
+        input.read_exact(&mut hash)?;
+        if u64::from_le_bytes(hash) != scene_hash {
+            return Err("it was made for a different scene".into());
+        }
+
+        let state = Accumulator::read(&mut input)?;
+        if input.read(&mut [0])? != 0 {
+            return Err("unexpected data after the end".into());
+        }
+        Ok(state)
+    })();
+    loaded.map_err(|e| format!("can't resume from checkpoint {}: {}", path.display(), e).into())
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{Framebuffer, Renderer};
+    use crate::scenes;
+
+    fn settings() -> RenderSettings {
+        RenderSettings {
+            width: 10,
+            height: 8,
+            samples_per_pixel: 6,
+            max_depth: 4,
+            threads: 1,
+            ..RenderSettings::default()
+        }
+    }
+
+    fn renderer(settings: &RenderSettings) -> Renderer {
+        let scene = (scenes::find_scene("cornell_box").unwrap().build)(1.25, 0).unwrap();
+        Renderer::new(scene.world, scene.camera, settings.clone())
+    }
+
+    // a file of its own in the temporary directory for every test
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("raytracrr-{}-{}.checkpoint", std::process::id(), name))
+    }
+
+    fn assert_same(a: &Framebuffer, b: &Framebuffer) {
+        for y in 0..a.height() {
+            for x in 0..a.width() {
+                assert_eq!(a.get(x, y).e, b.get(x, y).e, "pixel {} {}", x, y);
+                assert_eq!(a.samples(x, y), b.samples(x, y), "samples of pixel {} {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn resuming_gives_the_same_image() {
+        let settings = settings();
+        let scene = scene_hash(b"cornell_box 0");
+        let path = path("resume");
+        let image = renderer(&settings).render().unwrap();
+
+        // stopped after the first pass
+        let mut state = Accumulator::new(settings.width, settings.height);
+        renderer(&settings).render_progressive(&mut state, 2, None, |state| {
+            save(&path, state, &settings, scene)?;
+            Err("stopped".into())
+        }).unwrap_err();
+
+        // carried on by a new renderer, with threads and tiles that don't count
+        let resumed = RenderSettings { threads: 2, tile_size: 3, ..settings.clone() };
+        let mut state = load(&path, &resumed, scene).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((state.passes(), state.samples_per_pixel()), (1, 2));
+        renderer(&resumed).render_progressive(&mut state, 2, None, |_| Ok(())).unwrap();
+        assert_eq!(state.passes(), 3);
+        assert_same(&image, &state.framebuffer());
+    }
+
+    #[test]
+    fn load_refuses_other_settings_and_scenes() {
+        let settings = settings();
+        let scene = scene_hash(b"cornell_box 0");
+        let path = path("refuse");
+        save(&path, &Accumulator::new(settings.width, settings.height), &settings, scene).unwrap();
+
+        let others = [
+            RenderSettings { samples_per_pixel: 7, ..settings.clone() },
+            RenderSettings { seed: 1, ..settings.clone() },
+            RenderSettings { max_depth: 5, ..settings.clone() },
+            RenderSettings { light_sampling: false, ..settings.clone() }
+        ];
+        for other in &others {
+            let error = load(&path, other, scene).err().unwrap().to_string();
+            assert!(error.contains("different render settings"), "{}", error);
+        }
+        let error = load(&path, &settings, scene_hash(b"cornell_box 1")).err().unwrap().to_string();
+        assert!(error.contains("different scene"), "{}", error);
+
+        assert!(load(&path, &settings, scene).is_ok());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,436 @@
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use raytracrr::camera::ProjectionKind;
+use raytracrr::camera::fisheye::Mapping;
+use raytracrr::hit::bvh::Split;
+use raytracrr::Adaptive;
+use raytracrr::output::ImageFormat;
+use raytracrr::sampler::SamplerKind;
+use raytracrr::scenes::{self, SCENES};
+use raytracrr::Sequence;
+
+pub const USAGE: &str = "\
+usage: raytracrr [options]
+
+options:
+    --scene <name>       built-in scene to render (default: final_scene)
+    --scene-file <file>  render a scene described in a toml file instead
+    --width <px>         image width (default: 800, or --height if only that is given)
+    --height <px>        image height (default: --width)
+    --spp <n>            samples per pixel, on average with --adaptive (default: 1000)
+    --adaptive <error>   stop sampling a pixel once the relative error of its mean is below
+                         <error>, e.g. 0.02, and give the samples it doesn't take to the
+                         noisiest pixels
+    --min-spp <n>        with --adaptive, samples every pixel takes before it can stop, and
+                         how often it checks after that (default: 16, or --spp if lower)
+    --max-spp <n>        with --adaptive, the most samples any pixel takes (default: 4 times
+                         --spp)
+    --heatmap <file>     also write an image of how many samples every pixel took
+    --pass-spp <n>       render progressively, in passes of <n> samples per pixel over the
+                         whole image, rewriting --output as they finish (default: 16 with
+                         --time-limit, otherwise the image is rendered in one pass)
+    --snapshot-every <s> with passes, rewrite --output at most every <s> seconds instead of
+                         after every pass
+    --time-limit <s>     stop after <s> seconds, keeping the passes finished by then
+    --checkpoint <file>  with passes, save the render so far to <file> every now and then and
+                         when it stops, so it can be resumed (passes default to 16 spp)
+    --checkpoint-every <s>
+                         save the checkpoint at most every <s> seconds (default: 60)
+    --resume             carry on from --checkpoint, which must be of the same scene rendered
+                         with the same settings
+    --projection <name>  how the camera sees: perspective, orthographic, fisheye or
+                         equirectangular (360 degree panorama) (default: the scene's own)
+    --ortho-height <h>   height of the orthographic view in scene units (default: what the
+                         perspective camera sees at the point it looks at)
+    --fisheye-fov <deg>  field of view of the fisheye circle, up to 360 (default: 180)
+    --fisheye-mapping <name>
+                         equidistant or equisolid (default: equidistant)
+    --stereo <distance>  render a stereo pair over/under, the left eye on top, with the eyes
+                         <distance> apart in scene units
+    --frames <a>-<b>     render frames a to b of the scene's animation instead of a single
+                         image (or just frame <a>), each to --output with the frame number in
+                         place of the last run of #s in its name (frame_####.png), or added
+                         before the extension
+    --fps <n>            frames per second of the animation (default: 24)
+    --shutter <fraction> how much of every frame the shutter is open for, blurring whatever
+                         moves meanwhile (default: 0.5). a camera with an exposure is open
+                         for its shutter speed instead
+    --turntable          with --frames, turn the camera once around what it looks at over the
+                         frames, instead of the scene's own camera animation
+    --max-depth <n>      maximum ray bounces (default: 10)
+    --output <file>      write the image to <file> instead of stdout (as ppm), the format
+                         follows the extension: .png, .ppm, .hdr, .pfm or .exr
+    --bit-depth <8|16>   bits per channel of png output (default: 8)
+    --seed <n>           seed for the random numbers of the render, the same seed gives the
+                         same image on any number of threads (default: 0)
+    --scene-seed <n>     seed for the random parts of a built-in scene (default: 0)
+    --sampler <name>     where the samples of a pixel come from: independent, stratified,
+                         halton or sobol (default: sobol)
+    --threads <n>        render threads, 0 for one per cpu (default: 0)
+    --tile-size <px>     edge length of a render tile (default: 32)
+    --bvh <builder>      how to build the bvh over the scene: sah, median, or none to test
+                         every object for every ray (default: sah)
+    --no-light-sampling  only find lights by bouncing into them, not by sampling them (point,
+                         spot and directional lights can't be bounced into, they still are)
+    --list-scenes        list the built-in scenes and exit
+    --help               print this message and exit";
+
+pub enum SceneSource {
+    Builtin(String),
+    File(PathBuf)
+}
+
+pub struct Options {
+    pub scene: SceneSource,
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: u64,
+    pub adaptive: Option<Adaptive>,
+    pub max_depth: u64,
+    pub output: Option<(PathBuf, ImageFormat)>, // None: ppm on stdout
+    pub heatmap: Option<(PathBuf, ImageFormat)>,
+    pub pass_samples: Option<u64>, // None: a single pass
+    pub snapshot_interval: Duration,
+    pub time_limit: Option<Duration>,
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_interval: Duration,
+    pub resume: bool,
+    pub projection: Option<ProjectionKind>, // None: the scene's
+    pub stereo: Option<f64>,
+    pub sequence: Option<Sequence>, // None: a single image
+    pub turntable: bool,
+    pub seed: u64,
+    pub scene_seed: u64,
+    pub sampler: SamplerKind,
+    pub threads: usize,
+    pub tile_size: usize,
+    pub bvh: Option<Split>, // None: no bvh
+    pub light_sampling: bool
+}
+
+pub enum Command {
+    Render(Box<Options>),
+    ListScenes,
+    Help
+}
+
+// parses the arguments following the program name
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter();
+
+    let mut scene = None;
+    let mut scene_file = None;
+    let mut width = None;
+    let mut height = None;
+    let mut samples_per_pixel = 1000;
+    let mut threshold = None;
+    let mut min_samples = None;
+    let mut max_samples = None;
+    let mut max_depth = 10;
+    let mut output = None;
+    let mut heatmap = None;
+    let mut pass_samples = None;
+    let mut snapshot_interval = Duration::ZERO;
+    let mut time_limit = None;
+    let mut checkpoint = None;
+    let mut checkpoint_interval = Duration::from_secs(60);
+    let mut resume = false;
+    let mut projection = None;
+    let mut ortho_height = None;
+    let mut fisheye_fov = None;
+    let mut fisheye_mapping = None;
+    let mut stereo = None;
+    let mut frames = None;
+    let mut fps = None;
+    let mut shutter = None;
+    let mut turntable = false;
+    let mut bit_depth = 8;
+    let mut seed = 0;
+    let mut scene_seed = 0;
+    let mut sampler = SamplerKind::Sobol;
+    let mut threads = 0;
+    let mut tile_size = 32;
+    let mut bvh = Some(Split::Sah);
+    let mut light_sampling = true;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" => return Ok(Command::Help),
+            "--list-scenes" => return Ok(Command::ListScenes),
+            "--scene" => scene = Some(value(&arg, args.next())?),
+            "--scene-file" => scene_file = Some(PathBuf::from(value(&arg, args.next())?)),
+            "--width" => width = Some(positive(&arg, args.next(), 2)?),
+            "--height" => height = Some(positive(&arg, args.next(), 2)?),
+            "--spp" => samples_per_pixel = positive(&arg, args.next(), 1)?,
+            "--adaptive" => {
+                let value = value(&arg, args.next())?;
+                threshold = match value.parse::<f64>() {
+                    Ok(threshold) if threshold > 0.0 => Some(threshold),
+                    _ => return Err(format!("invalid value '{}' for --adaptive: expected a positive number", value))
+                };
+            }
+            "--min-spp" => min_samples = Some(positive(&arg, args.next(), 1)?),
+            "--max-spp" => max_samples = Some(positive(&arg, args.next(), 1)?),
+            "--heatmap" => heatmap = Some(PathBuf::from(value(&arg, args.next())?)),
+            "--pass-spp" => pass_samples = Some(positive(&arg, args.next(), 1)?),
+            "--snapshot-every" => snapshot_interval = seconds(&arg, args.next())?,
+            "--time-limit" => time_limit = Some(seconds(&arg, args.next())?),
+            "--checkpoint" => checkpoint = Some(PathBuf::from(value(&arg, args.next())?)),
+            "--checkpoint-every" => checkpoint_interval = seconds(&arg, args.next())?,
+            "--resume" => resume = true,
+            "--projection" => {
+                let value = value(&arg, args.next())?;
+                projection = match value.as_str() {
+                    "perspective" | "orthographic" | "fisheye" | "equirectangular" => Some(value),
+                    _ => return Err(format!(
+                        "invalid value '{}' for --projection: expected perspective, orthographic, fisheye or equirectangular",
+                        value
+                    ))
+                };
+            }
+            "--ortho-height" => ortho_height = Some(positive_number(&arg, args.next())?),
+            "--fisheye-fov" => {
+                let fov = positive_number(&arg, args.next())?;
+                if fov > 360.0 {
+                    return Err(format!("invalid value '{}' for --fisheye-fov: can't be more than 360", fov));
+                }
+                fisheye_fov = Some(fov);
+            }
+            "--fisheye-mapping" => {
+                let value = value(&arg, args.next())?;
+                fisheye_mapping = match value.as_str() {
+                    "equidistant" => Some(Mapping::Equidistant),
+                    "equisolid" => Some(Mapping::Equisolid),
+                    _ => return Err(format!(
+                        "invalid value '{}' for --fisheye-mapping: expected equidistant or equisolid", value
+                    ))
+                };
+            }
+            "--stereo" => stereo = Some(positive_number(&arg, args.next())?),
+            "--frames" => frames = Some(frame_range(&arg, args.next())?),
+            "--fps" => {
+                let value = value(&arg, args.next())?;
+                fps = match value.parse::<f64>() {
+                    Ok(fps) if fps > 0.0 && fps.is_finite() => Some(fps),
+                    _ => return Err(format!("invalid value '{}' for --fps: expected a positive number", value))
+                };
+            }
+            "--shutter" => {
+                let value = value(&arg, args.next())?;
+                shutter = match value.parse::<f64>() {
+                    Ok(shutter) if (0.0..=1.0).contains(&shutter) => Some(shutter),
+                    _ => return Err(format!("invalid value '{}' for --shutter: expected a number from 0 to 1", value))
+                };
+            }
+            "--turntable" => turntable = true,
+            "--max-depth" => max_depth = positive(&arg, args.next(), 1)?,
+            "--output" | "-o" => output = Some(PathBuf::from(value(&arg, args.next())?)),
+            "--bit-depth" => bit_depth = number(&arg, args.next())?,
+            "--seed" => seed = number(&arg, args.next())?,
+            "--scene-seed" => scene_seed = number(&arg, args.next())?,
+            "--sampler" => {
+                let value = value(&arg, args.next())?;
+                sampler = match value.as_str() {
+                    "independent" => SamplerKind::Independent,
+                    "stratified" => SamplerKind::Stratified,
+                    "halton" => SamplerKind::Halton,
+                    "sobol" => SamplerKind::Sobol,
+                    _ => return Err(format!(
+                        "invalid value '{}' for --sampler: expected independent, stratified, halton or sobol", value
+                    ))
+                };
+            }
+            "--threads" => threads = number(&arg, args.next())?,
+            "--tile-size" => tile_size = positive(&arg, args.next(), 1)?,
+            "--bvh" => {
+                let value = value(&arg, args.next())?;
+                bvh = match value.as_str() {
+                    "sah" => Some(Split::Sah),
+                    "median" => Some(Split::Median),
+                    "none" => None,
+                    _ => return Err(format!("invalid value '{}' for --bvh: expected sah, median or none", value))
+                };
+            }
+            "--no-light-sampling" => light_sampling = false,
+            _ => return Err(format!("unknown argument '{}'", arg))
+        }
+    }
+
+    let scene = match (scene, scene_file) {
+        (Some(_), Some(_)) => return Err("--scene and --scene-file can't be used together".to_string()),
+        (None, Some(path)) => SceneSource::File(path),
+        (name, None) => {
+            let name = name.unwrap_or_else(|| String::from("final_scene"));
+            if scenes::find_scene(&name).is_none() {
+                return Err(format!("unknown scene '{}' (try --list-scenes)", name));
+            }
+            SceneSource::Builtin(name)
+        }
+    };
+
+    // checked now rather than after a long render
+    let output = match output {
+        Some(path) => {
+            let format = ImageFormat::from_path(&path, bit_depth).map_err(|e| e.to_string())?;
+            Some((path, format))
+        }
+        None => None
+    };
+    let heatmap = match heatmap {
+        Some(path) => {
+            let format = ImageFormat::from_path(&path, bit_depth).map_err(|e| e.to_string())?;
+            Some((path, format))
+        }
+        None => None
+    };
+
+    let projection = match projection.as_deref() {
+        Some("orthographic") => Some(ProjectionKind::Orthographic { height: ortho_height }),
+        Some("fisheye") => Some(ProjectionKind::Fisheye {
+            fov: fisheye_fov.unwrap_or(180.0),
+            mapping: fisheye_mapping.unwrap_or_default()
+        }),
+        Some("equirectangular") => Some(ProjectionKind::Equirectangular),
+        Some(_) => Some(ProjectionKind::Perspective),
+        None => None
+    };
+    if ortho_height.is_some() && !matches!(projection, Some(ProjectionKind::Orthographic { .. })) {
+        return Err("--ortho-height only makes sense with --projection orthographic".to_string());
+    }
+    if (fisheye_fov.is_some() || fisheye_mapping.is_some()) && !matches!(projection, Some(ProjectionKind::Fisheye { .. })) {
+        return Err("--fisheye-fov and --fisheye-mapping only make sense with --projection fisheye".to_string());
+    }
+
+    let sequence = match frames {
+        Some(frames) => {
+            if output.is_none() {
+                return Err("--frames needs --output to name the frames after".to_string());
+            }
+            if pass_samples.is_some() || time_limit.is_some() || checkpoint.is_some() {
+                return Err("--frames can't be used with --pass-spp, --time-limit or --checkpoint".to_string());
+            }
+            Some(Sequence { frames, fps: fps.unwrap_or(24.0), shutter: shutter.unwrap_or(0.5) })
+        }
+        None if fps.is_some() || shutter.is_some() || turntable => {
+            return Err("--fps, --shutter and --turntable only make sense with --frames".to_string())
+        }
+        None => None
+    };
+
+    if resume && checkpoint.is_none() {
+        return Err("--resume needs --checkpoint".to_string());
+    }
+    if pass_samples.is_none() && (time_limit.is_some() || checkpoint.is_some()) {
+        pass_samples = Some(16);
+    }
+
+    let adaptive = match threshold {
+        Some(threshold) => Some(Adaptive {
+            min_samples: min_samples.unwrap_or_else(|| samples_per_pixel.min(16)),
+            threshold,
+            max_samples: max_samples.unwrap_or(samples_per_pixel.saturating_mul(4))
+        }),
+        None if min_samples.is_some() => return Err("--min-spp only makes sense with --adaptive".to_string()),
+        None if max_samples.is_some() => return Err("--max-spp only makes sense with --adaptive".to_string()),
+        None => None
+    };
+
+    // a missing side copies the other one, so the image is square by default
+    let (width, height) = match (width, height) {
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, w),
+        (None, Some(h)) => (h, h),
+        (None, None) => (800, 800)
+    };
+
+    Ok(Command::Render(Box::new(Options {
+        scene,
+        width,
+        height,
+        samples_per_pixel,
+        adaptive,
+        max_depth,
+        output,
+        heatmap,
+        pass_samples,
+        snapshot_interval,
+        time_limit,
+        checkpoint,
+        checkpoint_interval,
+        resume,
+        projection,
+        stereo,
+        sequence,
+        turntable,
+        seed,
+        scene_seed,
+        sampler,
+        threads,
+        tile_size,
+        bvh,
+        light_sampling
+    })))
+}
+
+pub fn scene_list() -> String {
+    let name_width = SCENES.iter().map(|scene| scene.name.len()).max().unwrap_or(0);
+    SCENES
+        .iter()
+        .map(|scene| format!("{:width$}  {}", scene.name, scene.description, width = name_width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn value(flag: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("{} expects a value", flag))
+}
+
+fn number<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = self::value(flag, value)?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}: expected a non-negative integer", value, flag))
+}
+
+fn seconds(flag: &str, value: Option<String>) -> Result<Duration, String> {
+    let value = self::value(flag, value)?;
+    value
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("invalid value '{}' for {}: expected a number of seconds", value, flag))
+}
+
+fn positive_number(flag: &str, value: Option<String>) -> Result<f64, String> {
+    let value = self::value(flag, value)?;
+    match value.parse::<f64>() {
+        Ok(n) if n > 0.0 && n.is_finite() => Ok(n),
+        _ => Err(format!("invalid value '{}' for {}: expected a positive number", value, flag))
+    }
+}
+
+// <first>-<last> or just <frame>
+fn frame_range(flag: &str, value: Option<String>) -> Result<RangeInclusive<u64>, String> {
+    let value = self::value(flag, value)?;
+    let invalid = || format!("invalid value '{}' for {}: expected <first>-<last> or a frame number", value, flag);
+    let (first, last) = value.split_once('-').unwrap_or((&value, &value));
+    let first: u64 = first.parse().map_err(|_| invalid())?;
+    let last: u64 = last.parse().map_err(|_| invalid())?;
+    if last < first {
+        return Err(format!("invalid value '{}' for {}: the last frame comes before the first", value, flag));
+    }
+    Ok(first..=last)
+}
+
+fn positive<T: FromStr + PartialOrd + From<u8>>(flag: &str, value: Option<String>, min: u8) -> Result<T, String> {
+    let n: T = number(flag, value.clone())?;
+    if n < T::from(min) {
+        return Err(format!(
+            "invalid value '{}' for {}: must be at least {}", value.unwrap_or_default(), flag, min
+        ));
+    }
+    Ok(n)
+}
@@ -0,0 +1,85 @@
+pub mod map;
+pub mod sky;
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::hit::Hit;
+use crate::hit::aabb::AABB;
+use crate::hit::hit_record::HitRecord;
+use crate::ray::Ray;
+use crate::vec::{Colour, Point3, Vec3};
+
+// what a ray sees once it has left the scene: light from infinitely far away, so it only
+// depends on which way the ray goes.
+// environments with bright spots worth aiming at (a sun) are sampled directly with the
+// lights: such an environment says so with is_light and implements the other two like
+// Hit's, sample picking a direction from the uniform numbers u and pdf its density per
+// solid angle
+pub trait Environment : Send + Sync {
+    fn radiance(&self, direction: Vec3) -> Colour;
+
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    fn pdf(&self, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    fn sample(&self, _u: (f64, f64)) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+}
+
+// the same colour every way, the camera's background
+pub struct Uniform {
+    colour: Colour
+}
+
+impl Uniform {
+    pub fn new(colour: Colour) -> Uniform {
+        Uniform { colour }
+    }
+}
+
+impl Environment for Uniform {
+    fn radiance(&self, _direction: Vec3) -> Colour {
+        self.colour
+    }
+}
+
+// an environment as one of the renderer's lights, which are all shapes. it is never in the
+// world: rays only find it by missing everything, so hitting it never happens and it has no
+// size
+pub struct Light {
+    environment: Arc<dyn Environment>
+}
+
+impl Light {
+    pub fn new(environment: Arc<dyn Environment>) -> Light {
+        Light { environment }
+    }
+}
+
+impl Hit for Light {
+    fn hit(&self, _r: &Ray, _time_range: Range<f64>) -> Option<HitRecord> {
+        None
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> AABB {
+        AABB::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0))
+    }
+
+    fn is_light(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, _origin: Point3, direction: Vec3, _time: f64) -> f64 {
+        self.environment.pdf(direction)
+    }
+
+    fn random(&self, _origin: Point3, _time: f64, u: (f64, f64)) -> Vec3 {
+        self.environment.sample(u)
+    }
+}
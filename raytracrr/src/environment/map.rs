@@ -0,0 +1,230 @@
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use image::codecs::hdr::HdrDecoder;
+
+use crate::environment::Environment;
+use crate::sampler::Distribution;
+use crate::vec::{Colour, Vec3};
+use crate::Error;
+
+// an equirectangular (latitude-longitude) hdr photo of everything around a point: the same
+// layout the equirectangular camera renders, so x goes once round from behind on the left
+// to behind on the right with straight ahead (-z) in the middle, and y from straight up at
+// the top to straight down. rotation turns it about the up axis (degrees, anticlockwise seen
+// from above) and intensity scales it.
+//
+// directions are sampled in proportion to how bright their pixel is: a row of the image is
+// picked by the total of its pixels, then a pixel in it. rows near the poles cover less of
+// the sphere than the horizon, so their pixels count less. inside a pixel the direction is
+// uniform over the piece of sphere it covers (uniform in y, not in latitude), so the density
+// is the same all over the pixel and pdf gives it exactly
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Colour>, // rows from the top
+    rotation: (f64, f64), // sine and cosine
+    intensity: f64,
+    rows: Distribution,
+    columns: Vec<Distribution> // of every row
+}
+
+impl EnvironmentMap {
+    // pixels has width * height colours, rows from the top
+    pub fn new(width: usize, height: usize, pixels: Vec<Colour>, rotation: f64, intensity: f64) -> EnvironmentMap {
+        let columns: Vec<Distribution> = pixels
+            .chunks(width)
+            .enumerate()
+            .map(|(j, row)| {
+                let band = band(j, height);
+                Distribution::new(row.iter().map(|colour| colour.luminance().max(0.0) * band).collect())
+            })
+            .collect();
+        let rows = Distribution::new(columns.iter().map(|row| row.total()).collect());
+
+        EnvironmentMap {
+            width,
+            height,
+            pixels,
+            rotation: rotation.to_radians().sin_cos(),
+            intensity,
+            rows,
+            columns
+        }
+    }
+
+    // reads a .hdr or .exr file
+    pub fn load(path: impl AsRef<Path>, rotation: f64, intensity: f64) -> Result<EnvironmentMap, Error> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        let (width, height, pixels) = match extension.as_str() {
+            "hdr" => read_hdr(path),
+            "exr" => read_exr(path),
+            _ => Err("environment maps are .hdr or .exr images".into())
+        }.map_err(|e| format!("can't load environment map {}: {}", path.display(), e))?;
+
+        if width == 0 || height == 0 {
+            return Err(format!("environment map {} is empty", path.display()).into());
+        }
+        Ok(EnvironmentMap::new(width, height, pixels, rotation, intensity))
+    }
+
+    // the pixel direction (a unit vector) falls in
+    fn pixel(&self, direction: Vec3) -> (usize, usize) {
+        // undo the rotation
+        let (sin, cos) = self.rotation;
+        let x = cos * direction.x() - sin * direction.z();
+        let z = sin * direction.x() + cos * direction.z();
+
+        let longitude = x.atan2(-z);
+        let latitude = direction.y().clamp(-1.0, 1.0).asin();
+        let u = longitude / (2.0 * PI) + 0.5;
+        let v = 0.5 - latitude / PI;
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = ((v * self.height as f64) as usize).min(self.height - 1);
+        (i, j)
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: Vec3) -> Colour {
+        let (i, j) = self.pixel(direction.normalized());
+        self.intensity * self.pixels[j * self.width + i]
+    }
+
+    fn is_light(&self) -> bool {
+        self.intensity > 0.0 && self.rows.total() > 0.0
+    }
+
+    fn pdf(&self, direction: Vec3) -> f64 {
+        let (i, j) = self.pixel(direction.normalized());
+        // the chance of picking the pixel (the row's pdf / height times the column's pdf /
+        // width) over the solid angle it covers (2 pi band / width)
+        self.rows.pdf(j) * self.columns[j].pdf(i) / (2.0 * PI * self.height as f64 * band(j, self.height))
+    }
+
+    fn sample(&self, u: (f64, f64)) -> Vec3 {
+        let (v, j) = self.rows.sample(u.0);
+        let (u, _) = self.columns[j].sample(u.1);
+
+        // how far down the row v is, as a height between the row's top and bottom
+        let (top, bottom) = (row_y(j, self.height), row_y(j + 1, self.height));
+        let along = (v * self.height as f64 - j as f64).clamp(0.0, 1.0);
+        let y = top + along * (bottom - top);
+        let across = (1.0 - y * y).max(0.0).sqrt();
+
+        let longitude = (u - 0.5) * 2.0 * PI;
+        let (sin_long, cos_long) = longitude.sin_cos();
+        let x = sin_long * across;
+        let z = -cos_long * across;
+
+        let (sin, cos) = self.rotation;
+        Vec3::new(cos * x + sin * z, y, -sin * x + cos * z)
+    }
+}
+
+// the height (y of the direction) of the top of row j
+fn row_y(j: usize, height: usize) -> f64 {
+    (PI * j as f64 / height as f64).cos()
+}
+
+// the height of the band of the sphere row j covers, its solid angle over 2 pi
+fn band(j: usize, height: usize) -> f64 {
+    row_y(j, height) - row_y(j + 1, height)
+}
+
+fn read_hdr(path: &Path) -> Result<(usize, usize, Vec<Colour>), Error> {
+    let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+    let metadata = decoder.metadata();
+    let pixels = decoder
+        .read_image_hdr()?
+        .into_iter()
+        .map(|pixel| Colour::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64))
+        .collect();
+    Ok((metadata.width as usize, metadata.height as usize, pixels))
+}
+
+fn read_exr(path: &Path) -> Result<(usize, usize, Vec<Colour>), Error> {
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| (resolution.width(), vec![Colour::new(0.0, 0.0, 0.0); resolution.area()]),
+        |(width, pixels): &mut (usize, Vec<Colour>), position, (r, g, b, _): (f32, f32, f32, f32)| {
+            pixels[position.y() * *width + position.x()] = Colour::new(r as f64, g as f64, b as f64);
+        }
+    )?;
+    let size = image.layer_data.size;
+    let (_, pixels) = image.layer_data.channel_data.pixels;
+    Ok((size.width(), size.height(), pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::random;
+
+    // a small map, so its rows are wide: the top one reaches from the pole down to 45 degrees
+    fn map() -> EnvironmentMap {
+        let mut rng = random::seeded(5);
+        let (width, height) = (6, 4);
+        let pixels = (0..width * height)
+            .map(|_| {
+                let grey = rng.gen_range(0.1..2.0);
+                Colour::new(grey, grey, grey)
+            })
+            .collect();
+        EnvironmentMap::new(width, height, pixels, 30.0, 1.0)
+    }
+
+    // sampled directions land in every part of the sphere as often as pdf says they should:
+    // the rows are cut into slices of equal y, which would catch a density that is only right
+    // in the middle of a pixel
+    #[test]
+    fn pdf_matches_the_samples() {
+        let map = map();
+        let slices = 4;
+        let n = 400000;
+        let mut counts = vec![0u32; map.width * map.height * slices];
+        let mut rng = random::seeded(9);
+        for _ in 0..n {
+            let direction = map.sample((rng.gen(), rng.gen()));
+            assert!((direction.length() - 1.0).abs() < 1e-9);
+            let (i, j) = map.pixel(direction);
+            let (top, bottom) = (row_y(j, map.height), row_y(j + 1, map.height));
+            let slice = (((top - direction.y()) / (top - bottom) * slices as f64) as usize).min(slices - 1);
+            counts[(j * map.width + i) * slices + slice] += 1;
+        }
+
+        for j in 0..map.height {
+            let (top, bottom) = (row_y(j, map.height), row_y(j + 1, map.height));
+            for i in 0..map.width {
+                for slice in 0..slices {
+                    // pdf in the middle of the slice, times the slice's solid angle
+                    let y = top - (slice as f64 + 0.5) / slices as f64 * (top - bottom);
+                    let longitude = ((i as f64 + 0.5) / map.width as f64 - 0.5) * 2.0 * PI;
+                    let across = (1.0 - y * y).sqrt();
+                    let local = Vec3::new(longitude.sin() * across, y, -longitude.cos() * across);
+                    let (sin, cos) = map.rotation;
+                    let direction = Vec3::new(cos * local.x() + sin * local.z(), y, -sin * local.x() + cos * local.z());
+                    assert_eq!(map.pixel(direction), (i, j));
+
+                    let solid_angle = 2.0 * PI / map.width as f64 * (top - bottom) / slices as f64;
+                    let expected = map.pdf(direction) * solid_angle * n as f64;
+                    let found = counts[(j * map.width + i) * slices + slice] as f64;
+                    assert!(
+                        (found - expected).abs() < 5.0 * expected.sqrt(),
+                        "pixel {} {} slice {}: {} samples, expected {}", i, j, slice, found, expected
+                    );
+                }
+            }
+        }
+    }
+}
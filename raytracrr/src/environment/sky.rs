@@ -0,0 +1,201 @@
+use std::f64::consts::PI;
+
+use crate::camera::exposure::blackbody;
+use crate::environment::Environment;
+use crate::onb::Onb;
+use crate::sampler;
+use crate::vec::{Colour, Vec3};
+
+// a clear daytime sky and its sun (preetham, shirley and smits, "a practical analytic model
+// for daylight"): the sky's brightness and colour everywhere follow from where the sun is
+// and the turbidity, how hazy the air is (2 is very clear, 3 a clear day, 6 and up hazy and
+// whiter). below the horizon the sky carries on as it is at the horizon, the scene is
+// expected to have a ground of its own.
+//
+// the sun is at elevation degrees above the horizon and azimuth degrees round from straight
+// ahead (-z) towards the right (+x), as a compass turns. radiance is in cd/m², as in
+// camera::exposure: several thousand for the sky, over a billion for the sun, so without an
+// exposure intensity has to bring it down to something like 1e-4.
+//
+// the sun is a disk of its real size, half a degree across, reddened by the air the lower it
+// is. it is far brighter than the rest of the sky while covering a tiny part of it, so light
+// sampling aims at it as often as its share of the light: the rest of the time at any
+// direction (the sky is smooth, bounces find it well enough)
+pub struct Sky {
+    sun: Vec3, // towards the sun
+    sun_radiance: Colour,
+    cos_sun_radius: f64,
+    sun_probability: f64, // of sample aiming at the sun
+    // perez distributions of luminance and the x and y of chromaticity, and their values
+    // straight up
+    perez: [Perez; 3],
+    zenith: [f64; 3],
+    intensity: f64
+}
+
+// the sun's angular radius (radians) and its luminance before the atmosphere, from the
+// illuminance it gives (about 133000 lux) over the solid angle it covers
+const SUN_RADIUS: f64 = 0.004654;
+const SUN_LUMINANCE: f64 = 1.96e9;
+
+impl Sky {
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64, intensity: f64) -> Sky {
+        let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+        let sun = Vec3::new(azimuth.sin() * elevation.cos(), elevation.sin(), -azimuth.cos() * elevation.cos());
+        let theta_sun = PI / 2.0 - elevation; // from straight up
+        let t = turbidity;
+
+        let perez = [
+            Perez::new(theta_sun, [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703
+            ]),
+            Perez::new(theta_sun, [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452
+            ]),
+            Perez::new(theta_sun, [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529
+            ])
+        ];
+
+        // straight up: luminance (kcd/m² in the paper) and chromaticity
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192) * 1000.0;
+        let cubic = |a: [f64; 4]| ((a[0] * theta_sun + a[1]) * theta_sun + a[2]) * theta_sun + a[3];
+        let x = t * t * cubic([0.00166, -0.00375, 0.00209, 0.0])
+            + t * cubic([-0.02903, 0.06377, -0.03202, 0.00394])
+            + cubic([0.11693, -0.21196, 0.06052, 0.25886]);
+        let y = t * t * cubic([0.00275, -0.00610, 0.00317, 0.0])
+            + t * cubic([-0.04214, 0.08970, -0.04153, 0.00516])
+            + cubic([0.15346, -0.26756, 0.06670, 0.26688]);
+
+        let mut sky = Sky {
+            sun,
+            sun_radiance: SUN_LUMINANCE * blackbody(5778.0) * transmittance(theta_sun, t),
+            cos_sun_radius: SUN_RADIUS.cos(),
+            sun_probability: 0.0,
+            perez,
+            zenith: [luminance, x, y],
+            intensity
+        };
+        sky.sun_probability = sky.sun_share();
+        sky
+    }
+
+    // the sky without the sun
+    fn sky_radiance(&self, direction: Vec3) -> Colour {
+        // the formulas go wrong towards and past the horizon
+        let cos_theta = direction.y().max(0.01);
+        let cos_gamma = direction.dot(self.sun).clamp(-1.0, 1.0);
+        let [luminance, x, y] = [0, 1, 2].map(|i| self.zenith[i] * self.perez[i].relative(cos_theta, cos_gamma));
+        Colour::from_xyy(x, y, luminance)
+    }
+
+    // the sun's share of all the light from sun and sky, roughly, with the sky's taken from
+    // a coarse grid of directions
+    fn sun_share(&self) -> f64 {
+        let (n, m) = (32, 16);
+        let mut sky = 0.0;
+        for i in 0..n {
+            for j in 0..m {
+                let direction = sampler::sphere(((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / m as f64));
+                sky += self.sky_radiance(direction).luminance().max(0.0);
+            }
+        }
+        let sky = sky / (n * m) as f64 * 4.0 * PI;
+        let sun = self.sun_radiance.luminance().max(0.0) * 2.0 * PI * (1.0 - self.cos_sun_radius);
+        if sun + sky > 0.0 {
+            (sun / (sun + sky)).clamp(0.1, 0.9)
+        } else {
+            0.5
+        }
+    }
+}
+
+impl Environment for Sky {
+    fn radiance(&self, direction: Vec3) -> Colour {
+        let direction = direction.normalized();
+        let mut radiance = self.sky_radiance(direction);
+        if direction.dot(self.sun) >= self.cos_sun_radius {
+            radiance += self.sun_radiance;
+        }
+        self.intensity * radiance
+    }
+
+    fn is_light(&self) -> bool {
+        self.intensity > 0.0
+    }
+
+    fn pdf(&self, direction: Vec3) -> f64 {
+        let mut pdf = (1.0 - self.sun_probability) / (4.0 * PI);
+        if direction.normalized().dot(self.sun) >= self.cos_sun_radius {
+            pdf += self.sun_probability / (2.0 * PI * (1.0 - self.cos_sun_radius));
+        }
+        pdf
+    }
+
+    fn sample(&self, u: (f64, f64)) -> Vec3 {
+        if u.0 < self.sun_probability {
+            // uniformly over the cone of the sun
+            let r1 = u.0 / self.sun_probability;
+            let z = 1.0 + u.1 * (self.cos_sun_radius - 1.0);
+            let phi = 2.0 * PI * r1;
+            let sin_theta = (1.0 - z * z).sqrt();
+            Onb::new(self.sun).local(Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z))
+        } else {
+            sampler::sphere(((u.0 - self.sun_probability) / (1.0 - self.sun_probability), u.1))
+        }
+    }
+}
+
+// perez et al.'s sky distribution, how the sky is brighter towards the horizon (a, b) and
+// around the sun (c, d, e), over what it is straight up
+struct Perez {
+    coefficients: [f64; 5],
+    zenith: f64 // the distribution straight up, with the sun theta_sun from it
+}
+
+impl Perez {
+    fn new(theta_sun: f64, coefficients: [f64; 5]) -> Perez {
+        let mut perez = Perez { coefficients, zenith: 1.0 };
+        perez.zenith = perez.f(1.0, theta_sun.cos());
+        perez
+    }
+
+    // theta is from straight up, gamma from the sun
+    fn f(&self, cos_theta: f64, cos_gamma: f64) -> f64 {
+        let [a, b, c, d, e] = self.coefficients;
+        let gamma = cos_gamma.acos();
+        (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+    }
+
+    fn relative(&self, cos_theta: f64, cos_gamma: f64) -> f64 {
+        self.f(cos_theta, cos_gamma) / self.zenith
+    }
+}
+
+// how much of the sunlight gets through the air, for red, green and blue (680, 550 and 440
+// nm): scattered by the air itself (rayleigh) and by haze (aerosols, more with turbidity),
+// through more air the lower the sun is (kasten's optical mass)
+fn transmittance(theta_sun: f64, turbidity: f64) -> Colour {
+    let degrees = theta_sun.to_degrees().min(93.0);
+    let mass = 1.0 / (theta_sun.cos().max(0.0) + 0.15 * (93.885 - degrees).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let through = |wavelength: f64| {
+        let rayleigh = 0.008735 * wavelength.powf(-4.08);
+        let aerosol = beta * wavelength.powf(-1.3);
+        (-(rayleigh + aerosol) * mass).exp()
+    };
+    Colour::new(through(0.680), through(0.550), through(0.440))
+}
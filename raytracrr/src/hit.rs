@@ -8,15 +8,20 @@ pub mod quad;
 pub mod rect;
 pub mod block;
 pub mod translate;
-pub mod rotate;
+pub mod transform;
+pub mod instance;
+pub mod animated;
 pub mod constant_medium;
+pub mod triangle;
+pub mod mesh;
+pub mod point_light;
+pub mod spot_light;
+pub mod directional_light;
 
-use super::vec::{Point3, Vec3};
 use super::ray::{Ray};
-use super::material::{Scatter};
+use super::vec::{Colour, Point3, Vec3};
 use aabb::{AABB};
 use std::ops::Range;
-use std::sync::{Arc};
 use hit_record::{HitRecord};
 
 pub trait Hit : Send + Sync {
@@ -30,4 +35,46 @@ pub trait Hit : Send + Sync {
     // recall that some objects may be animated. 
     // Such objects should return their bounds over the entire range of motion, from time=0 to time=1.
     fn bounding_box(&self, time_range: Range<f64>) -> AABB;
+
+    // shapes with an emitting material can be sampled directly by the renderer
+    // (next event estimation) instead of waiting for a bounce to stumble on them.
+    // such a shape says so with is_light and implements the other two:
+    // random picks a direction from origin towards a random point on the shape, chosen by the
+    // uniform numbers u from the sampler,
+    // pdf_value is the density (per solid angle, seen from origin) of random picking direction,
+    // 0 if a ray that way misses the shape.
+    // both are for the shape as it is at time, for shapes that move.
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3, _time: f64) -> f64 {
+        0.0
+    }
+
+    fn random(&self, _origin: Point3, _time: f64, _u: (f64, f64)) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    // lights that are a point or a single direction (point_light.rs and the like) are lights
+    // too, but can't be hit or picked by density: shadow rays go straight for them instead,
+    // with the direction, distance and light arriving that illuminate gives. None if no
+    // light reaches origin
+    fn illuminate(&self, _origin: Point3, _time: f64) -> Option<Illumination> {
+        None
+    }
+
+    // whether this is one of those lights and nothing else: never hit, and nowhere a bvh could
+    // put it (a direction isn't anywhere at all), so the renderer keeps it out of the bvh and
+    // only among the lights
+    fn is_punctual(&self) -> bool {
+        false
+    }
+}
+
+pub struct Illumination {
+    pub direction: Vec3, // unit vector from origin towards the light
+    pub distance: f64, // infinite for lights that are only a direction
+    // the light arriving, for a surface facing the light: illuminance, lux in physical units
+    pub incoming: Colour
 }
@@ -1,11 +1,12 @@
-use std::{ops::Range, mem, cmp::Ordering};
+use std::{ops::Range, mem};
 
-use crate::{vec::Vec3, ray::Ray};
+use crate::{vec::{Point3, Vec3}, ray::Ray};
 
 // minimum.x, maximum.x represents the x range
 // minimum.y, maximum.y represents the y range, etc
 // when given 2 bounding boxes, the minimum.x is the min(minimum1.x, minimum2.x), etc
 #[derive(Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub struct AABB {
     minimum: Vec3, 
     maximum: Vec3
@@ -24,11 +25,25 @@ impl AABB {
         let Vec3 { e: [minx, miny, minz] } = minimum;
 
         AABB {
-            minimum: Vec3::new(minx + delta, miny + delta, minz + delta), 
+            minimum: Vec3::new(minx - delta, miny - delta, minz - delta), 
             maximum: Vec3::new(maxx + delta, maxy + delta, maxz + delta)
         }
     }
 
+    // smallest box around the points, padded so flat shapes (a triangle or quad lying in an
+    // axis plane) still have some thickness for rays to hit
+    pub fn from_points(points: &[Point3]) -> AABB {
+        let mut minimum = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut maximum = Vec3::new(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY);
+        for p in points {
+            for axis in 0..3 {
+                minimum[axis] = minimum[axis].min(p[axis]);
+                maximum[axis] = maximum[axis].max(p[axis]);
+            }
+        }
+        AABB::new_pad(minimum, maximum)
+    }
+
     pub fn surrounding_box(bbox1: AABB, bbox2: AABB) -> AABB {
         let Vec3 { e: [bb1minx, bb1miny, bb1minz] } = bbox1.minimum;
         let Vec3 { e: [bb1maxx, bb1maxy, bb1maxz] } = bbox1.maximum;
@@ -36,7 +51,7 @@ impl AABB {
         let Vec3 { e: [bb2maxx, bb2maxy, bb2maxz] } = bbox2.maximum;
 
         let minimum = Vec3::new(bb1minx.min(bb2minx), bb1miny.min(bb2miny), bb1minz.min(bb2minz));
-        let maximum = Vec3::new(bb1maxx.min(bb2maxx), bb1maxy.min(bb2maxy), bb1maxz.min(bb2maxz));
+        let maximum = Vec3::new(bb1maxx.max(bb2maxx), bb1maxy.max(bb2maxy), bb1maxz.max(bb2maxz));
 
         AABB::new(minimum, maximum)
     }
@@ -49,6 +64,15 @@ impl AABB {
         self.maximum
     }
 
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let Vec3 { e: [dx, dy, dz] } = self.maximum - self.minimum;
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
     pub fn hit(&self, r: &Ray, time_range: Range<f64>) -> bool {
         let mut tmin = time_range.start;
         let mut tmax = time_range.end;
@@ -73,7 +97,7 @@ pub fn hit_1d(minimum: f64, maximum: f64, origin: f64, direction: f64, tmin: &mu
     }
 
     *tmin = t0.max(*tmin);
-    *tmax = t1.max(*tmax);
+    *tmax = t1.min(*tmax);
 
     tmin < tmax
 }
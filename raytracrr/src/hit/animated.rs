@@ -0,0 +1,120 @@
+use std::{sync::Arc, ops::Range};
+
+use crate::{animation::Animation, ray::Ray, vec::{Point3, Vec3}};
+use super::{aabb::AABB, transform::Affine, Hit, Illumination, hit_record::HitRecord};
+
+/*
+ * an object moved by an Animation: a Transform whose matrix is worked out for the time of
+ * every ray, so the object is blurred along however it moves while the shutter is open.
+ * at an instant where the transform squashes the object flat (a scale passing through 0)
+ * there is nothing to hit.
+ */
+
+// the most the rotation turns between two of the poses the bounding box is made from
+const MAX_BOX_STEP: f64 = 2.0 * std::f64::consts::PI / 180.0;
+
+pub struct Animated {
+    object: Arc<dyn Hit>,
+    animation: Animation
+}
+
+impl Animated {
+    pub fn new(object: Arc<dyn Hit>, animation: Animation) -> Animated {
+        Animated { object, animation }
+    }
+
+    pub fn new_arc(object: Arc<dyn Hit>, animation: Animation) -> Arc<Box<dyn Hit>> {
+        Arc::new(Box::new(Animated::new(object, animation)))
+    }
+
+    fn affine(&self, time: f64) -> Option<Affine> {
+        Affine::new(self.animation.matrix(time))
+    }
+}
+
+impl Hit for Animated {
+    fn hit(&self, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
+        self.affine(r.time)?.hit(self.object.as_ref(), r, time_range)
+    }
+
+    // the boxes of the object at the start and end of the shutter, at every key in between
+    // and at enough poses between those that the rotation only turns a little from one to the
+    // next. a point's path between two poses strays from the straight line joining them by
+    // less than its distance from the object's origin times half the angle turned, so the
+    // boxes are grown by that much
+    fn bounding_box(&self, time_range: Range<f64>) -> AABB {
+        let object_box = self.object.bounding_box(time_range.clone());
+        let (b_min, b_max) = (object_box.get_minimum(), object_box.get_maximum());
+        let corner_distance = |scale: Vec3| {
+            let furthest = Vec3::new(
+                (b_min.x() * scale.x()).abs().max((b_max.x() * scale.x()).abs()),
+                (b_min.y() * scale.y()).abs().max((b_max.y() * scale.y()).abs()),
+                (b_min.z() * scale.z()).abs().max((b_max.z() * scale.z()).abs())
+            );
+            furthest.length()
+        };
+
+        let mut times = vec![time_range.start];
+        times.extend(
+            self.animation
+                .keys()
+                .iter()
+                .map(|key| key.time)
+                .filter(|&time| time > time_range.start && time < time_range.end)
+        );
+        times.push(time_range.end);
+
+        let mut bounding_box: Option<AABB> = None;
+        for pair in times.windows(2) {
+            let (start, end) = (self.animation.at(pair[0]), self.animation.at(pair[1]));
+            let angle = start.rotation.angle_to(end.rotation);
+            let steps = (angle / MAX_BOX_STEP).ceil().max(1.0) as usize;
+            let pad = if angle > 0.0 {
+                corner_distance(start.scale).max(corner_distance(end.scale)) * angle / steps as f64 / 2.0
+            } else {
+                0.0
+            };
+            let pad = Vec3::new(pad, pad, pad);
+
+            for step in 0..=steps {
+                let time = pair[0] + (pair[1] - pair[0]) * step as f64 / steps as f64;
+                let pose = match self.affine(time) {
+                    Some(affine) => affine.bounding_box(&object_box),
+                    None => continue // flat for an instant, the poses around it cover it
+                };
+                let pose = AABB::new(pose.get_minimum() - pad, pose.get_maximum() + pad);
+                bounding_box = Some(match bounding_box {
+                    Some(bounding_box) => AABB::surrounding_box(bounding_box, pose),
+                    None => pose
+                });
+            }
+        }
+        bounding_box.unwrap_or_else(|| AABB::new(Vec3::default(), Vec3::default()))
+    }
+
+    fn is_light(&self) -> bool {
+        self.object.is_light()
+    }
+
+    fn is_punctual(&self) -> bool {
+        self.object.is_punctual()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        match self.affine(time) {
+            Some(affine) => affine.pdf_value(self.object.as_ref(), origin, direction, time),
+            None => 0.0
+        }
+    }
+
+    fn random(&self, origin: Point3, time: f64, u: (f64, f64)) -> Vec3 {
+        match self.affine(time) {
+            Some(affine) => affine.random(self.object.as_ref(), origin, time, u),
+            None => Vec3::new(1.0, 0.0, 0.0)
+        }
+    }
+
+    fn illuminate(&self, origin: Point3, time: f64) -> Option<Illumination> {
+        self.affine(time)?.illuminate(self.object.as_ref(), origin, time)
+    }
+}
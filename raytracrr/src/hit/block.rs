@@ -25,13 +25,14 @@ impl Block {
         let Vec3{e: [p0x, p0y, p0z]} = b_min;
         let Vec3{e: [p1x, p1y, p1z]} = b_max;
 
-        let mut sides: Vec<Arc<dyn Hit>> = Vec::new();
-        sides.push(Arc::new(Rect::new(p0x..p1x, p0y..p1y, p1z, 0, Arc::clone(&material))));
-        sides.push(Arc::new(Rect::new(p0x..p1x, p0y..p1y, p0z, 0, Arc::clone(&material))));
-        sides.push(Arc::new(Rect::new(p0x..p1x, p0z..p1z, p1y, 1, Arc::clone(&material))));
-        sides.push(Arc::new(Rect::new(p0x..p1x, p0z..p1z, p0y, 1, Arc::clone(&material))));
-        sides.push(Arc::new(Rect::new(p0y..p1y, p0z..p1z, p1x, 2, Arc::clone(&material))));
-        sides.push(Arc::new(Rect::new(p0y..p1y, p0z..p1z, p0x, 2, material)));
+        let sides: Vec<Arc<dyn Hit>> = vec![
+            Arc::new(Rect::new(p0x..p1x, p0y..p1y, p1z, 0, Arc::clone(&material))),
+            Arc::new(Rect::new(p0x..p1x, p0y..p1y, p0z, 0, Arc::clone(&material))),
+            Arc::new(Rect::new(p0x..p1x, p0z..p1z, p1y, 1, Arc::clone(&material))),
+            Arc::new(Rect::new(p0x..p1x, p0z..p1z, p0y, 1, Arc::clone(&material))),
+            Arc::new(Rect::new(p0y..p1y, p0z..p1z, p1x, 2, Arc::clone(&material))),
+            Arc::new(Rect::new(p0y..p1y, p0z..p1z, p0x, 2, material)),
+        ];
 
         assert_eq!(sides.len(), 6);
 
@@ -53,7 +54,7 @@ impl Hit for Block {
         self.sides.hit(r, time_range)
     }
 
-    fn bounding_box(&self, time_range: std::ops::Range<f64>) -> AABB {
+    fn bounding_box(&self, _time_range: std::ops::Range<f64>) -> AABB {
         AABB::new(self.b_min, self.b_max)
     }
 }
@@ -1,129 +1,499 @@
-use std::cmp::Ordering;
-use std::mem;
+use std::fmt;
 use std::sync::Arc;
 use std::ops::Range;
 
-use rand::{thread_rng, Rng};
-
 use crate::hit::{Hit, HitRecord};
 use crate::hit::aabb::{AABB};
 use crate::ray::Ray;
+use crate::vec::{Point3, Vec3};
 use super::world::World;
 
-// a hittable — just like lists of hittables. 
-// It’s really a container, but it can respond to the query “does this ray hit you?”. 
-// design question: 
-// 1. do we have two classes, one for the tree, and one for the nodes in the tree; 
-// 2. do we have just one class and have the root just be a node we point to. 
-// hit function is pretty straightforward: check whether the box for the node is hit, 
+// a hittable — just like lists of hittables.
+// It’s really a container, but it can respond to the query “does this ray hit you?”.
+// hit function is pretty straightforward: check whether the box for the node is hit,
 // and if so, check the children and sort out any details.
+//
+// the tree is kept flat in one vec instead of nodes pointing at nodes: the left child of a
+// node is stored right after it and the node remembers where its right child is, so walking
+// down the tree mostly reads memory that was just read. the primitives are reordered so
+// that every leaf owns a contiguous run of them.
+//
+// splits are chosen with the surface area heuristic: the chance a ray that hits a box also
+// hits a box inside it is roughly the ratio of their surface areas, so the expected cost of
+// a split is
+//
+//   traversal + intersection * (area(left) * count(left) + area(right) * count(right)) / area(parent)
+//
+// centroids are dropped into BINS buckets along each axis and every bucket boundary is
+// tried as a split; the cheapest one wins, or no split at all when testing every primitive
+// in the node is cheaper. the build only depends on the boxes and their order, so the same
+// scene always builds the same tree.
 
-pub struct BVH {
-    left: Arc<Box<dyn Hit>>,
-    right: Arc<Box<dyn Hit>>,
-    bounding_box: AABB
+const BINS: usize = 16;
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+const MAX_LEAF_SIZE: usize = 4; // nodes above this are always split if they can be
+
+// past this depth splits fall back to the median, which halves every node, so no tree is
+// deeper than 2 * MAX_SAH_DEPTH and traversal can use a fixed size stack
+const MAX_SAH_DEPTH: usize = 32;
+const STACK_SIZE: usize = 2 * MAX_SAH_DEPTH + 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Split {
+    Sah,
+    Median // at the middle primitive along the longest axis, the old builder minus the random axis
 }
 
-impl BVH {
-    // as long as the list of objects in a bvh_node gets divided into two sub-lists, the hit function will work. 
-    // at each node split the list along one axis
-
-    // 1. randomly choose an axis
-    // 2. sort the primitives (using std::sort)
-    // 3.put half in each subtree
-    // when list coming in is two elements, put one in each subtree and end the recursion. 
-    // traversal algorithm should be smooth and not have to check for null pointers, 
-    // so if just have one element, duplicate it in each subtree. 
-    // following code uses three methods — box_x_compare, box_y_compare_, and box_z_compare.
-    pub fn new(objects: &mut World, time_range: Range<f64>) -> BVH {
-        Self::from_range(&mut objects.to_owned(), time_range)
-    }
-
-    pub fn from_range(objects: &mut World, time_range: Range<f64>) -> BVH {
-        let axis = thread_rng().gen_range(0..=2);
-        let comparator = match axis {
-            0 => box_x_compare,
-            1 => box_y_compare,
-            2 => box_z_compare,
-            _ => unreachable!(),
+#[derive(Clone, Debug, Default)]
+pub struct BuildStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    pub depth: usize, // edges from the root to the deepest leaf
+    pub sah_cost: f64 // expected cost of a ray that hits the root box, in intersection tests
+}
+
+impl fmt::Display for BuildStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "{} nodes, {} leaves, depth {}, sah cost {:.2}",
+            self.nodes, self.leaves, self.depth, self.sah_cost
+        )
+    }
+}
+
+// count > 0: a leaf holding primitives [offset, offset + count)
+// count == 0: an interior node split along axis, left child at index + 1 and right child at offset
+struct Node {
+    bounding_box: AABB,
+    offset: u32,
+    count: u32,
+    axis: u8
+}
+
+// the tree on its own, over primitives known only by their boxes.
+// BVH uses it for hittables and Mesh for triangles.
+pub struct LinearBVH {
+    nodes: Vec<Node>,
+    stats: BuildStats
+}
+
+impl LinearBVH {
+    // returns the tree and the order the primitives have to be stored in: leaves refer to
+    // position i of that order, i.e. primitive order[i] of boxes
+    pub fn build(boxes: &[AABB], split: Split) -> (LinearBVH, Vec<usize>) {
+        assert!(boxes.len() < u32::MAX as usize, "too many primitives for a bvh");
+
+        let mut builder = Builder {
+            boxes,
+            centroids: boxes.iter().map(AABB::centroid).collect(),
+            split,
+            nodes: Vec::with_capacity(2 * boxes.len() / MAX_LEAF_SIZE + 1),
+            depth: 0
         };
-        let (left, right) = match objects.len() {
-            0 => unreachable!(),
-            1 => {
-                let left = Arc::clone(objects.first().unwrap());
-                let right = Arc::clone(objects.last().unwrap());
-                (left, right)
+        let mut order: Vec<usize> = (0..boxes.len()).collect();
+        if !boxes.is_empty() {
+            builder.build(&mut order, 0, 0);
+        }
+
+        let nodes = builder.nodes;
+        let depth = builder.depth;
+        let stats = match nodes.first() {
+            Some(root) => {
+                let root_area = root.bounding_box.surface_area().max(f64::MIN_POSITIVE);
+                let sah_cost = nodes
+                    .iter()
+                    .map(|node| {
+                        let cost = if node.count == 0 {
+                            TRAVERSAL_COST
+                        } else {
+                            INTERSECTION_COST * node.count as f64
+                        };
+                        cost * node.bounding_box.surface_area() / root_area
+                    })
+                    .sum();
+                BuildStats {
+                    nodes: nodes.len(),
+                    leaves: nodes.iter().filter(|node| node.count > 0).count(),
+                    depth,
+                    sah_cost
+                }
             }
-            2 => {
-                let mut left = Arc::clone(objects.first().unwrap());
-                let mut right = Arc::clone(objects.last().unwrap());
-                if let Ordering::Greater = comparator(&left, &right) {
-                    mem::swap(&mut left, &mut right)
+            None => BuildStats::default()
+        };
+
+        (LinearBVH { nodes, stats }, order)
+    }
+
+    pub fn stats(&self) -> &BuildStats {
+        &self.stats
+    }
+
+    pub fn bounding_box(&self) -> AABB {
+        match self.nodes.first() {
+            Some(root) => root.bounding_box.clone(),
+            None => AABB::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0))
+        }
+    }
+
+    // calls hit for the primitives in every leaf the ray reaches, with the range shrunk to
+    // the closest hit so far. hit returns the t of its hit and whatever it wants back, the
+    // value of the closest hit is returned.
+    pub fn hit<T>(&self, r: &Ray, time_range: Range<f64>, mut hit: impl FnMut(usize, Range<f64>) -> Option<(f64, T)>) -> Option<T> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest = None;
+        let mut closest_t = time_range.end;
+
+        let mut stack = [0; STACK_SIZE];
+        let mut size = 1;
+        while size > 0 {
+            size -= 1;
+            let index = stack[size];
+            let node = &self.nodes[index];
+            if !node.bounding_box.hit(r, time_range.start..closest_t) {
+                continue;
+            }
+
+            if node.count == 0 {
+                // the left child has the smaller centroids, so it's nearer when the ray goes
+                // up the split axis. visiting the near child first finds the closest hit
+                // sooner, and then whole far subtrees are skipped by their boxes.
+                let (near, far) = if r.direction()[node.axis as usize] < 0.0 {
+                    (node.offset as usize, index + 1)
+                } else {
+                    (index + 1, node.offset as usize)
+                };
+                stack[size] = far;
+                stack[size + 1] = near;
+                size += 2;
+                continue;
+            }
+
+            let offset = node.offset as usize;
+            for i in offset..offset + node.count as usize {
+                if let Some((t, value)) = hit(i, time_range.start..closest_t) {
+                    closest_t = t;
+                    closest = Some(value);
                 }
-                (left, right)
             }
-            len => {
-                objects.sort_unstable_by(comparator);
-                let mid = len / 2;
-                let left: Arc<Box<dyn Hit>> = Arc::new(
-                    Box::new(
-                        Self::from_range(&mut objects[..mid].to_vec(), time_range.clone())
-                    )
-                );
-                let right: Arc<Box<dyn Hit>> = Arc::new(
-                    Box::new(
-                        Self::from_range(&mut objects[mid..].to_vec(), time_range.clone())
-                    )
-                );
-                (left, right)
+        }
+
+        closest
+    }
+}
+
+struct Builder<'a> {
+    boxes: &'a [AABB],
+    centroids: Vec<Point3>,
+    split: Split,
+    nodes: Vec<Node>,
+    depth: usize
+}
+
+impl Builder<'_> {
+    // builds the subtree over order (which starts at position offset) and returns its index
+    fn build(&mut self, order: &mut [usize], offset: usize, depth: usize) -> usize {
+        let bounding_box = order
+            .iter()
+            .map(|&i| self.boxes[i].clone())
+            .reduce(AABB::surrounding_box)
+            .unwrap();
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            bounding_box: bounding_box.clone(),
+            offset: offset as u32,
+            count: order.len() as u32,
+            axis: 0
+        });
+        self.depth = self.depth.max(depth);
+
+        if order.len() == 1 {
+            return index;
+        }
+
+        let mut low = self.centroids[order[0]];
+        let mut high = low;
+        for &i in order.iter() {
+            let c = self.centroids[i];
+            for axis in 0..3 {
+                low[axis] = low[axis].min(c[axis]);
+                high[axis] = high[axis].max(c[axis]);
             }
+        }
+
+        let split = match self.split {
+            Split::Sah if depth < MAX_SAH_DEPTH => self.sah_split(order, &bounding_box, low, high),
+            _ if order.len() <= MAX_LEAF_SIZE => None,
+            _ => self.median_split(order, low, high)
         };
+        let (axis, mid) = match split {
+            Some(split) => split,
+            None => return index
+        };
+
+        let (left, right) = order.split_at_mut(mid);
+        self.build(left, offset, depth + 1);
+        let right = self.build(right, offset + mid, depth + 1);
+
+        let node = &mut self.nodes[index];
+        node.offset = right as u32;
+        node.count = 0;
+        node.axis = axis as u8;
+        index
+    }
 
-        let box_left = left.bounding_box(time_range.clone());
-        let box_right = right.bounding_box(time_range);
-        let bounding_box = AABB::surrounding_box(box_left, box_right);
+    // returns the axis and the number of primitives moved to the front (left) of order,
+    // or None if the node is best left a leaf
+    fn sah_split(&self, order: &mut [usize], bounding_box: &AABB, low: Point3, high: Point3) -> Option<(usize, usize)> {
+        let parent_area = bounding_box.surface_area().max(f64::MIN_POSITIVE);
+        let bin = |i: usize, axis: usize| {
+            let extent = high[axis] - low[axis];
+            (((self.centroids[i][axis] - low[axis]) / extent * BINS as f64) as usize).min(BINS - 1)
+        };
+
+        // (cost, axis, first bin on the right)
+        let mut best: Option<(f64, usize, usize)> = None;
+        for axis in 0..3 {
+            if high[axis] <= low[axis] {
+                continue;
+            }
 
-        Self {
-            left,
-            right,
-            bounding_box,
+            let mut counts = [0; BINS];
+            let mut boxes: [Option<AABB>; BINS] = Default::default();
+            for &i in order.iter() {
+                let b = bin(i, axis);
+                counts[b] += 1;
+                boxes[b] = Some(match boxes[b].take() {
+                    Some(bbox) => AABB::surrounding_box(bbox, self.boxes[i].clone()),
+                    None => self.boxes[i].clone()
+                });
+            }
+
+            // sweep from the right to get the cost of everything at or after each boundary,
+            // then from the left adding the other side
+            let mut right_cost = [0.0; BINS];
+            let mut right_box: Option<AABB> = None;
+            let mut right_count = 0;
+            for b in (1..BINS).rev() {
+                right_count += counts[b];
+                right_box = union(right_box, boxes[b].clone());
+                right_cost[b] = right_box.as_ref().map_or(0.0, |bbox| bbox.surface_area() * right_count as f64);
+            }
+
+            let mut left_box: Option<AABB> = None;
+            let mut left_count = 0;
+            for b in 1..BINS {
+                left_count += counts[b - 1];
+                left_box = union(left_box, boxes[b - 1].clone());
+                if left_count == 0 || left_count == order.len() {
+                    continue;
+                }
+                let left_cost = left_box.as_ref().map_or(0.0, |bbox| bbox.surface_area() * left_count as f64);
+                let cost = TRAVERSAL_COST + INTERSECTION_COST * (left_cost + right_cost[b]) / parent_area;
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, b));
+                }
+            }
         }
+
+        let (cost, axis, split) = best?;
+        let leaf_cost = INTERSECTION_COST * order.len() as f64;
+        if cost >= leaf_cost && order.len() <= MAX_LEAF_SIZE {
+            return None;
+        }
+
+        let mut mid = 0;
+        for j in 0..order.len() {
+            if bin(order[j], axis) < split {
+                order.swap(j, mid);
+                mid += 1;
+            }
+        }
+        Some((axis, mid))
     }
-}
 
-impl Hit for BVH {
-    fn hit(&self, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
-        if !self.bounding_box.hit(r, time_range.clone()) {
+    fn median_split(&self, order: &mut [usize], low: Point3, high: Point3) -> Option<(usize, usize)> {
+        let extent = high - low;
+        let axis = if extent.x() > extent.y() && extent.x() > extent.z() {
+            0
+        } else if extent.y() > extent.z() {
+            1
+        } else {
+            2
+        };
+        if extent[axis] <= 0.0 {
+            // every centroid in the same spot, no split would separate them
             return None;
         }
 
-        let hit_left = self.left.hit(r, time_range.clone());
-        let tr = hit_left
-                .as_ref()
-                .map_or(time_range.clone(), |rec| time_range.start..rec.t);
-        let hit_right = self.right.hit(r, tr);
-        hit_left.or(hit_right)
+        let mid = order.len() / 2;
+        order.select_nth_unstable_by(mid, |&a, &b| self.centroids[a][axis].total_cmp(&self.centroids[b][axis]));
+        Some((axis, mid))
     }
-    
-    fn bounding_box(&self, time_range: Range<f64>) -> AABB {
-        self.bounding_box.clone()
+}
+
+fn union(a: Option<AABB>, b: Option<AABB>) -> Option<AABB> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(AABB::surrounding_box(a, b)),
+        (a, None) => a,
+        (None, b) => b
     }
 }
 
-fn box_x_compare(a: &Arc<Box<dyn Hit>>, b: &Arc<Box<dyn Hit>>) -> Ordering {
-    let a = a.bounding_box(0.0..1.0).get_minimum().x();
-    let b = b.bounding_box(0.0..1.0).get_minimum().x();
-    a.partial_cmp(&b).expect("unexpected NaN in bounding x")
+#[allow(clippy::upper_case_acronyms)]
+pub struct BVH {
+    objects: World, // in leaf order
+    tree: LinearBVH
+}
+
+impl BVH {
+    // time_range is the shutter interval, moving objects are bounded over all of it
+    pub fn new(objects: World, time_range: Range<f64>) -> BVH {
+        Self::with_split(objects, time_range, Split::Sah)
+    }
+
+    pub fn with_split(objects: World, time_range: Range<f64>, split: Split) -> BVH {
+        let boxes: Vec<AABB> = objects
+            .iter()
+            .map(|object| object.bounding_box(time_range.clone()))
+            .collect();
+        let (tree, order) = LinearBVH::build(&boxes, split);
+        let objects = order.iter().map(|&i| Arc::clone(&objects[i])).collect();
+        BVH { objects, tree }
+    }
+
+    pub fn stats(&self) -> &BuildStats {
+        self.tree.stats()
+    }
 }
 
-fn box_y_compare(a: &Arc<Box<dyn Hit>>, b: &Arc<Box<dyn Hit>>) -> Ordering {
-    let a = a.bounding_box(0.0..1.0).get_minimum().y();
-    let b = b.bounding_box(0.0..1.0).get_minimum().y();
-    a.partial_cmp(&b).expect("unexpected NaN in bounding y")
+impl Hit for BVH {
+    fn hit(&self, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
+        self.tree.hit(r, time_range, |i, range| {
+            self.objects[i].hit(r, range).map(|record| (record.t, record))
+        })
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> AABB {
+        self.tree.bounding_box()
+    }
 }
 
-fn box_z_compare(a: &Arc<Box<dyn Hit>>, b: &Arc<Box<dyn Hit>>) -> Ordering {
-    let a = a.bounding_box(0.0..1.0).get_minimum().z();
-    let b = b.bounding_box(0.0..1.0).get_minimum().z();
-    a.partial_cmp(&b).expect("unexpected NaN in bounding z")
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use super::super::aabb::hit_1d;
+    use crate::random::{self, Random};
+    use crate::sampler;
+
+    fn cube(centre: Point3, half: f64) -> AABB {
+        let half = Vec3::new(half, half, half);
+        AABB::new(centre - half, centre + half)
+    }
+
+    // the boxes themselves are the primitives: where r first meets the surface of b in range.
+    // boxes of nodes are computed the same way, so the tree can't miss what this finds
+    fn hit_box(b: &AABB, r: &Ray, range: Range<f64>) -> Option<f64> {
+        let (mut t0, mut t1) = (f64::NEG_INFINITY, f64::INFINITY);
+        let (low, high) = (b.get_minimum(), b.get_maximum());
+        for axis in 0..3 {
+            if !hit_1d(low[axis], high[axis], r.origin()[axis], r.direction()[axis], &mut t0, &mut t1) {
+                return None;
+            }
+        }
+        [t0, t1].into_iter().find(|t| range.contains(t))
+    }
+
+    // the tree finds the same closest hit as testing every box, for rays from all over the
+    // scene, half of them aimed at a box. returns the depth of the deepest tree
+    fn check(boxes: &[AABB], rng: &mut Random) -> usize {
+        let bounds = boxes.iter().cloned().reduce(AABB::surrounding_box).unwrap();
+        let (low, high) = (bounds.get_minimum(), bounds.get_maximum());
+        let mut depth = 0;
+
+        for split in [Split::Sah, Split::Median] {
+            let (tree, order) = LinearBVH::build(boxes, split);
+            assert!(tree.stats().depth < STACK_SIZE, "{}", tree.stats());
+            depth = depth.max(tree.stats().depth);
+            let mut sorted = order.clone();
+            sorted.sort_unstable();
+            assert!(sorted.into_iter().eq(0..boxes.len()));
+
+            let mut hits = 0;
+            for n in 0..2000 {
+                let origin = Point3::new(
+                    rng.gen_range(low.x() - 1.0..high.x() + 1.0),
+                    rng.gen_range(low.y() - 1.0..high.y() + 1.0),
+                    rng.gen_range(low.z() - 1.0..high.z() + 1.0)
+                );
+                let direction = if n % 2 == 0 {
+                    sampler::sphere((rng.gen(), rng.gen()))
+                } else {
+                    boxes[rng.gen_range(0..boxes.len())].centroid() - origin
+                };
+                let r = Ray::new_(origin, direction, 0.5);
+
+                let mut expected = None;
+                for b in boxes {
+                    if let Some(t) = hit_box(b, &r, 0.001..expected.unwrap_or(f64::INFINITY)) {
+                        expected = Some(t);
+                    }
+                }
+                let found = tree.hit(&r, 0.001..f64::INFINITY, |i, range| {
+                    hit_box(&boxes[order[i]], &r, range).map(|t| (t, t))
+                });
+                assert_eq!(expected, found, "{:?}", split);
+                hits += found.is_some() as usize;
+            }
+            assert!(hits > 1000, "{:?}: only {} hits", split, hits);
+        }
+        depth
+    }
+
+    #[test]
+    fn finds_the_closest_hit() {
+        let mut rng = random::seeded(1);
+        let boxes: Vec<AABB> = (0..500)
+            .map(|_| {
+                let centre = Point3::new(rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0));
+                cube(centre, rng.gen_range(0.1..5.0))
+            })
+            .collect();
+        check(&boxes, &mut rng);
+    }
+
+    // nothing separates the boxes, so they all end up in one leaf
+    #[test]
+    fn boxes_all_in_the_same_place() {
+        let mut rng = random::seeded(2);
+        let boxes: Vec<AABB> = (1..=40).map(|i| cube(Point3::new(1.0, 2.0, 3.0), i as f64 * 0.25)).collect();
+        for split in [Split::Sah, Split::Median] {
+            let (tree, _) = LinearBVH::build(&boxes, split);
+            assert_eq!((tree.stats().nodes, tree.stats().leaves), (1, 1));
+        }
+        check(&boxes, &mut rng);
+    }
+
+    // every box twice the size of the last and just past it: the centroids crowd into the
+    // lowest bin, so every split only peels the few biggest boxes off and the surface area
+    // heuristic alone would make a list of a tree. the median takes over past MAX_SAH_DEPTH and
+    // keeps it within the traversal stack
+    #[test]
+    fn skewed_boxes_stay_within_the_stack() {
+        let mut rng = random::seeded(3);
+        let boxes: Vec<AABB> = (0..200)
+            .map(|i| {
+                let half = 2.0_f64.powi(i);
+                cube(Point3::new(3.0 * half, 0.0, 0.0), half)
+            })
+            .collect();
+        let depth = check(&boxes, &mut rng);
+        assert!(depth > MAX_SAH_DEPTH, "depth {}", depth);
+    }
 }
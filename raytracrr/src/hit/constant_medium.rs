@@ -1,7 +1,5 @@
 use std::{sync::Arc, ops::Range};
-use rand::prelude::*;
-
-use crate::{material::{Scatter, isotropic::Isotropic}, texture::Texture, ray::Ray, vec::Vec3};
+use crate::{material::{Scatter, isotropic::Isotropic}, texture::Texture, random, ray::Ray, vec::Vec3};
 use super::{Hit, hit_record::HitRecord, aabb::AABB};
 
 /**
@@ -50,7 +48,11 @@ impl Hit for ConstantMedium {
 
                     let ray_length = r.direction().length();
                     let distance_inside_boundary = (t2 - t1) * ray_length;
-                    let hit_distance = self.neg_inv_density * thread_rng().gen::<f64>().ln();
+                    // hit has no rng to draw from, but the rays arriving here are already random
+                    // (and never the same twice), so hashing the ray gives a number that is just as
+                    // random and still the same on every run. 1 - u so ln never sees 0
+                    let u = random::hash_unit(&[r.orig.x(), r.orig.y(), r.orig.z(), r.dir.x(), r.dir.y(), r.dir.z(), r.time]);
+                    let hit_distance = self.neg_inv_density * (1.0 - u).ln();
 
                     // make sure hit is inside boundary, otherwise no hit
                     if hit_distance <= distance_inside_boundary {
@@ -0,0 +1,52 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::aabb::AABB;
+use crate::hit::{Hit, HitRecord, Illumination};
+use crate::ray::Ray;
+use crate::vec::{Colour, Point3, Vec3};
+
+// light from so far away that it arrives the same way everywhere, going towards direction
+// (the sun, without the size of its disk). illuminance is what a surface facing it gets, per
+// colour channel, in lux for physical units (around 100000 for the midday sun)
+pub struct DirectionalLight {
+    direction: Vec3, // unit vector
+    illuminance: Colour
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, illuminance: Colour) -> DirectionalLight {
+        DirectionalLight { direction: direction.normalized(), illuminance }
+    }
+
+    pub fn new_arc(direction: Vec3, illuminance: Colour) -> Arc<dyn Hit> {
+        Arc::new(DirectionalLight::new(direction, illuminance))
+    }
+}
+
+impl Hit for DirectionalLight {
+    fn hit(&self, _r: &Ray, _time_range: Range<f64>) -> Option<HitRecord> {
+        None
+    }
+
+    // it is nowhere, the origin will do for anyone asking (it is kept out of the bvh)
+    fn bounding_box(&self, _time_range: Range<f64>) -> AABB {
+        AABB::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0))
+    }
+
+    fn is_light(&self) -> bool {
+        true
+    }
+
+    fn is_punctual(&self) -> bool {
+        true
+    }
+
+    fn illuminate(&self, _origin: Point3, _time: f64) -> Option<Illumination> {
+        Some(Illumination {
+            direction: -1.0 * self.direction,
+            distance: f64::INFINITY,
+            incoming: self.illuminance
+        })
+    }
+}
@@ -0,0 +1,119 @@
+use std::{sync::Arc, ops::Range};
+
+use crate::{material::Scatter, matrix::Matrix4, ray::Ray, vec::{Point3, Vec3}};
+use super::{aabb::AABB, bvh::BVH, transform::Transform, world::World, Hit, Illumination, hit_record::HitRecord};
+
+/*
+ * one placement of a shared prototype: a Transform that can also paint the prototype in a
+ * material of its own. the prototype (a mesh, or a group of objects with their own bvh) is
+ * built once and only referenced, so a thousand copies of a heavy mesh cost a thousand
+ * matrices and not a thousand meshes.
+ *
+ * instances go into the world like any other object, and the bvh the renderer builds over the
+ * world then sits on top of the prototypes' own trees: a ray walks the top tree to an
+ * instance, is taken into the prototype's space and walks its tree there (two levels, the
+ * usual way to instance).
+ */
+
+pub struct Instance {
+    transform: Transform,
+    material: Option<Arc<dyn Scatter>> // replaces the prototype's materials if given
+}
+
+impl Instance {
+    // panics if the matrix can't be inverted, like Transform
+    pub fn new(prototype: Arc<dyn Hit>, matrix: Matrix4, material: Option<Arc<dyn Scatter>>) -> Instance {
+        Instance {
+            transform: Transform::new(prototype, matrix),
+            material
+        }
+    }
+
+    pub fn new_arc(prototype: Arc<dyn Hit>, matrix: Matrix4, material: Option<Arc<dyn Scatter>>) -> Arc<Box<dyn Hit>> {
+        Arc::new(Box::new(Instance::new(prototype, matrix, material)))
+    }
+
+    // a prototype made of several objects, with a bvh of its own to be shared by its instances.
+    // time_range is the shutter interval, as for the world's bvh
+    pub fn prototype(objects: World, time_range: Range<f64>) -> Arc<dyn Hit> {
+        Arc::new(Prototype::new(objects, time_range))
+    }
+}
+
+// the bvh only bounds moving objects over the time range it was built for, the world's is
+// built again for every frame but a prototype's is built once with the scene. rays and boxes
+// from outside that time (a frame of a sequence past the scene's shutter) go to the objects
+// one by one instead, slower but nothing goes missing
+struct Prototype {
+    objects: World,
+    bvh: BVH,
+    time_range: Range<f64>
+}
+
+impl Prototype {
+    fn new(objects: World, time_range: Range<f64>) -> Prototype {
+        Prototype {
+            bvh: BVH::new(objects.clone(), time_range.clone()),
+            objects,
+            time_range
+        }
+    }
+
+    fn covers(&self, time_range: &Range<f64>) -> bool {
+        self.time_range.start <= time_range.start && time_range.end <= self.time_range.end
+    }
+}
+
+impl Hit for Prototype {
+    fn hit(&self, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
+        if self.covers(&(r.time..r.time)) {
+            self.bvh.hit(r, time_range)
+        } else {
+            self.objects.hit(r, time_range)
+        }
+    }
+
+    fn bounding_box(&self, time_range: Range<f64>) -> AABB {
+        if self.covers(&time_range) {
+            self.bvh.bounding_box(time_range)
+        } else {
+            self.objects.bounding_box(time_range)
+        }
+    }
+}
+
+impl Hit for Instance {
+    fn hit(&self, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
+        let mut record = self.transform.hit(r, time_range)?;
+        if let Some(material) = &self.material {
+            record.material = Arc::clone(material);
+        }
+        Some(record)
+    }
+
+    fn bounding_box(&self, time_range: Range<f64>) -> AABB {
+        self.transform.bounding_box(time_range)
+    }
+
+    // only a prototype that is a light can be sampled as one, so an emitting material on
+    // something that isn't is only found by bouncing into it
+    fn is_light(&self) -> bool {
+        self.transform.is_light() && self.material.as_ref().is_none_or(|material| material.is_emissive())
+    }
+
+    fn is_punctual(&self) -> bool {
+        self.transform.is_punctual()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        self.transform.pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: Point3, time: f64, u: (f64, f64)) -> Vec3 {
+        self.transform.random(origin, time, u)
+    }
+
+    fn illuminate(&self, origin: Point3, time: f64) -> Option<Illumination> {
+        self.transform.illuminate(origin, time)
+    }
+}
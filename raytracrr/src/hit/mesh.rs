@@ -0,0 +1,135 @@
+use std::{sync::Arc, ops::Range};
+
+use crate::{vec::{Point3, Vec3}, material::Scatter, ray::Ray};
+use super::{aabb::AABB, Hit, hit_record::HitRecord};
+use super::bvh::{BuildStats, LinearBVH, Split};
+use super::triangle::{intersect, triangle_box};
+
+// a triangle mesh keeps its vertex data in shared buffers and its faces only hold indices
+// into them, so a vertex used by six triangles is stored once.
+// normals and texture coordinates are optional per face: with them the shading normal and
+// (u, v) are interpolated across the face from the corners, without them the face is flat
+// and (u, v) are the barycentric coordinates.
+//
+// testing every face would make a model with a million triangles hopeless, so the mesh
+// builds its own bvh over the faces when it is created.
+
+pub struct Face {
+    pub vertices: [u32; 3], // counter-clockwise seen from the front
+    pub normals: Option<[u32; 3]>,
+    pub uvs: Option<[u32; 3]>,
+    pub material: u32 // index into the mesh materials
+}
+
+pub struct Mesh {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<Face>,
+    materials: Vec<Arc<dyn Scatter>>,
+    tree: LinearBVH
+}
+
+impl Mesh {
+    // faces must only refer to existing vertices, normals, uvs and materials
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<Face>,
+        materials: Vec<Arc<dyn Scatter>>
+    ) -> Mesh {
+        let boxes: Vec<AABB> = faces
+            .iter()
+            .map(|face| {
+                let [a, b, c] = face.vertices.map(|i| positions[i as usize]);
+                triangle_box(a, b, c)
+            })
+            .collect();
+        let (tree, order) = LinearBVH::build(&boxes, Split::Sah);
+
+        // put the faces in leaf order so every leaf is a contiguous run
+        let mut faces: Vec<Option<Face>> = faces.into_iter().map(Some).collect();
+        let faces = order.iter().map(|&i| faces[i].take().unwrap()).collect();
+
+        Mesh {
+            positions,
+            normals,
+            uvs,
+            faces,
+            materials,
+            tree
+        }
+    }
+
+    pub fn new_arc(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<Face>,
+        materials: Vec<Arc<dyn Scatter>>
+    ) -> Arc<Box<dyn Hit>> {
+        Arc::new(Box::new(Mesh::new(positions, normals, uvs, faces, materials)))
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn stats(&self) -> &BuildStats {
+        self.tree.stats()
+    }
+
+    fn corners(&self, face: &Face) -> [Point3; 3] {
+        face.vertices.map(|i| self.positions[i as usize])
+    }
+}
+
+impl Hit for Mesh {
+    fn hit(&self, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
+        let (t, f, b1, b2) = self.tree.hit(r, time_range, |f, range| {
+            let [a, b, c] = self.corners(&self.faces[f]);
+            intersect(r, a, b, c, range).map(|(t, b1, b2)| (t, (t, f, b1, b2)))
+        })?;
+        let face = &self.faces[f];
+        let [a, b, c] = self.corners(face);
+        let b0 = 1.0 - b1 - b2;
+
+        let (u, v) = match face.uvs {
+            Some(uvs) => {
+                let [uv0, uv1, uv2] = uvs.map(|i| self.uvs[i as usize]);
+                (b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0, b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1)
+            }
+            None => (b1, b2)
+        };
+
+        // the geometric normal decides which side was hit, the shading normal only bends it
+        let outward_normal = (b - a).cross(c - a).normalized();
+        let mut record = HitRecord {
+            p: r.at(t),
+            normal: outward_normal,
+            t,
+            u,
+            v,
+            material: Arc::clone(&self.materials[face.material as usize]),
+            front_face: false
+        };
+        record.set_face_normal(r, outward_normal);
+
+        if let Some(normals) = face.normals {
+            let [n0, n1, n2] = normals.map(|i| self.normals[i as usize]);
+            let shading = b0 * n0 + b1 * n1 + b2 * n2;
+            if !shading.near_zero() {
+                // on the side of the hit, even if the file's winding disagrees with its normals
+                let shading = shading.normalized();
+                record.normal = if shading.dot(record.normal) < 0.0 { (-1.0) * shading } else { shading };
+            }
+        }
+
+        Some(record)
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> AABB {
+        self.tree.bounding_box()
+    }
+}
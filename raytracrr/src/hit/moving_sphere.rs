@@ -70,8 +70,8 @@ impl Hit for MovingSphere {
         let mut record = HitRecord {
             p,
             t: root, 
-            u: u, 
-            v: v,
+            u, 
+            v,
             material: self.material.clone(),
             normal: Vec3::new(0.0, 0.0, 0.0),
             front_face: false
@@ -79,7 +79,7 @@ impl Hit for MovingSphere {
 
         record.set_face_normal(r, outward_normal);
 
-        return Some(record);
+        Some(record)
     }
 
     fn bounding_box(&self, time_range: Range<f64>) -> AABB {
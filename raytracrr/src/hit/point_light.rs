@@ -0,0 +1,57 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::aabb::AABB;
+use crate::hit::{Hit, HitRecord, Illumination};
+use crate::ray::Ray;
+use crate::vec::{Colour, Point3};
+
+// a light the size of a point, shining the same every way. intensity is per colour channel,
+// in candela for physical units (a 100 W bulb is about 130 cd): what arrives falls off with
+// the square of the distance. it can't be hit, so it isn't seen by the camera or in mirrors,
+// only by what it lights
+pub struct PointLight {
+    position: Point3,
+    intensity: Colour
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Colour) -> PointLight {
+        PointLight { position, intensity }
+    }
+
+    pub fn new_arc(position: Point3, intensity: Colour) -> Arc<dyn Hit> {
+        Arc::new(PointLight::new(position, intensity))
+    }
+}
+
+impl Hit for PointLight {
+    fn hit(&self, _r: &Ray, _time_range: Range<f64>) -> Option<HitRecord> {
+        None
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> AABB {
+        AABB::new_pad(self.position, self.position)
+    }
+
+    fn is_light(&self) -> bool {
+        true
+    }
+
+    fn is_punctual(&self) -> bool {
+        true
+    }
+
+    fn illuminate(&self, origin: Point3, _time: f64) -> Option<Illumination> {
+        let to_light = self.position - origin;
+        let distance = to_light.length();
+        if distance == 0.0 {
+            return None;
+        }
+        Some(Illumination {
+            direction: to_light / distance,
+            distance,
+            incoming: self.intensity / (distance * distance)
+        })
+    }
+}
@@ -1,8 +1,9 @@
 use std::{sync::Arc, ops::Range};
-use crate::{vec::{Point3, Vec3}, material::Scatter};
+
+use crate::{vec::{Point3, Vec3}, material::Scatter, ray::Ray};
 use super::{aabb::AABB, Hit, hit_record::HitRecord};
 
-/**
+/*
  *    v ------- Q + u + v
  *   /         /
  *  /         /
@@ -31,52 +32,51 @@ use super::{aabb::AABB, Hit, hit_record::HitRecord};
  * for arbitrary P, P = Q + au + bv
  * p = P - Q = au + bv (p = vector from Q to P)
  * 
- * w = n / (n.dot(n))
- * a = v.cross(p).dot(n)
- * b = u.cross(p).dot(n)
+ * w = (u x v) / ((u x v).dot(u x v)), not the normalized n
+ * a = w.dot(p.cross(v))
+ * b = w.dot(u.cross(p))
  * 
  * check that 0 <= a <= 1 and 0 <= b <= 1
  */
 
-// FIXME: for some reason, following the direct implementation in the c++ code doesn't work
-// i kept this one for reference (might try to fix it later but i've been 
-// staring at it for a while and idk what went wrong).
-// see Rect for the correctly implemented hittable :sob:
+// w has to use u x v as it is, the normalized n made alpha and beta off by a factor |u x v|
+// (which is why this used to only work for unit quads)
 pub struct Quad {
-    Q: Point3,
+    q: Point3,
     u: Vec3,
     v: Vec3, 
     material: Arc<dyn Scatter>,
     b: AABB,
     normal: Vec3, 
-    D: f64, 
+    d: f64, 
     w: Vec3
 }
 
 impl Quad {
-    pub fn new(Q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Scatter>) -> Quad {
-        let n = u.cross(v).normalized();
-        let D = n.dot(Q);
-        let w = n / n.dot(n);
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Scatter>) -> Quad {
+        let uv = u.cross(v);
+        let n = uv.normalized();
+        let d = n.dot(q);
+        let w = uv / uv.dot(uv);
         Quad {
-            Q, 
+            q, 
             u, 
             v, 
             material, 
-            b: AABB::new_pad(Q, Q + u + v), 
+            b: AABB::from_points(&[q, q + u, q + v, q + u + v]), 
             normal: n, 
-            D: D,
-            w: w
+            d,
+            w
         }
     }
 }
 
 impl Hit for Quad {
-    fn bounding_box(&self, time_range: Range<f64>) -> AABB {
+    fn bounding_box(&self, _time_range: Range<f64>) -> AABB {
         self.b.clone()
     }
 
-    fn hit(&self, r: &crate::ray::Ray, time_range: std::ops::Range<f64>) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
         let n_dot_d = self.normal.dot(r.direction());
 
         // no hit if ray parallel to plane
@@ -84,7 +84,7 @@ impl Hit for Quad {
             return None;
         }
 
-        let t = (self.D - self.normal.dot(r.origin())) / n_dot_d;
+        let t = (self.d - self.normal.dot(r.origin())) / n_dot_d;
 
         if !time_range.contains(&t) {
             return None;
@@ -92,7 +92,7 @@ impl Hit for Quad {
 
         // determining if hit point is in planar shape
         let intersection = r.at(t);
-        let p_vec = intersection - self.Q;
+        let p_vec = intersection - self.q;
 
         let alpha = self.w.dot(p_vec.cross(self.v));
         let beta = self.w.dot(self.u.cross(p_vec));
@@ -103,7 +103,7 @@ impl Hit for Quad {
 
         let mut record = HitRecord {
             p: intersection,
-            t: t, 
+            t, 
             u: alpha, 
             v: beta,
             material: self.material.clone(),
@@ -113,6 +113,28 @@ impl Hit for Quad {
 
         record.set_face_normal(r, self.normal);
 
-        return Some(record);
+        Some(record)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // same as Rect: uniform over the area, converted to a density over directions
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        let record = match self.hit(&Ray::new_(origin, direction, time), 0.001..f64::INFINITY) {
+            Some(record) => record,
+            None => return 0.0
+        };
+        let area = self.u.cross(self.v).length();
+        let distance_squared = record.t * record.t * direction.dot(direction);
+        let cosine = (direction.dot(self.normal) / direction.length()).abs();
+        distance_squared / (cosine * area)
+    }
+
+    fn random(&self, origin: Point3, _time: f64, u: (f64, f64)) -> Vec3 {
+        let point = self.q + u.0 * self.u + u.1 * self.v;
+        point - origin
     }
 }
+
@@ -1,7 +1,7 @@
-use core::time;
 use std::{ops::Range, sync::Arc};
 
 use crate::material::Scatter;
+use crate::ray::Ray;
 
 use super::{Hit, aabb::AABB, hit_record::HitRecord};
 use crate::vec::{Vec3};
@@ -33,7 +33,7 @@ impl Rect {
 }
 
 impl Hit for Rect {
-    fn hit(&self, r: &crate::ray::Ray, time_range: Range<f64>) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
 
         // finding the t in R(t) = At + B
         let t = if self.axis == 0 { // xy
@@ -100,7 +100,8 @@ impl Hit for Rect {
         Some(record)
     }
 
-    fn bounding_box(&self, time_range: Range<f64>) -> AABB {
+    // padded along k, a box with no thickness is missed by every ray
+    fn bounding_box(&self, _time_range: Range<f64>) -> AABB {
         let minimum = if self.axis == 0 {
             Vec3::new(self.u.start, self.v.start, self.k - 0.0001)
         } else if self.axis == 1 {
@@ -109,12 +110,42 @@ impl Hit for Rect {
             Vec3::new(self.k - 0.0001, self.u.start, self.v.start)
         };
         let maximum = if self.axis == 0 {
-            Vec3::new(self.u.end, self.v.end, self.k - 0.0001)
+            Vec3::new(self.u.end, self.v.end, self.k + 0.0001)
         } else if self.axis == 1 {
-            Vec3::new(self.u.end, self.k - 0.0001, self.v.end)
+            Vec3::new(self.u.end, self.k + 0.0001, self.v.end)
         } else {
-            Vec3::new(self.k - 0.0001, self.u.end, self.v.end)
+            Vec3::new(self.k + 0.0001, self.u.end, self.v.end)
         };
         AABB::new(minimum, maximum)
     }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // random picks points uniformly over the area, turning that density (1 / area) into one over
+    // directions takes the distance squared over the cosine at the light
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f64) -> f64 {
+        let record = match self.hit(&Ray::new_(origin, direction, time), 0.001..f64::INFINITY) {
+            Some(record) => record,
+            None => return 0.0
+        };
+        let area = (self.u.end - self.u.start) * (self.v.end - self.v.start);
+        let distance_squared = record.t * record.t * direction.dot(direction);
+        let cosine = (direction.dot(record.normal) / direction.length()).abs();
+        distance_squared / (cosine * area)
+    }
+
+    fn random(&self, origin: Vec3, _time: f64, u: (f64, f64)) -> Vec3 {
+        let a = self.u.start + u.0 * (self.u.end - self.u.start);
+        let b = self.v.start + u.1 * (self.v.end - self.v.start);
+        let point = if self.axis == 0 { // xy
+            Vec3::new(a, b, self.k)
+        } else if self.axis == 1 { // xz
+            Vec3::new(a, self.k, b)
+        } else { // yz
+            Vec3::new(self.k, a, b)
+        };
+        point - origin
+    }
 }
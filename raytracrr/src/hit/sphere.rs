@@ -4,6 +4,7 @@ use std::sync::Arc;
 
 use super::aabb::{AABB};
 use crate::material::Scatter;
+use crate::onb::Onb;
 use crate::ray::{Ray};
 use crate::hit::{Hit, HitRecord};
 use crate::vec::{Point3, Vec3};
@@ -65,8 +66,8 @@ impl Hit for Sphere {
         let mut record = HitRecord {
             p,
             t: root, 
-            u: u, 
-            v: v,
+            u, 
+            v,
             material: self.material.clone(),
             normal: Vec3::new(0.0, 0.0, 0.0),
             front_face: false
@@ -74,14 +75,42 @@ impl Hit for Sphere {
 
         record.set_face_normal(r, outward_normal);
 
-        return Some(record);
+        Some(record)
     }
 
     // bounding box is just the box that surrounds the sphere
-    fn bounding_box(&self, time_range: Range<f64>) -> AABB {
+    fn bounding_box(&self, _time_range: Range<f64>) -> AABB {
         let rvec = Vec3::new(self.radius, self.radius, self.radius);
-        let bbox = AABB::new(self.center - rvec, self.center + rvec);
-        bbox
+        AABB::new(self.center - rvec, self.center + rvec)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // seen from outside, the sphere covers a cone of directions around the way to its centre,
+    // random picks uniformly inside that cone so the density is 1 / solid angle of the cone
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        if self.hit(&Ray::new_(origin, direction, time), 0.001..f64::INFINITY).is_none() {
+            return 0.0;
+        }
+        let distance_squared = (self.center - origin).dot(self.center - origin);
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).max(0.0).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Point3, _time: f64, u: (f64, f64)) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.dot(direction);
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).max(0.0).sqrt();
+
+        let (r1, r2) = u;
+        let z = 1.0 + r2 * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = (1.0 - z * z).sqrt();
+
+        Onb::new(direction).local(Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z))
     }
 }
 
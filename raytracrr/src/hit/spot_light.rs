@@ -0,0 +1,83 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::aabb::AABB;
+use crate::hit::{Hit, HitRecord, Illumination};
+use crate::ray::Ray;
+use crate::vec::{Colour, Point3, Vec3};
+
+// a point light that only shines within outer degrees of direction: full intensity (candela,
+// see PointLight) out to inner degrees from it, then fading smoothly to nothing at outer
+pub struct SpotLight {
+    position: Point3,
+    direction: Vec3, // unit vector
+    intensity: Colour,
+    cos_inner: f64,
+    cos_outer: f64
+}
+
+impl SpotLight {
+    pub fn new(position: Point3, direction: Vec3, intensity: Colour, inner: f64, outer: f64) -> SpotLight {
+        let outer = outer.to_radians();
+        SpotLight {
+            position,
+            direction: direction.normalized(),
+            intensity,
+            cos_inner: inner.to_radians().min(outer).cos(),
+            cos_outer: outer.cos()
+        }
+    }
+
+    pub fn new_arc(position: Point3, direction: Vec3, intensity: Colour, inner: f64, outer: f64) -> Arc<dyn Hit> {
+        Arc::new(SpotLight::new(position, direction, intensity, inner, outer))
+    }
+
+    // how much of the intensity goes out at an angle with cosine cos_theta from the direction:
+    // a smoothstep from the outer edge in to the inner one
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Hit for SpotLight {
+    fn hit(&self, _r: &Ray, _time_range: Range<f64>) -> Option<HitRecord> {
+        None
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> AABB {
+        AABB::new_pad(self.position, self.position)
+    }
+
+    fn is_light(&self) -> bool {
+        true
+    }
+
+    fn is_punctual(&self) -> bool {
+        true
+    }
+
+    fn illuminate(&self, origin: Point3, _time: f64) -> Option<Illumination> {
+        let to_light = self.position - origin;
+        let distance = to_light.length();
+        if distance == 0.0 {
+            return None;
+        }
+        let direction = to_light / distance;
+        let falloff = self.falloff(-direction.dot(self.direction));
+        if falloff == 0.0 {
+            return None;
+        }
+        Some(Illumination {
+            direction,
+            distance,
+            incoming: falloff / (distance * distance) * self.intensity
+        })
+    }
+}
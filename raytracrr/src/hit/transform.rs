@@ -0,0 +1,255 @@
+use std::{sync::Arc, ops::Range};
+
+use crate::{matrix::Matrix4, ray::Ray, vec::{Point3, Vec3}};
+use super::{aabb::AABB, Hit, Illumination, hit_record::HitRecord};
+
+/*
+ * like Translate, but for any affine transform: the ray is taken into the object's own space
+ * by the inverse transform, hit there, and the hit brought back out.
+ * the direction isn't normalised on the way in, so t is the same in both spaces.
+ *
+ * points and directions go out through the matrix, normals through the transpose of its
+ * inverse: that keeps them perpendicular to the surface when it is scaled or sheared unevenly,
+ * which the matrix itself wouldn't.
+ */
+
+pub struct Transform {
+    object: Arc<dyn Hit>,
+    affine: Affine
+}
+
+impl Transform {
+    // panics if the matrix can't be inverted (see Matrix4::inverse)
+    pub fn new(object: Arc<dyn Hit>, matrix: Matrix4) -> Transform {
+        Transform {
+            object,
+            affine: Affine::new(matrix).expect("transform matrix can't be inverted")
+        }
+    }
+
+    pub fn new_arc(object: Arc<dyn Hit>, matrix: Matrix4) -> Arc<dyn Hit> {
+        Arc::new(Transform::new(object, matrix))
+    }
+}
+
+impl Hit for Transform {
+    fn hit(&self, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
+        self.affine.hit(self.object.as_ref(), r, time_range)
+    }
+
+    fn bounding_box(&self, time_range: Range<f64>) -> AABB {
+        self.affine.bounding_box(&self.object.bounding_box(time_range))
+    }
+
+    fn is_light(&self) -> bool {
+        self.object.is_light()
+    }
+
+    fn is_punctual(&self) -> bool {
+        self.object.is_punctual()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        self.affine.pdf_value(self.object.as_ref(), origin, direction, time)
+    }
+
+    fn random(&self, origin: Point3, time: f64, u: (f64, f64)) -> Vec3 {
+        self.affine.random(self.object.as_ref(), origin, time, u)
+    }
+
+    fn illuminate(&self, origin: Point3, time: f64) -> Option<Illumination> {
+        self.affine.illuminate(self.object.as_ref(), origin, time)
+    }
+}
+
+// the matrix with everything Transform needs worked out once, also used on its own by the
+// transforms that change over time
+#[derive(Clone, Copy)]
+pub struct Affine {
+    matrix: Matrix4,
+    inverse: Matrix4,
+    normal_matrix: Matrix4
+}
+
+impl Affine {
+    // None if the matrix can't be inverted
+    pub fn new(matrix: Matrix4) -> Option<Affine> {
+        let inverse = matrix.inverse()?;
+        Some(Affine {
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose()
+        })
+    }
+
+    pub fn hit(&self, object: &dyn Hit, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
+        let local_ray = Ray::new_(self.inverse.point(r.origin()), self.inverse.vector(r.direction()), r.time);
+        object.hit(&local_ray, time_range).map(|rec| {
+            HitRecord {
+                p: self.matrix.point(rec.p),
+                normal: self.normal_matrix.vector(rec.normal).normalized(),
+                t: rec.t,
+                u: rec.u,
+                v: rec.v,
+                material: rec.material,
+                front_face: rec.front_face
+            }
+        })
+    }
+
+    // the exact box around the transformed box of the object: along every axis, each column
+    // of the matrix moves the box furthest one way by whichever end of the object's box
+    // takes it further (arvo's method)
+    pub fn bounding_box(&self, bounding_box: &AABB) -> AABB {
+        let (b_min, b_max) = (bounding_box.get_minimum(), bounding_box.get_maximum());
+        let mut minimum = Vec3::new(self.matrix.m[0][3], self.matrix.m[1][3], self.matrix.m[2][3]);
+        let mut maximum = minimum;
+        for i in 0..3 {
+            for j in 0..3 {
+                let a = self.matrix.m[i][j] * b_min[j];
+                let b = self.matrix.m[i][j] * b_max[j];
+                minimum[i] += a.min(b);
+                maximum[i] += a.max(b);
+            }
+        }
+        AABB::new(minimum, maximum)
+    }
+
+    // the object's density for the same direction in its own space, times how much the
+    // transform squeezes the solid angle around it: a cone of directions around the unit
+    // vector w comes out |det| / |matrix w|^3 times as wide
+    pub fn pdf_value(&self, object: &dyn Hit, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        let local_direction = self.inverse.vector(direction).normalized();
+        let pdf = object.pdf_value(self.inverse.point(origin), local_direction, time);
+        pdf * self.matrix.vector(local_direction).length().powi(3) / self.matrix.determinant().abs()
+    }
+
+    pub fn random(&self, object: &dyn Hit, origin: Point3, time: f64, u: (f64, f64)) -> Vec3 {
+        self.matrix.vector(object.random(self.inverse.point(origin), time, u))
+    }
+
+    // the light is moved, its intensity stays as the object gives it in its own space. a
+    // light at a distance falls off with the square of it, and a scaling matrix changes the
+    // distance, so the light arriving is worked out again for the distance out here
+    pub fn illuminate(&self, object: &dyn Hit, origin: Point3, time: f64) -> Option<Illumination> {
+        let local = object.illuminate(self.inverse.point(origin), time)?;
+        if local.distance.is_infinite() {
+            return Some(Illumination { direction: self.matrix.vector(local.direction).normalized(), ..local });
+        }
+        let to_light = self.matrix.vector(local.distance * local.direction);
+        let distance = to_light.length();
+        Some(Illumination {
+            direction: to_light / distance,
+            distance,
+            incoming: (local.distance / distance).powi(2) * local.incoming
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use rand::Rng;
+
+    use super::*;
+    use crate::hit::sphere::Sphere;
+    use crate::material::matte::Matte;
+    use crate::random;
+    use crate::sampler;
+    use crate::texture::solid::Solid;
+    use crate::vec::Colour;
+
+    // a unit sphere stretched into an ellipsoid, turned and moved away from the origin
+    fn ellipsoid() -> (Transform, Matrix4) {
+        let grey = Matte::new_arc(Solid::new_arc(Colour::new(0.5, 0.5, 0.5)));
+        let matrix = Matrix4::scaling(Vec3::new(3.0, 1.0, 0.5))
+            .rotate(Vec3::new(1.0, 1.0, 0.0), 40.0)
+            .translate(Vec3::new(1.0, 2.0, 6.0));
+        (Transform::new(Sphere::new_arc(Point3::default(), 1.0, grey), matrix), matrix)
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        let (ellipsoid, matrix) = ellipsoid();
+        let inverse = matrix.inverse().unwrap();
+        let mut rng = random::seeded(1);
+        for _ in 0..1000 {
+            let target = matrix.point(0.9 * sampler::sphere((rng.gen(), rng.gen())));
+            let r = Ray::new_(Point3::default(), target, 0.0);
+            let rec = ellipsoid.hit(&r, 0.001..f64::INFINITY).unwrap();
+            // the surface runs along the transformed tangents of the sphere at the same point
+            let local = inverse.point(rec.p);
+            let a = if local.x().abs() < 0.9 { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+            let tangent = local.cross(a);
+            for tangent in [tangent, local.cross(tangent)] {
+                let tangent = matrix.vector(tangent).normalized();
+                assert!(rec.normal.dot(tangent).abs() < 1e-9, "normal {:?} tangent {:?}", rec.normal, tangent);
+            }
+            assert!((rec.normal.length() - 1.0).abs() < 1e-9);
+            assert!(rec.normal.dot(rec.p - matrix.point(Point3::default())) > 0.0);
+        }
+    }
+
+    #[test]
+    fn bounding_box_holds_the_corners() {
+        let matrices = [
+            Matrix4::rotation(Vec3::new(1.0, 2.0, 3.0), 75.0).translate(Vec3::new(-4.0, 0.0, 2.0)),
+            Matrix4::scaling(Vec3::new(-2.0, 0.5, 3.0)).shear(0.5, 0.0, -1.0, 0.2, 0.0, 0.7),
+            Matrix4::IDENTITY
+        ];
+        let object = AABB::new(Point3::new(-1.0, 0.5, 2.0), Point3::new(3.0, 1.0, 4.0));
+        for matrix in matrices {
+            let bounding_box = Affine::new(matrix).unwrap().bounding_box(&object);
+            let (low, high) = (bounding_box.get_minimum(), bounding_box.get_maximum());
+            let corners: Vec<Point3> = (0..8)
+                .map(|i| {
+                    let pick = |axis: usize| if i >> axis & 1 == 0 { object.get_minimum()[axis] } else { object.get_maximum()[axis] };
+                    matrix.point(Point3::new(pick(0), pick(1), pick(2)))
+                })
+                .collect();
+            for axis in 0..3 {
+                for corner in &corners {
+                    assert!(low[axis] - 1e-12 <= corner[axis] && corner[axis] <= high[axis] + 1e-12, "{:?} outside {:?} to {:?}", corner, low, high);
+                }
+                // and no bigger than it has to be
+                let (lowest, highest) = corners.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(l, h), c| (l.min(c[axis]), h.max(c[axis])));
+                assert!((lowest - low[axis]).abs() < 1e-12 && (highest - high[axis]).abs() < 1e-12);
+            }
+        }
+    }
+
+    // a light's pdf is the density its samples have: it integrates to 1 over all directions,
+    // and weighting samples by 1 / pdf integrates a function over the light the same as
+    // finding the light by sending rays everywhere does
+    #[test]
+    fn scaled_light_pdf_matches_its_samples() {
+        let (ellipsoid, _) = ellipsoid();
+        let origin = Point3::new(0.5, -0.5, 0.0);
+        let f = |w: Vec3| (1.0 + w.x()).powi(2);
+        let mut rng = random::seeded(2);
+        let n = 200_000;
+
+        let (mut total_pdf, mut everywhere) = (0.0, 0.0);
+        for _ in 0..n {
+            let w = sampler::sphere((rng.gen(), rng.gen()));
+            total_pdf += ellipsoid.pdf_value(origin, w, 0.0);
+            if ellipsoid.hit(&Ray::new_(origin, w, 0.0), 0.001..f64::INFINITY).is_some() {
+                everywhere += f(w);
+            }
+        }
+        let total_pdf = 4.0 * PI * total_pdf / n as f64;
+        let everywhere = 4.0 * PI * everywhere / n as f64;
+
+        let mut sampled = 0.0;
+        for _ in 0..n {
+            let w = ellipsoid.random(origin, 0.0, (rng.gen(), rng.gen())).normalized();
+            assert!(ellipsoid.hit(&Ray::new_(origin, w, 0.0), 0.001..f64::INFINITY).is_some());
+            sampled += f(w) / ellipsoid.pdf_value(origin, w, 0.0);
+        }
+        let sampled = sampled / n as f64;
+
+        assert!((total_pdf - 1.0).abs() < 0.03, "pdf integrates to {}", total_pdf);
+        assert!((sampled - everywhere).abs() < 0.03 * everywhere, "sampled {} found {}", sampled, everywhere);
+    }
+}
@@ -3,7 +3,7 @@ use std::{sync::Arc, ops::Range};
 use crate::{vec::Vec3, ray::Ray};
 use super::{Hit, hit_record::HitRecord, aabb::AABB};
 
-/**
+/*
  * think of moving the incident ray backwards the offset amount, 
  * determining if an intersection occurs, 
  * and then moving that intersection point forward the offset amount.
@@ -32,7 +32,7 @@ impl Translate {
 
 impl Hit for Translate {
     fn hit(&self, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
-        let moved_ray = Ray::new(r.origin() - self.offset, r.direction());
+        let moved_ray = Ray::new_(r.origin() - self.offset, r.direction(), r.time);
         self.object.hit(&moved_ray, time_range).map(|rec| {
             HitRecord {
                 front_face: rec.front_face,
@@ -48,8 +48,21 @@ impl Hit for Translate {
 
     fn bounding_box(&self, time_range: Range<f64>) -> AABB {
         AABB::new(
-            self.bounding_box(time_range.clone()).get_minimum() + self.offset,
-            self.bounding_box(time_range).get_maximum() + self.offset
+            self.object.bounding_box(time_range.clone()).get_minimum() + self.offset,
+            self.object.bounding_box(time_range).get_maximum() + self.offset
         )
     }
+
+    // the light moved by offset is the original seen from origin moved back by offset
+    fn is_light(&self) -> bool {
+        self.object.is_light()
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f64) -> f64 {
+        self.object.pdf_value(origin - self.offset, direction, time)
+    }
+
+    fn random(&self, origin: Vec3, time: f64, u: (f64, f64)) -> Vec3 {
+        self.object.random(origin - self.offset, time, u)
+    }
 }
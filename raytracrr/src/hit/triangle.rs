@@ -0,0 +1,102 @@
+use std::{sync::Arc, ops::Range};
+
+use crate::{vec::Point3, material::Scatter, ray::Ray};
+use super::{aabb::AABB, Hit, hit_record::HitRecord};
+
+/*
+ *       c
+ *      / \
+ *     /   \
+ *    a --- b
+ *
+ * moller-trumbore: any point on the triangle is P = (1 - b1 - b2) a + b1 b + b2 c,
+ * so solving R(t) = P gives a 3x3 linear system in (t, b1, b2)
+ *
+ *   -t d + b1 (b - a) + b2 (c - a) = o - a
+ *
+ * which cramer's rule turns into a handful of cross and dot products.
+ * the hit counts if b1 >= 0, b2 >= 0 and b1 + b2 <= 1.
+ *
+ * the outward normal is (b - a) x (c - a), so the front face is the one where
+ * a, b, c go counter-clockwise (the obj convention).
+ */
+
+pub struct Triangle {
+    a: Point3,
+    b: Point3,
+    c: Point3,
+    material: Arc<dyn Scatter>
+}
+
+impl Triangle {
+    pub fn new(a: Point3, b: Point3, c: Point3, material: Arc<dyn Scatter>) -> Triangle {
+        Triangle {
+            a, b, c, material
+        }
+    }
+
+    pub fn new_arc(a: Point3, b: Point3, c: Point3, material: Arc<dyn Scatter>) -> Arc<Box<dyn Hit>> {
+        Arc::new(Box::new(Triangle::new(a, b, c, material)))
+    }
+}
+
+impl Hit for Triangle {
+    fn hit(&self, r: &Ray, time_range: Range<f64>) -> Option<HitRecord> {
+        let (t, b1, b2) = intersect(r, self.a, self.b, self.c, time_range)?;
+        let outward_normal = (self.b - self.a).cross(self.c - self.a).normalized();
+
+        // without texture coordinates the barycentrics are the next best thing
+        let mut record = HitRecord {
+            p: r.at(t),
+            normal: outward_normal,
+            t,
+            u: b1,
+            v: b2,
+            material: Arc::clone(&self.material),
+            front_face: false
+        };
+        record.set_face_normal(r, outward_normal);
+        Some(record)
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> AABB {
+        triangle_box(self.a, self.b, self.c)
+    }
+}
+
+// returns (t, b1, b2) of the hit, shared with Mesh which keeps its vertices elsewhere
+pub fn intersect(r: &Ray, a: Point3, b: Point3, c: Point3, time_range: Range<f64>) -> Option<(f64, f64, f64)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+
+    let p = r.direction().cross(edge2);
+    let det = edge1.dot(p);
+    if det == 0.0 {
+        // ray parallel to the plane of the triangle
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let s = r.origin() - a;
+    let b1 = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let b2 = r.direction().dot(q) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inv_det;
+    if !time_range.contains(&t) {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+pub fn triangle_box(a: Point3, b: Point3, c: Point3) -> AABB {
+    AABB::from_points(&[a, b, c])
+}
@@ -1,6 +1,5 @@
-use crate::vec::{Point3, Vec3};
+use crate::vec::Vec3;
 use crate::ray::{Ray};
-use crate::material::{Scatter};
 use crate::hit::{Hit, HitRecord};
 use super::aabb::{AABB};
 use std::ops::Range;
@@ -0,0 +1,45 @@
+//! raytracrr as a library: build a [`World`] (by hand, or from one of the built-in
+//! [`scenes`]), point a [`Camera`] at it and hand both to a [`Renderer`] together with
+//! [`RenderSettings`]. The result is a [`Framebuffer`] of linear colours.
+//!
+//! ```no_run
+//! use raytracrr::{find_scene, RenderSettings, Renderer};
+//!
+//! let settings = RenderSettings { width: 200, height: 200, ..Default::default() };
+//! let scene = (find_scene("cornell_box").unwrap().build)(1.0, 0).unwrap();
+//! let image = Renderer::new(scene.world, scene.camera, settings).render().unwrap();
+//! image.write_ppm(&mut std::io::stdout()).unwrap();
+//! ```
+
+pub mod vec;
+pub mod ray;
+pub mod hit;
+pub mod camera;
+pub mod environment;
+pub mod checkpoint;
+pub mod material;
+pub mod texture;
+pub mod perlin;
+pub mod render;
+pub mod output;
+pub mod scenes;
+pub mod scene_file;
+pub mod obj;
+pub mod onb;
+pub mod matrix;
+pub mod animation;
+pub mod random;
+pub mod sampler;
+pub mod sequence;
+
+pub use camera::Camera;
+pub use hit::Hit;
+pub use hit::world::World;
+pub use material::Scatter;
+pub use render::{Accumulator, Adaptive, Framebuffer, RenderSettings, Renderer};
+pub use scenes::{find_scene, Scene, SceneBuilder, SCENES};
+pub use sequence::Sequence;
+pub use texture::Texture;
+pub use vec::{Colour, Point3, Vec3};
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
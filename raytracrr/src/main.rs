@@ -1,499 +1,219 @@
-mod vec;
-mod ray;
-mod hit;
-mod camera;
-mod material;
-mod texture;
-mod perlin;
+mod cli;
 
-use std::io::{stderr, Write};
-use hit::block::Block;
-use hit::constant_medium::ConstantMedium;
-use hit::rect::Rect;
-use hit::rotate::Rotate;
-use hit::translate::Translate;
-use material::diffuse::Diffuse;
-use rand::distributions::Uniform;
-use rand::{Rng, thread_rng};
-use std::sync::Arc;
+use std::fs;
+use std::io::{stderr, stdout, Write};
+use std::process::exit;
+use std::time::Instant;
 
-use vec::{Vec3, Colour, Point3};
-use ray::{Ray};
-use hit::{Hit};
-use hit::world::{World};
-use hit::sphere::{Sphere};
-use hit::moving_sphere::{MovingSphere};
-use camera::{Camera};
-use crate::hit::bvh::BVH;
-use crate::hit::quad::Quad;
-use crate::material::{matte::Matte, metal::Metal, dielectric::Dielectric};
-use crate::texture::checker::Checker;
-use crate::texture::image::Image;
-use crate::texture::noise::Noise;
-use crate::texture::solid::Solid;
+use cli::{Command, Options, SceneSource};
+use raytracrr::{checkpoint, output, random, scene_file, scenes, sequence};
+use raytracrr::{Accumulator, Error, Framebuffer, RenderSettings, Renderer, Scene, Sequence};
 
-fn ray_colour(r: &Ray, background: Colour, world: &World, depth: u64) -> Colour {
-    // ray going from origin (camera eye) to point on the screen
-    // linearly blends white and blue depending on the height of the y coordinate 
-    // after scaling the ray direction to unit length (−1.0 < y < 1.0). 
+fn main() {
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Render(options)) => *options,
+        Ok(Command::ListScenes) => {
+            println!("{}", cli::scene_list());
+            return;
+        }
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("run with --help for usage");
+            exit(2);
+        }
+    };
 
-    // because looking at the y height after normalizing the vector, 
-    // there's a horizontal gradient in addition to the vertical gradient.
+    // WORLD
+    let aspect_ratio = options.width as f64 / options.height as f64;
+    let scene = match &options.scene {
+        SceneSource::Builtin(name) => {
+            let builder = scenes::find_scene(name).unwrap();
+            (builder.build)(aspect_ratio, options.scene_seed).map_err(|e| format!("can't build scene {}: {}", name, e))
+        }
+        SceneSource::File(path) => scene_file::load(path, aspect_ratio).map_err(|e| e.to_string())
+    };
+    let mut scene = match scene {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(1);
+        }
+    };
 
-    // standard graphics trick of scaling that to 0.0 ≤ t ≤ 1.0
-    // t = 1.0 -> blue, t = 0.0 -> white, in between -> blend 
-    // forms "linear interpolation" between two things. A lerp is always of the form
-    // blendedValue = (1 − t) ⋅ startValue + t ⋅ endValue, 0.0 ≤ t ≤ 1.0
-    if depth <= 0 {
-        // exceeded ray bounce limit, no light gathered
-        return Colour::new(0.0, 0.0, 0.0);
+    if let Some(projection) = options.projection {
+        scene.camera = scene.camera.with_projection(projection);
+    }
+    if options.stereo.is_some() {
+        scene.camera = scene.camera.with_stereo(options.stereo);
     }
 
-    if let Some(record) = world.hit(r, 0.001..f64::INFINITY) {
-        let emit = record.material.as_ref().emitted(record.u, record.v, record.p);
-        if let Some((attenuation, scattered)) = record.material.scatter(r, &record) {
-            emit + attenuation * ray_colour(&scattered, background, world, depth - 1)
-        } else {
-            emit
+    let settings = RenderSettings {
+        width: options.width,
+        height: options.height,
+        samples_per_pixel: options.samples_per_pixel,
+        adaptive: options.adaptive,
+        max_depth: options.max_depth,
+        threads: options.threads,
+        tile_size: options.tile_size,
+        seed: options.seed,
+        sampler: options.sampler,
+        bvh: options.bvh,
+        light_sampling: options.light_sampling
+    };
+
+    if let Some(sequence) = &options.sequence {
+        if let Err(e) = render_sequence(scene, settings, sequence, &options) {
+            eprintln!("\nerror: {}", e);
+            exit(1);
         }
-    } else {
-        // let unit_direction = r.direction().normalized();
-        // let t = 0.5 * (unit_direction.y() as f64 + 1.0);
-        // (1.0 - t) * Colour::new(1.0, 1.0, 1.0) + t * Colour::new(0.5, 0.7, 1.0)
-        background
+        eprintln!("Done.");
+        return;
     }
-}
-
-fn cornell_box() -> World {
-
-    let mut world = World::new();
-
-    let red = Matte::new_arc(Solid::new_arc(Colour::new(0.65, 0.05, 0.05)));
-    let white = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.73, 0.73, 0.73))));
-    let green = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.12, 0.45, 0.15))));
-    let light = Diffuse::new_arc(Arc::new(Solid::new(Colour::new(15.0, 15.0, 15.0))));
-    
-    let left = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 2, green);
-    let right = Rect::new(0.0..555.0, 0.0..555.0, 0.0, 2, red);
-    let lightRect = Rect::new(213.0..343.0, 113.0..332.0, 554.0, 1, light.clone());
-    let bottom = Rect::new(0.0..555.0, 0.0..555.0, 0.0, 1, white.clone());
-    let top = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 1, white.clone());
-    let back = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 0, white.clone());
-
-    let box1 = Block::new(
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(165.0, 330.0, 165.0),
-        white.clone()
-    );
-    let mut box1_rotate = Rotate::new(Arc::new(box1), 35.0, 1);
-    box1_rotate = Rotate::new(Arc::new(box1_rotate), 25.0, 0);
-    box1_rotate = Rotate::new(Arc::new(box1_rotate), -15.0, 2);
-    let box1_translate = Translate::new(Arc::new(box1_rotate), Vec3::new(265.0, 0.0, 295.0));
-
-    let box2 = Block::new(
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(165.0, 165.0, 165.0),
-        white.clone(),
-    );
-    let box2_rotate = Rotate::new(Arc::new(box2), -18.0, 1);
-    let box2_translate = Translate::new(Arc::new(box2_rotate), Vec3::new(130.0, 0.0, 65.0));
-        
-    world.push(Arc::new(Box::new(left)));
-    world.push(Arc::new(Box::new(right)));
-    world.push(Arc::new(Box::new(bottom)));
-    world.push(Arc::new(Box::new(top)));
-    world.push(Arc::new(Box::new(back)));
-    world.push(Arc::new(Box::new(lightRect)));
-    world.push(Arc::new(Box::new(box1_translate)));
-    world.push(Arc::new(Box::new(box2_translate)));
-
-    world
-}
-
-fn cornell_smoke() -> World {
-
-    let mut world = World::new();
-
-    let red = Matte::new_arc(Solid::new_arc(Colour::new(0.65, 0.05, 0.05)));
-    let white = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.73, 0.73, 0.73))));
-    let green = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.12, 0.45, 0.15))));
-    let light = Diffuse::new_arc(Arc::new(Solid::new(Colour::new(15.0, 15.0, 15.0))));
-    
-    let left = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 2, green);
-    let right = Rect::new(0.0..555.0, 0.0..555.0, 0.0, 2, red);
-    let lightRect = Rect::new(213.0..343.0, 113.0..332.0, 554.0, 1, light.clone());
-    let bottom = Rect::new(0.0..555.0, 0.0..555.0, 0.0, 1, white.clone());
-    let top = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 1, white.clone());
-    let back = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 0, white.clone());
-
-    let box1 = Block::new(
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(165.0, 330.0, 165.0),
-        white.clone()
-    );
-    let mut box1_rotate = Rotate::new(Arc::new(box1), 35.0, 1);
-    box1_rotate = Rotate::new(Arc::new(box1_rotate), 25.0, 0);
-    box1_rotate = Rotate::new(Arc::new(box1_rotate), -15.0, 2);
-    let box1_translate = Translate::new_arc(Arc::new(box1_rotate), Vec3::new(265.0, 0.0, 295.0));
-
-    let box2 = Block::new(
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(165.0, 165.0, 165.0),
-        white.clone(),
-    );
-    let box2_rotate = Rotate::new(Arc::new(box2), -18.0, 1);
-    let box2_translate = Translate::new_arc(Arc::new(box2_rotate), Vec3::new(130.0, 0.0, 65.0));
-        
-    world.push(Arc::new(Box::new(left)));
-    world.push(Arc::new(Box::new(right)));
-    world.push(Arc::new(Box::new(bottom)));
-    world.push(Arc::new(Box::new(top)));
-    world.push(Arc::new(Box::new(back)));
-    world.push(Arc::new(Box::new(lightRect)));
-    world.push(ConstantMedium::new_arc(box1_translate, Solid::new_arc(Colour::new(1.0, 1.0, 1.0)), 0.06));
-    world.push(ConstantMedium::new_arc(box2_translate, Solid::new_arc(Colour::new(0.0, 1.0, 0.0)), 0.05));
 
-    world
-}
-
-fn cornell_smoke_without_light() -> World {
-
-    let mut world = World::new();
-
-    let white = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.73, 0.73, 0.73))));
-    let green = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.12, 0.45, 0.15))));
-
-    let box1 = Block::new(
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(165.0, 330.0, 165.0),
-        white.clone()
-    );
-    let mut box1_rotate = Rotate::new(Arc::new(box1), 35.0, 1);
-    box1_rotate = Rotate::new(Arc::new(box1_rotate), 25.0, 0);
-    box1_rotate = Rotate::new(Arc::new(box1_rotate), -15.0, 2);
-    let box1_translate = Translate::new_arc(Arc::new(box1_rotate), Vec3::new(265.0, 0.0, 295.0));
-
-    let box2 = Block::new(
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(165.0, 165.0, 165.0),
-        white.clone(),
-    );
-    let box2_rotate = Rotate::new(Arc::new(box2), -18.0, 1);
-    let box2_translate = Translate::new_arc(Arc::new(box2_rotate), Vec3::new(130.0, 0.0, 65.0));
-        
-    let checker = Checker::new_texture_arc(0.32, Colour::new(0.2, 0.3, 0.1),
-        Colour::new(0.9, 0.9, 0.9)
-    );
-    let mat_ground = Matte::new_arc(checker);
-    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, mat_ground.clone());
-
-    world.push(ConstantMedium::new_arc(box1_translate, Solid::new_arc(Colour::new(1.0, 1.0, 1.0)), 0.06));
-    world.push(ConstantMedium::new_arc(box2_translate, Solid::new_arc(Colour::new(0.0, 1.0, 0.0)), 0.01));
-    world.push(Arc::new(Box::new(ground_sphere)));
-
-    world
-}
-
-
-fn simple_light() -> World {
-    let mut world = World::new();
-
-    let mat_perlin = Matte::new_arc(Noise::new_arc(4.0));
-    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, mat_perlin.clone());
-    let sphere_center = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, mat_perlin);
-
-    let difflight = Diffuse::new_arc(Solid::new_arc(Colour::new(4.0,4.0,4.0)));
-    let _light = Quad::new(
-        Point3::new(3.0,1.0,-1.0), 
-        Vec3::new(-7.0,0.0,0.0), 
-        Vec3::new(0.0,-2.0,0.0), 
-        difflight.clone()
-    );
-    let light = Rect::new(3.0..5.0, 1.0..3.0, -1.0, 0, difflight.clone());
-    let lightball = Sphere::new(Point3::new(0.0, 7.0, 0.0), 2.0, difflight);
-
-    world.push(Arc::new(Box::new(ground_sphere)));
-    world.push(Arc::new(Box::new(light)));
-    // world.push(Arc::new(Box::new(lightball)));
-    world.push(Arc::new(Box::new(sphere_center)));
-
-    world
-}
-
-fn lots_of_spheres() -> World {
-    let mut world = World::new();
-    let mut rng = rand::thread_rng();
-
-    for a in -11..=11 {
-        for b in -11..=11 {
-            let choose_mat: f64 = rng.gen();
-            let center = Point3::new((a as f64) + rng.gen_range(0.0..0.9),
-                                     0.2,
-                                     (b as f64) + rng.gen_range(0.0..0.9));
-
-            if choose_mat < 0.8 {
-                // Diffuse
-                let albedo = Colour::random(0.0..1.0) * Colour::random(0.0..1.0);
-                let sphere_mat = Arc::new(
-                    Matte::new(
-                        Arc::new(
-                            Solid::new(albedo)
-                        )
-                    )
-                );
-                let center1 = center + Vec3::new(0.0, rng.gen_range(0.0..0.3), 0.0);
-                let sphere = Sphere::new(
-                    center, 0.2, sphere_mat
-                );
-                // let sphere = MovingSphere::new(
-                //     center, 
-                //     center1,
-                //     0.2, 
-                //     sphere_mat);
-                world.push(Arc::new(Box::new(sphere)));
-            } else if choose_mat < 0.95 {
-                // Metal
-                let albedo = Colour::random(0.4..1.0);
-                let fuzz = rng.gen_range(0.0..0.5);
-                let sphere_mat = Metal::new_arc(Solid::new_arc(albedo), fuzz);
-                let center1 = center + Vec3::new(0.0, rng.gen_range(0.0..0.5), 0.0);
-                let sphere = MovingSphere::new(
-                    center, 
-                    center1,
-                    0.2, 
-                    0.0..1.0,
-                    sphere_mat);
-                world.push(Arc::new(Box::new(sphere)));
-            } else {
-                // Glass
-                let sphere_mat = Dielectric::new_arc(1.5);
-                let center1 = center + Vec3::new(0.0, rng.gen_range(0.0..0.5), 0.0);
-                let sphere = MovingSphere::new(
-                    center, 
-                    center1,
-                    0.2, 
-                    0.0..1.0,
-                    sphere_mat);
-                world.push(Arc::new(Box::new(sphere)));
-            }
+    let mut renderer = Renderer::new(scene.world, scene.camera, settings).with_progress(|done, total| {
+        eprint!("\rTiles rendered: {:4}/{}", done, total);
+        stderr().flush().unwrap();
+    });
+    if let Some(environment) = scene.environment {
+        renderer = renderer.with_environment(environment);
+    }
+    if let Some(stats) = renderer.bvh_stats() {
+        eprintln!("BVH: {}", stats);
+    }
+    let image = match options.pass_samples {
+        Some(pass_samples) => render_passes(&renderer, &options, pass_samples),
+        None => renderer.render()
+    };
+    let image = match image {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("\nerror: {}", e);
+            exit(1);
         }
+    };
+    eprintln!();
+    if options.adaptive.is_some() {
+        let pixels = (image.width() * image.height()) as f64;
+        eprintln!("Samples: {:.1} per pixel on average", image.total_samples() as f64 / pixels);
     }
 
-    let checker = Checker::new_texture_arc(0.32, 
-            Colour::new(0.2, 0.3, 0.1),
-            Colour::new(0.9, 0.9, 0.9)
-    );
-    let mat_perlin = Matte::new_arc(Noise::new_arc(4.0));
-    let mat_ground = Matte::new_arc(checker);
-    let mat_center = Matte::new_arc(Arc::new(Image::new("earth.jpg").unwrap()));
-    let mat_left = Arc::new(Dielectric::new(1.5));
-    let mat_right = Metal::new_arc(Solid::new_arc(Colour::new(0.8, 0.6, 0.2)), 0.0);
-
-    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, mat_perlin.clone());
-    let sphere_center = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, mat_center);
-    let sphere_left = Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, mat_left);
-    let sphere_right = Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, mat_perlin);
-
-    let earth_texture = Image::new("earth.jpg").unwrap();
-    let earth_surface: Matte = Matte::new(Arc::new(earth_texture));
-    let globe = Sphere::new(Vec3::new(0.0, 0.0, 0.0), 2.0, Arc::new(earth_surface));
+    if let Some((path, format)) = &options.heatmap {
+        if let Err(e) = output::save(&image.sample_heatmap(), path, *format) {
+            eprintln!("error: {}", e);
+            exit(1);
+        }
+    }
 
-    world.push(Arc::new(Box::new(globe)));
-    world.push(Arc::new(Box::new(ground_sphere)));
-    world.push(Arc::new(Box::new(sphere_center)));
-    world.push(Arc::new(Box::new(sphere_left)));
-    world.push(Arc::new(Box::new(sphere_right)));
+    let written = match &options.output {
+        Some((path, format)) => output::save(&image, path, *format),
+        None => image
+            .write_ppm(&mut stdout().lock())
+            .map_err(|e| format!("can't write image to stdout: {}", e).into())
+    };
+    if let Err(e) = written {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
 
-    world
+    eprintln!("Done.");
 }
 
-pub fn final_scene() -> World {
-    let mut world = World::new();
-    let mut rand_eng = thread_rng();
-
-    let ground = Matte::new_arc(Solid::new_arc(Colour::new(0.48, 0.83, 0.53)));
-
-    let rand_dst = Uniform::from(1.0..=100.0);
-    const BOXES_PER_SIDE: u32 = 20;
-
-    let block = Block::new_arc(
-        Vec3::new(-1000.0, 0.0, -1000.0),
-        Vec3::new(1000.0, 0.0, 1000.0),
-        ground.clone(),
-    );
-    world.push(block);
-    
-    // for i in 0..BOXES_PER_SIDE {
-    //     let i = f64::from(i);
-    //     for j in 0..BOXES_PER_SIDE {
-    //         let j = f64::from(j);
-    //         const W: f64 = 100.0;
-    //         let x0 = -1000.0 + i * W;
-    //         let z0 = -1000.0 + j * W;
-    //         let y0 = 0.0;
-    //         let x1 = x0 + W;
-    //         let y1 = rand_eng.sample(rand_dst);
-    //         let z1 = z0 + W;
-
-    //         let block = Block::new_arc(
-    //             Vec3::new(x0, y0, z0),
-    //             Vec3::new(x1, y1, z1),
-    //             ground.clone(),
-    //         );
-    //         world.push(block);
-    //     }
-    // }
-
-    let light = Diffuse::new_arc(Solid::new_arc(Colour::new(7.0, 7.0, 7.0)));
-    world.push(Rect::new_arc(
-        123.0..423.0,
-        147.0..412.0,
-        554.0,
-        1,
-        light
-    ));
-
-    let centre1 = Vec3::new(400.0, 400.0, 200.0);
-    let centre2 = centre1 + Vec3::new(29.0, 0.0, 0.0);
-    let moving_sphere_material = Matte::new_arc(Solid::new_arc(Colour::new(0.7, 0.3, 0.1)));
-    world.push(MovingSphere::new_arc(
-        centre1,
-        centre2,
-        50.0,
-        0.0..1.0,
-        moving_sphere_material,
-    ));
-
-    // // glass
-    world.push(Arc::new(Box::new(Sphere::new(
-        Vec3::new(260.0, 150.0, 45.0),
-        50.0,
-        Dielectric::new_arc(1.5),
-    ))));
-
-    // matte lower right
-    world.push(Arc::new(Box::new(Sphere::new(
-        Vec3::new(0.0, 150.0, 145.0),
-        50.0,
-        Metal::new_arc(Solid::new_arc(Colour::new(0.8, 0.8, 0.9)), 1.0),
-    ))));
-
-    // 
-    let boundary = Sphere::new(
-        Vec3::new(360.0, 150.0, 145.0),
-        71.0,
-        Dielectric::new_arc(1.5),
-    );
-    world.push(Arc::new(Box::new(boundary)));
-    let boundary = Sphere::new_arc(
-        Vec3::new(360.0, 150.0, 145.0),
-        70.0,
-        Dielectric::new_arc(1.5),
-    );
-    world.push(ConstantMedium::new_arc(
-        boundary,
-        Solid::new_arc(Colour::new(0.2, 0.4, 0.9)),
-        0.2,
-    ));
-
-    let boundary = Sphere::new_arc(
-        Vec3::new(0.0, 0.0, 0.0), 
-        5000.0, 
-        Dielectric::new_arc(1.5)
-    );
-    world.push(ConstantMedium::new_arc(
-        boundary,
-        Solid::new_arc(Colour::new(1.0, 1.0, 1.0)),
-        0.0001
-    ));
-
-    let emat = Matte::new_arc(Arc::new(Image::new("earth.jpg").unwrap()));
-    world.push(Arc::new(Box::new(Sphere::new(
-        Vec3::new(400.0, 200.0, 400.0), 
-        100.0, 
-        emat)))
-    );
-
-    let pertext = Noise::new_arc(0.1);
-    world.push(Arc::new(Box::new(Sphere::new(
-        Vec3::new(220.0, 280.0, 300.0),
-        80.0,
-        Matte::new_arc(pertext),
-    ))));
-
-    // let mut boxes2: Vec<_> = Vec::new();
-    // let white = Matte::new_arc(Solid::new_arc(Colour::new(0.73, 0.73, 0.73)));
-    // const NS: u32 = 15;
-    // for _ in 0..NS {
-    //     boxes2.push(Sphere::new_hittable(
-    //         Vec3::random(0.0..165.0),
-    //         10.0,
-    //         white.clone(),
-    //     ));
-    // }
-
-    // world.push(Arc::new(Box::new(Translate::new(
-    //     Rotate::new_arc(Arc::new(BVH::new(&mut boxes2, 0.0..1.0)), 15.0, 1),
-    //     Vec3::new(-100.0, 270.0, 395.0),
-    // ))));
-
-    world
+// renders every frame to its own file. every frame has a seed of its own, or the noise would
+// stand still while the picture moves
+fn render_sequence(scene: Scene, settings: RenderSettings, sequence: &Sequence, options: &Options) -> Result<(), Error> {
+    let animation = if options.turntable {
+        Some(sequence.turntable(&scene.camera))
+    } else {
+        scene.camera_animation
+    };
+    let (path, format) = options.output.as_ref().ok_or("frames need an output file")?;
+
+    for frame in sequence.frames.clone() {
+        let camera = sequence.camera(&scene.camera, animation.as_ref(), frame);
+        let settings = RenderSettings { seed: random::hash(settings.seed, &[frame]), ..settings.clone() };
+        let mut renderer = Renderer::new(scene.world.clone(), camera, settings).with_progress(move |done, total| {
+            eprint!("\rFrame {}: tiles rendered {:4}/{}", frame, done, total);
+            stderr().flush().unwrap();
+        });
+        if let Some(environment) = &scene.environment {
+            renderer = renderer.with_environment(environment.clone());
+        }
+        let image = renderer.render()?;
+        output::save(&image, &sequence::frame_path(path, frame), *format)?;
+        if let Some((path, format)) = &options.heatmap {
+            output::save(&image.sample_heatmap(), &sequence::frame_path(path, frame), *format)?;
+        }
+        eprintln!();
+    }
+    Ok(())
 }
 
-fn main() {
-    // IMAGE
-    const ASPECT_RATIO: f64 = 1.0;
-    const IMAGE_WIDTH: u64 = 800; // 800
-    const IMAGE_HEIGHT: u64 = ((IMAGE_WIDTH as f64) / ASPECT_RATIO) as u64;
-    const SAMPLES_PER_PIXEL: u64 = 1000; // 10000
-    const MAX_DEPTH: u64 = 10; // 50
-
-    // WORLD
-    let world = final_scene();
-
-    let lookfrom = Point3::new(478.0, 278.0, -600.0);
-    let lookat = Point3::new(278.0, 278.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.0;
-
-    let camera = Camera::new(lookfrom,
-        lookat,
-        vup,
-        40.0,
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
-        Colour::new(0.00, 0.00, 0.00)
-    );
-
-    println!("P3");
-    println!("{} {}", IMAGE_WIDTH, IMAGE_HEIGHT);
-    println!("255");
-
-    let mut rng = rand::thread_rng();
-    for j in (0..IMAGE_HEIGHT).rev() {
-        eprint!("\rScanlines remaining: {:3}", IMAGE_HEIGHT - j - 1);
-        stderr().flush().unwrap();
-        // traverse the screen from the upper left hand corner 
-        // use two offset vectors along the screen sides to move the ray endpoint across the screen.
-        for i in 0..IMAGE_WIDTH {
-            let mut pixel = Colour::new(0.0, 0.0, 0.0);
-
-            for _ in 0..SAMPLES_PER_PIXEL {
-                let random_u : f64 = rng.gen();
-                let random_v : f64 = rng.gen();
-
-                let u = (i as f64 + random_u) / ((IMAGE_WIDTH - 1) as f64);
-                let v = (j as f64 + random_v) / ((IMAGE_HEIGHT - 1) as f64);
-    
-                let r = camera.get_ray(u, v);
-                pixel += ray_colour(&r, camera.background, &world, MAX_DEPTH);
+// renders pass after pass, rewriting the output file with the image so far as they finish,
+// and saving and resuming checkpoints
+fn render_passes(renderer: &Renderer, options: &Options, pass_samples: u64) -> Result<Framebuffer, Error> {
+    let scene_hash = match &options.checkpoint {
+        Some(_) => scene_hash(options)?,
+        None => 0
+    };
+    let mut state = match &options.checkpoint {
+        Some(path) if options.resume => {
+            let state = checkpoint::load(path, renderer.settings(), scene_hash)?;
+            eprintln!(
+                "Resuming after {} passes, {} samples per pixel", state.passes(), state.samples_per_pixel()
+            );
+            state
+        }
+        _ => Accumulator::new(options.width, options.height)
+    };
+    let mut saved_passes = state.passes();
+
+    let mut last_snapshot = Instant::now();
+    let mut last_checkpoint = Instant::now();
+    renderer.render_progressive(&mut state, pass_samples, options.time_limit, |state| {
+        eprint!(" - pass {}, {} samples per pixel", state.passes(), state.samples_per_pixel());
+        if let Some((path, format)) = &options.output {
+            if last_snapshot.elapsed() >= options.snapshot_interval {
+                output::save(&state.framebuffer(), path, *format)?;
+                last_snapshot = Instant::now();
+                eprint!(", saved");
+            }
+        }
+        if let Some(path) = &options.checkpoint {
+            if last_checkpoint.elapsed() >= options.checkpoint_interval {
+                checkpoint::save(path, state, renderer.settings(), scene_hash)?;
+                last_checkpoint = Instant::now();
+                saved_passes = state.passes();
+                eprint!(", checkpoint saved");
             }
+        }
+        eprintln!();
+        Ok(())
+    })?;
 
-            println!("{}", pixel.format_color(SAMPLES_PER_PIXEL));
+    if let Some(path) = &options.checkpoint {
+        if saved_passes != state.passes() {
+            checkpoint::save(path, &state, renderer.settings(), scene_hash)?;
         }
     }
+    if state.samples_per_pixel() < options.samples_per_pixel {
+        eprint!("\nTime limit reached after {} passes", state.passes());
+    }
+    Ok(state.framebuffer())
+}
 
-    eprintln!("Done.");
+// built-in scenes are made by the code, so their name and seed stand for them. for a scene
+// file it's the text of the file, the meshes and images it refers to aren't looked at.
+// the camera options that change what the scene looks like are added to both
+fn scene_hash(options: &Options) -> Result<u64, Error> {
+    let mut source = match &options.scene {
+        SceneSource::Builtin(name) => format!("built-in scene {} seed {}", name, options.scene_seed).into_bytes(),
+        SceneSource::File(path) => fs::read(path)
+            .map_err(|e| format!("can't read scene file {}: {}", path.display(), e))?
+    };
+    source.extend(format!("\nprojection {:?} stereo {:?}", options.projection, options.stereo).into_bytes());
+    Ok(checkpoint::scene_hash(&source))
 }
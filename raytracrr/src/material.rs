@@ -3,16 +3,59 @@ pub mod matte;
 pub mod dielectric;
 pub mod diffuse;
 pub mod isotropic;
+pub mod conductor;
+pub mod microfacet;
 
 use super::vec::{Vec3, Colour};
 use super::hit::hit_record::{HitRecord};
-use super::ray::{Ray};
 
+// how a material scatters light, described by its bsdf (or phase function, for volumes).
+// directions are unit vectors pointing away from the hit point: wo towards where the ray came
+// from (the camera side), wi towards where the light comes from. record.normal faces wo.
+//
+// eval(wi, wo) is the bsdf times |cos| of wi to the normal (volumes have no cosine), so the
+// light leaving towards wo because of light arriving from wi is eval * incoming.
+// sample picks wi with some density pdf(wi, wo), ideally close to eval so eval / pdf is flat.
+// it turns uniform numbers in [0, 1) from the sampler into the direction: uc to choose between
+// lobes, u for the direction inside the lobe.
+//
+// mirrors and glass only scatter into one exact direction (a delta lobe): eval and pdf can
+// never find it and are 0, only sample can, and says so with delta.
 pub trait Scatter : Send + Sync {
-    fn scatter(&self, r_in: &Ray, record: &HitRecord) -> Option<(Colour, Ray)>;
+    fn eval(&self, _record: &HitRecord, _wi: Vec3, _wo: Vec3) -> Colour {
+        Colour::new(0.0, 0.0, 0.0)
+    }
+
+    // None if the light is absorbed
+    fn sample(&self, _record: &HitRecord, _wo: Vec3, _uc: f64, _u: (f64, f64)) -> Option<ScatterSample> {
+        None
+    }
+
+    fn pdf(&self, _record: &HitRecord, _wi: Vec3, _wo: Vec3) -> f64 {
+        0.0
+    }
 
     // just tells the ray what color it is and performs no reflection
-    fn emitted(&self, u: f64, v: f64, p: Vec3) -> Colour {
+    fn emitted(&self, _u: f64, _v: f64, _p: Vec3) -> Colour {
         Colour::new(0.0, 0.0, 0.0)
     }
+
+    // whether emitted can return anything but black, shapes made of it are light sources
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // what's left of light that has gone distance through the inside of the material, to a
+    // surface of it seen from the inside (record.front_face false). coloured glass absorbs
+    // some on the way
+    fn transmittance(&self, _distance: f64) -> Colour {
+        Colour::new(1.0, 1.0, 1.0)
+    }
+}
+
+pub struct ScatterSample {
+    pub wi: Vec3,
+    pub weight: Colour, // eval / pdf, or what a delta lobe lets through
+    pub pdf: f64, // for a delta lobe, the chance it was picked
+    pub delta: bool
 }
@@ -0,0 +1,289 @@
+use std::sync::Arc;
+
+use crate::material::{Scatter, ScatterSample};
+use crate::material::microfacet::Microfacet;
+use crate::onb::Onb;
+use crate::texture::Texture;
+use crate::vec::{Colour, Vec3};
+use crate::hit::hit_record::{HitRecord};
+
+// a metal: a rough mirror made of microfacets (microfacet.rs), each reflecting as much as the
+// fresnel equations say for the metal's complex index of refraction, eta + i k, for red,
+// green and blue. that is what gives gold its colour, and every metal a whiter sheen at
+// grazing angles.
+//
+// roughness is a texture, read as grey (0 is a mirror, 1 very rough). a metal with a grain,
+// like brushed steel, has a roughness of its own across the grain: grain is the direction
+// along it, in world space, laid flat onto the surface wherever it is hit.
+// light hidden from or bounced between the facets is lost, so very rough metals come out a
+// little dark
+pub struct Conductor {
+    eta: Colour,
+    k: Colour,
+    roughness: Arc<dyn Texture>,
+    across: Option<(Arc<dyn Texture>, Vec3)> // the roughness across the grain, and the grain
+}
+
+// measured metals, at 650, 550 and 450 nm
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Preset {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver
+}
+
+impl Preset {
+    // eta and k
+    pub fn ior(self) -> (Colour, Colour) {
+        match self {
+            Preset::Gold => (Colour::new(0.143, 0.374, 1.442), Colour::new(3.983, 2.385, 1.603)),
+            Preset::Copper => (Colour::new(0.200, 0.924, 1.102), Colour::new(3.912, 2.452, 2.142)),
+            Preset::Aluminium => (Colour::new(1.657, 0.880, 0.521), Colour::new(9.224, 6.270, 4.837)),
+            Preset::Silver => (Colour::new(0.155, 0.117, 0.138), Colour::new(4.828, 3.122, 2.147))
+        }
+    }
+}
+
+impl Conductor {
+    pub fn new(eta: Colour, k: Colour, roughness: Arc<dyn Texture>) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            roughness,
+            across: None
+        }
+    }
+
+    pub fn new_arc(eta: Colour, k: Colour, roughness: Arc<dyn Texture>) -> Arc<Conductor> {
+        Arc::new(Conductor::new(eta, k, roughness))
+    }
+
+    pub fn preset(preset: Preset, roughness: Arc<dyn Texture>) -> Conductor {
+        let (eta, k) = preset.ior();
+        Conductor::new(eta, k, roughness)
+    }
+
+    pub fn with_grain(self, grain: Vec3, roughness_across: Arc<dyn Texture>) -> Conductor {
+        Conductor {
+            across: Some((roughness_across, grain)),
+            ..self
+        }
+    }
+
+    // the surface's frame and facets where record hit it
+    fn lobe(&self, record: &HitRecord) -> (Onb, Microfacet) {
+        let roughness = |texture: &Arc<dyn Texture>| texture.value(record.u, record.v, record.p).luminance();
+        let along = roughness(&self.roughness);
+        match &self.across {
+            Some((across, grain)) => (Onb::new_tangent(record.normal, *grain), Microfacet::new(along, roughness(across))),
+            None => (Onb::new(record.normal), Microfacet::new(along, along))
+        }
+    }
+
+    // the share of light a facet reflects, cos_i from its normal, exactly for unpolarised
+    // light
+    fn fresnel(&self, cos_i: f64) -> Colour {
+        let cos_i = cos_i.clamp(0.0, 1.0);
+        let (cos2, sin2) = (cos_i * cos_i, 1.0 - cos_i * cos_i);
+        let mut reflectance = Colour::new(0.0, 0.0, 0.0);
+        for i in 0..3 {
+            let (eta2, k2) = (self.eta[i] * self.eta[i], self.k[i] * self.k[i]);
+            let t0 = eta2 - k2 - sin2;
+            let a2_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+            let a = ((a2_b2 + t0) / 2.0).max(0.0).sqrt();
+            let t1 = a2_b2 + cos2;
+            let t2 = 2.0 * cos_i * a;
+            let rs = (t1 - t2) / (t1 + t2);
+            let t3 = cos2 * a2_b2 + sin2 * sin2;
+            let t4 = t2 * sin2;
+            let rp = rs * (t3 - t4) / (t3 + t4);
+            reflectance[i] = (rs + rp) / 2.0;
+        }
+        reflectance
+    }
+}
+
+// the light leaving towards wo comes off the facets facing halfway between wo and wi: the
+// brdf is fresnel * d * g / (4 cos_o cos_i). facets are picked as they are seen from wo, which
+// leaves little but fresnel and the hidden share for the sample to carry
+impl Scatter for Conductor {
+    fn eval(&self, record: &HitRecord, wi: Vec3, wo: Vec3) -> Colour {
+        let (frame, facets) = self.lobe(record);
+        let (wi, wo) = (frame.to_local(wi), frame.to_local(wo));
+        if facets.is_smooth() || wi.z() <= 0.0 || wo.z() <= 0.0 {
+            return Colour::new(0.0, 0.0, 0.0);
+        }
+        let h = (wi + wo).normalized();
+        facets.d(h) * facets.g(wo, wi) / (4.0 * wo.z()) * self.fresnel(wo.dot(h))
+    }
+
+    fn sample(&self, record: &HitRecord, wo: Vec3, _uc: f64, u: (f64, f64)) -> Option<ScatterSample> {
+        let (frame, facets) = self.lobe(record);
+        let wo = frame.to_local(wo);
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        if facets.is_smooth() {
+            return Some(ScatterSample {
+                wi: frame.local(Vec3::new(-wo.x(), -wo.y(), wo.z())),
+                weight: self.fresnel(wo.z()),
+                pdf: 1.0,
+                delta: true
+            });
+        }
+
+        let h = facets.sample_visible(wo, u);
+        let wi = ((-1.0) * wo).reflect(h);
+        if wi.z() <= 0.0 || wo.dot(h) <= 0.0 {
+            return None;
+        }
+        Some(ScatterSample {
+            wi: frame.local(wi),
+            weight: facets.g(wo, wi) / facets.g1(wo) * self.fresnel(wo.dot(h)),
+            pdf: facets.pdf_visible(wo, h) / (4.0 * wo.dot(h)),
+            delta: false
+        })
+    }
+
+    fn pdf(&self, record: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+        let (frame, facets) = self.lobe(record);
+        let (wi, wo) = (frame.to_local(wi), frame.to_local(wo));
+        if facets.is_smooth() || wi.z() <= 0.0 || wo.z() <= 0.0 {
+            return 0.0;
+        }
+        let h = (wi + wo).normalized();
+        facets.pdf_visible(wo, h) / (4.0 * wo.dot(h))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::random;
+    use crate::sampler;
+    use crate::texture::solid::Solid;
+
+    fn record(normal: Vec3) -> HitRecord {
+        HitRecord {
+            p: Vec3::new(0.0, 0.0, 0.0),
+            normal,
+            material: Conductor::new_arc(Colour::new(1.0, 1.0, 1.0), Colour::new(1.0, 1.0, 1.0), grey(0.0)),
+            t: 1.0,
+            u: 0.0,
+            v: 0.0,
+            front_face: true
+        }
+    }
+
+    fn grey(value: f64) -> Arc<dyn Texture> {
+        Solid::new_arc(Colour::new(value, value, value))
+    }
+
+    // reflects everything at every angle
+    fn perfect(roughness: f64) -> Conductor {
+        Conductor::new(Colour::new(1.0, 1.0, 1.0), Colour::new(1e4, 1e4, 1e4), grey(roughness))
+    }
+
+    // every sample carries exactly eval / pdf, and pdf agrees with the density sample says it
+    // picked with
+    fn check(conductor: &Conductor) {
+        let mut rng = random::seeded(7);
+        let mut lobes = 0;
+        for _ in 0..20000 {
+            let normal = sampler::sphere((rng.gen(), rng.gen()));
+            let record = record(normal);
+            let wo = Onb::new(normal).local(sampler::cosine_hemisphere((rng.gen(), rng.gen())));
+            if wo.dot(normal) < 1e-3 {
+                continue;
+            }
+            let sample = match conductor.sample(&record, wo, rng.gen(), (rng.gen(), rng.gen())) {
+                Some(sample) => sample,
+                None => continue
+            };
+            assert!(!sample.delta && sample.pdf > 0.0 && sample.pdf.is_finite());
+            let eval = conductor.eval(&record, sample.wi, wo);
+            let pdf = conductor.pdf(&record, sample.wi, wo);
+            assert!((pdf - sample.pdf).abs() <= 1e-6 * sample.pdf, "pdf {} sampled with {}", pdf, sample.pdf);
+            for i in 0..3 {
+                let weight = eval[i] / pdf;
+                assert!((weight - sample.weight[i]).abs() <= 1e-6 * weight.max(1.0), "eval / pdf {} weight {}", weight, sample.weight[i]);
+            }
+            lobes += 1;
+        }
+        assert!(lobes > 10000);
+    }
+
+    #[test]
+    fn weight_is_eval_over_pdf() {
+        for roughness in [0.05, 0.3, 0.7, 1.0] {
+            check(&Conductor::preset(Preset::Gold, grey(roughness)));
+        }
+    }
+
+    #[test]
+    fn brushed_weight_is_eval_over_pdf() {
+        for (along, across) in [(0.1, 0.5), (0.6, 0.05), (0.3, 1.0)] {
+            check(&Conductor::preset(Preset::Aluminium, grey(along)).with_grain(Vec3::new(1.0, 2.0, 0.5), grey(across)));
+        }
+    }
+
+    // a metal that reflects all the light its facets get loses some between them, and never
+    // makes any: the light leaving, over every direction it can leave in, is at most 1.
+    // found both ways, from the samples and by integrating eval over the hemisphere
+    #[test]
+    fn white_furnace() {
+        let mut rng = random::seeded(3);
+        let record = record(Vec3::new(0.0, 0.0, 1.0));
+        let n = 100000;
+        for conductor in [perfect(0.2), perfect(0.6), perfect(1.0), perfect(0.5).with_grain(Vec3::new(1.0, 0.0, 0.0), grey(0.1))] {
+            for cos_o in [1.0_f64, 0.7, 0.3, 0.1] {
+                let wo = Vec3::new((1.0 - cos_o * cos_o).sqrt(), 0.0, cos_o);
+                let mut sampled = 0.0;
+                let (mut integrated, mut squares) = (0.0, 0.0);
+                for _ in 0..n {
+                    if let Some(sample) = conductor.sample(&record, wo, rng.gen(), (rng.gen(), rng.gen())) {
+                        assert!(sample.weight[0] <= 1.0 + 1e-9);
+                        sampled += sample.weight[0];
+                    }
+                    // eval / (cos / pi), cosine sampled
+                    let wi = sampler::cosine_hemisphere((rng.gen(), rng.gen()));
+                    if wi.z() > 0.0 {
+                        let estimate = conductor.eval(&record, wi, wo)[0] * std::f64::consts::PI / wi.z();
+                        integrated += estimate;
+                        squares += estimate * estimate;
+                    }
+                }
+                let (sampled, integrated) = (sampled / n as f64, integrated / n as f64);
+                // cosine sampling a narrow lobe is noisy, allowed four standard errors
+                let error = 4.0 * ((squares / n as f64 - integrated * integrated) / n as f64).sqrt();
+                assert!(sampled <= 1.0, "albedo {} at cos {}", sampled, cos_o);
+                assert!(integrated <= 1.0 + error, "albedo {} +- {} at cos {}", integrated, error, cos_o);
+                assert!((sampled - integrated).abs() < error + 0.01, "sampled {} integrated {} at cos {}", sampled, integrated, cos_o);
+            }
+        }
+    }
+
+    // head on, the fresnel equations for a complex index come down to
+    // ((eta - 1)^2 + k^2) / ((eta + 1)^2 + k^2): gold reflects about 97% of red light but only
+    // about a third of blue. at grazing angles every metal reflects everything
+    #[test]
+    fn gold_fresnel() {
+        let gold = Conductor::preset(Preset::Gold, grey(0.0));
+        let (eta, k) = Preset::Gold.ior();
+        let head_on = gold.fresnel(1.0);
+        for i in 0..3 {
+            let expected = ((eta[i] - 1.0).powi(2) + k[i] * k[i]) / ((eta[i] + 1.0).powi(2) + k[i] * k[i]);
+            assert!((head_on[i] - expected).abs() < 1e-12, "{} against {}", head_on[i], expected);
+        }
+        assert!((head_on[0] - 0.967).abs() < 1e-3 && (head_on[2] - 0.325).abs() < 1e-3, "{:?}", head_on.e);
+
+        let grazing = gold.fresnel(0.0);
+        for i in 0..3 {
+            assert!((grazing[i] - 1.0).abs() < 1e-9);
+        }
+    }
+}
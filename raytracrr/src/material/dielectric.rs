@@ -1,24 +1,37 @@
 use std::sync::Arc;
 
-use rand::{Rng};
-
-use crate::material::{Scatter};
+use crate::material::{Scatter, ScatterSample};
+use crate::material::microfacet::Microfacet;
+use crate::onb::Onb;
+use crate::texture::Texture;
 use crate::vec::{Colour, Vec3};
-use crate::ray::{Ray};
 use crate::hit::hit_record::{HitRecord};
 
-// with dielectric spheres is to note that if you use a negative radius, the geometry is unaffected, 
+// with dielectric spheres is to note that if you use a negative radius, the geometry is unaffected,
 // but the surface normal points inward. This can be used as a bubble to make a hollow glass sphere:
 
+// glass, smooth unless it has a roughness: then it is made of microfacets (microfacet.rs)
+// that each reflect or refract like smooth glass, frosting what is seen through it.
+// coloured glass absorbs light on its way through (beer-lambert), more the further it goes.
+//
+// a thin sheet is a single surface standing for both sides of a pane of glass thickness
+// thick, like a window: light that gets in comes straight out the other side, after bouncing
+// between the two sides, and isn't bent on the way
 pub struct Dielectric {
-    ir: f64
+    ir: f64,
+    roughness: Option<Arc<dyn Texture>>,
+    absorption: Colour, // the share of light lost per unit of distance, for red, green and blue
+    thin: Option<f64> // the thickness of a thin sheet
 }
 
 impl Dielectric {
     // ir: index of refraction
     pub fn new(ir: f64) -> Dielectric {
         Dielectric {
-            ir
+            ir,
+            roughness: None,
+            absorption: Colour::new(0.0, 0.0, 0.0),
+            thin: None
         }
     }
 
@@ -32,35 +45,341 @@ impl Dielectric {
     pub fn new_arc(ir: f64) -> Arc<Dielectric> {
         Arc::new(Dielectric::new(ir))
     }
-}
 
-impl Scatter for Dielectric {
-    fn scatter(&self, r_in: &Ray, record: &HitRecord) -> Option<(Colour, Ray)> {
-        let refraction_ratio = if record.front_face {
+    // roughness from 0 to 1, read as grey
+    pub fn with_roughness(self, roughness: Arc<dyn Texture>) -> Dielectric {
+        Dielectric {
+            roughness: Some(roughness),
+            ..self
+        }
+    }
+
+    // light that has gone distance through the glass is left with colour
+    pub fn with_absorption(self, colour: Colour, distance: f64) -> Dielectric {
+        let mut absorption = Colour::new(0.0, 0.0, 0.0);
+        for i in 0..3 {
+            absorption[i] = -colour[i].clamp(1e-6, 1.0).ln() / distance;
+        }
+        Dielectric {
+            absorption,
+            ..self
+        }
+    }
+
+    pub fn with_thin_sheet(self, thickness: f64) -> Dielectric {
+        Dielectric {
+            thin: Some(thickness),
+            ..self
+        }
+    }
+
+    fn refraction_ratio(&self, record: &HitRecord) -> f64 {
+        // a thin sheet has air on both sides
+        if record.front_face || self.thin.is_some() {
             1.0 / self.ir
         } else {
             self.ir
+        }
+    }
+
+    // the share of light reflected, cosine from the normal on the side of refraction_ratio.
+    // when the ray is in the material with the higher refractive index, there is not always be a
+    // solution to Snell’s law within the real numbers, and thus there is no refraction possible.
+    fn fresnel(cosine: f64, refraction_ratio: f64) -> f64 {
+        let sin_theta = (1.0 - cosine.powi(2)).sqrt();
+        if refraction_ratio * sin_theta > 1.0 {
+            1.0
+        } else {
+            Self::reflectance(cosine, refraction_ratio)
+        }
+    }
+
+    // the share of light a thin sheet reflects, on the first side it comes to or after going
+    // back and forth between the two
+    fn sheet_reflectance(&self, cosine: f64) -> f64 {
+        let r = Self::reflectance(cosine, 1.0 / self.ir);
+        2.0 * r / (1.0 + r)
+    }
+
+    // what's left of the light going once through a thin sheet, cosine from its normal
+    fn sheet_transmittance(&self, cosine: f64) -> Colour {
+        let thickness = self.thin.unwrap_or(0.0);
+        let cos_inside = (1.0 - (1.0 - cosine * cosine) / (self.ir * self.ir)).sqrt();
+        self.absorbed(thickness / cos_inside)
+    }
+
+    // what's left of the light after going distance through the glass
+    fn absorbed(&self, distance: f64) -> Colour {
+        let mut left = Colour::new(1.0, 1.0, 1.0);
+        for i in 0..3 {
+            left[i] = (-self.absorption[i] * distance).exp();
+        }
+        left
+    }
+
+    // the surface's frame and facets where record hit it, None if it is smooth there
+    fn rough(&self, record: &HitRecord) -> Option<(Onb, Microfacet)> {
+        let roughness = self.roughness.as_ref()?.value(record.u, record.v, record.p).luminance();
+        let facets = Microfacet::new(roughness, roughness);
+        if facets.is_smooth() {
+            return None;
+        }
+        Some((Onb::new(record.normal), facets))
+    }
+
+    // a single reflected or refracted direction, picked with the chance given by the fresnel
+    // reflectance. that chance is exactly the share of light going that way, so it cancels and
+    // the sample carries everything.
+    fn sample_smooth(&self, record: &HitRecord, wo: Vec3, uc: f64) -> ScatterSample {
+        let unit_direction = (-1.0) * wo;
+        let cos_theta = wo.dot(record.normal).min(1.0);
+
+        if self.thin.is_some() {
+            let reflectance = self.sheet_reflectance(cos_theta);
+            let (wi, weight, pdf) = if uc < reflectance {
+                (unit_direction.reflect(record.normal), Colour::new(1.0, 1.0, 1.0), reflectance)
+            } else {
+                (unit_direction, self.sheet_transmittance(cos_theta), 1.0 - reflectance)
+            };
+            return ScatterSample { wi, weight, pdf, delta: true };
+        }
+
+        let refraction_ratio = self.refraction_ratio(record);
+        let reflectance = Self::fresnel(cos_theta, refraction_ratio);
+        let will_reflect = uc < reflectance;
+
+        let (wi, pdf) = if will_reflect {
+            (unit_direction.reflect(record.normal), reflectance)
+        } else {
+            (unit_direction.refract(record.normal, refraction_ratio), 1.0 - reflectance)
         };
 
-        let unit_direction = r_in.direction().normalized();
+        ScatterSample {
+            wi,
+            weight: Colour::new(1.0, 1.0, 1.0),
+            pdf,
+            delta: true
+        }
+    }
 
-        // when the ray is in the material with the higher refractive index, there is not always be a 
-        // solution to Snell’s law within the real numbers, and thus there is no refraction possible. 
-        let cos_theta = ((-1.0) * unit_direction).dot(record.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+    // the facet normal h that scatters wo into wi, in the surface's frame, and whether wi went
+    // through. a thin sheet lets light through as the reflection would have gone, mirrored
+    // to the other side
+    fn half_vector(&self, wi: Vec3, wo: Vec3, refraction_ratio: f64) -> (Vec3, bool) {
+        let through = wi.z() < 0.0;
+        let h = if !through {
+            wi + wo
+        } else if self.thin.is_some() {
+            Vec3::new(wi.x(), wi.y(), -wi.z()) + wo
+        } else {
+            // light refracted by h has refraction_ratio wo + wi along h
+            refraction_ratio * wo + wi
+        };
+        let h = h.normalized();
+        (if h.z() < 0.0 { (-1.0) * h } else { h }, through)
+    }
 
-        let mut rng = rand::thread_rng();
-        let cannot_reflect = refraction_ratio * sin_theta > 1.0;
-        let will_reflect = rng.gen::<f64>() < Self::reflectance(cos_theta, refraction_ratio);
+    // eval and pdf together, for rough glass
+    fn eval_pdf(&self, record: &HitRecord, wi: Vec3, wo: Vec3) -> (Colour, f64) {
+        let black = (Colour::new(0.0, 0.0, 0.0), 0.0);
+        let (frame, facets) = match self.rough(record) {
+            Some(rough) => rough,
+            None => return black
+        };
+        let (wi, wo) = (frame.to_local(wi), frame.to_local(wo));
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return black;
+        }
+        let refraction_ratio = self.refraction_ratio(record);
+        let (h, through) = self.half_vector(wi, wo, refraction_ratio);
+        let cos_o = wo.dot(h);
+        if cos_o <= 0.0 {
+            return black;
+        }
+        let visible = facets.pdf_visible(wo, h);
+        let d_g = facets.d(h) * facets.g(wo, wi);
+
+        if self.thin.is_some() {
+            let reflectance = self.sheet_reflectance(cos_o);
+            let (share, chance) = if through {
+                ((1.0 - reflectance) * self.sheet_transmittance(cos_o), 1.0 - reflectance)
+            } else {
+                (Colour::new(reflectance, reflectance, reflectance), reflectance)
+            };
+            return (d_g / (4.0 * wo.z()) * share, chance * visible / (4.0 * cos_o));
+        }
+
+        let cos_i = wi.dot(h);
+        if (cos_i < 0.0) != through {
+            return black;
+        }
+        let reflectance = Self::fresnel(cos_o, refraction_ratio);
+        if !through {
+            let eval = reflectance * d_g / (4.0 * wo.z());
+            return (Colour::new(eval, eval, eval), reflectance * visible / (4.0 * cos_o));
+        }
+
+        // how much the directions wi bunch up against the facet normals that refract into them
+        let denominator = (cos_i + refraction_ratio * cos_o).powi(2);
+        if denominator <= 0.0 {
+            return black;
+        }
+        let jacobian = cos_i.abs() / denominator;
+        let eval = (1.0 - reflectance) * d_g * cos_o / wo.z() * jacobian;
+        (Colour::new(eval, eval, eval), (1.0 - reflectance) * visible * jacobian)
+    }
+}
+
+// rough glass picks a facet as it is seen from wo, then reflects or refracts off it with the
+// chance given by its fresnel reflectance, as smooth glass does, which leaves only the hidden
+// share of the facets for the sample to carry.
+// neither refracting into glass nor out of it scales the light by the squared ratio of the
+// indices, the two cancel out on the way through
+impl Scatter for Dielectric {
+    fn eval(&self, record: &HitRecord, wi: Vec3, wo: Vec3) -> Colour {
+        self.eval_pdf(record, wi, wo).0
+    }
+
+    fn sample(&self, record: &HitRecord, wo: Vec3, uc: f64, u: (f64, f64)) -> Option<ScatterSample> {
+        let (frame, facets) = match self.rough(record) {
+            Some(rough) => rough,
+            None => return Some(self.sample_smooth(record, wo, uc))
+        };
+        let wo_local = frame.to_local(wo);
+        if wo_local.z() <= 0.0 {
+            return None;
+        }
+        let h = facets.sample_visible(wo_local, u);
+        let cos_o = wo_local.dot(h);
+        if cos_o <= 0.0 {
+            return None;
+        }
 
-        let direction = if cannot_reflect || will_reflect {
-            unit_direction.reflect(record.normal)
+        let refraction_ratio = self.refraction_ratio(record);
+        let reflectance = match self.thin {
+            Some(_) => self.sheet_reflectance(cos_o),
+            None => Self::fresnel(cos_o, refraction_ratio)
+        };
+        let reflected = ((-1.0) * wo_local).reflect(h);
+        let wi = if uc < reflectance {
+            reflected
+        } else if self.thin.is_some() {
+            Vec3::new(reflected.x(), reflected.y(), -reflected.z())
         } else {
-            unit_direction.refract(record.normal, refraction_ratio)
+            ((-1.0) * wo_local).refract(h, refraction_ratio).normalized()
         };
+        if (wi.z() < 0.0) != (uc >= reflectance) || wi.z() == 0.0 {
+            return None;
+        }
+
+        let mut weight = Colour::new(1.0, 1.0, 1.0) * (facets.g(wo_local, wi) / facets.g1(wo_local));
+        if self.thin.is_some() && wi.z() < 0.0 {
+            weight *= self.sheet_transmittance(cos_o);
+        }
+        let wi = frame.local(wi);
+        let (_, pdf) = self.eval_pdf(record, wi, wo);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterSample { wi, weight, pdf, delta: false })
+    }
+
+    fn pdf(&self, record: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+        self.eval_pdf(record, wi, wo).1
+    }
+
+    // a thin sheet has no inside, its back is seen from the air
+    fn transmittance(&self, distance: f64) -> Colour {
+        match self.thin {
+            Some(_) => Colour::new(1.0, 1.0, 1.0),
+            None => self.absorbed(distance)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::random;
+    use crate::sampler;
+    use crate::texture::solid::Solid;
+
+    fn record(normal: Vec3, front_face: bool) -> HitRecord {
+        HitRecord {
+            p: Vec3::new(0.0, 0.0, 0.0),
+            normal,
+            material: Dielectric::new_arc(1.5),
+            t: 1.0,
+            u: 0.0,
+            v: 0.0,
+            front_face
+        }
+    }
+
+    fn grey(value: f64) -> Arc<dyn Texture> {
+        Solid::new_arc(Colour::new(value, value, value))
+    }
 
-        let scattered = Ray::new_(record.p, direction, r_in.time);
+    // every sample that isn't a delta carries exactly eval / pdf, and pdf agrees with the
+    // density sample says it picked with. delta samples are invisible to eval and pdf
+    fn check(dielectric: &Dielectric, front_face: bool) {
+        let mut rng = random::seeded(7);
+        let mut lobes = 0;
+        for _ in 0..20000 {
+            let normal = sampler::sphere((rng.gen(), rng.gen()));
+            let record = record(normal, front_face);
+            let wo = Onb::new(normal).local(sampler::cosine_hemisphere((rng.gen(), rng.gen())));
+            if wo.dot(normal) < 1e-3 {
+                continue;
+            }
+            let sample = match dielectric.sample(&record, wo, rng.gen(), (rng.gen(), rng.gen())) {
+                Some(sample) => sample,
+                None => continue
+            };
+            assert!(sample.pdf > 0.0 && sample.pdf.is_finite());
+            let eval = dielectric.eval(&record, sample.wi, wo);
+            let pdf = dielectric.pdf(&record, sample.wi, wo);
+            if sample.delta {
+                assert!(sample.pdf <= 1.0);
+                assert!(eval.near_zero() && pdf == 0.0);
+                continue;
+            }
+            assert!((pdf - sample.pdf).abs() <= 1e-6 * sample.pdf, "pdf {} sampled with {}", pdf, sample.pdf);
+            for i in 0..3 {
+                let weight = eval[i] / pdf;
+                assert!((weight - sample.weight[i]).abs() <= 1e-6 * weight.max(1.0), "eval / pdf {} weight {}", weight, sample.weight[i]);
+            }
+            lobes += 1;
+        }
+        assert!(lobes > 0 || dielectric.roughness.is_none());
+    }
 
-        Some((Colour::new(1.0, 1.0, 1.0), scattered))
+    #[test]
+    fn smooth_samples_are_deltas() {
+        check(&Dielectric::new(1.5), true);
+        check(&Dielectric::new(1.5), false);
+        check(&Dielectric::new(1.5).with_thin_sheet(0.1).with_absorption(Colour::new(0.5, 0.7, 0.9), 1.0), true);
+    }
+
+    #[test]
+    fn rough_weight_is_eval_over_pdf() {
+        for roughness in [0.1, 0.4, 0.9] {
+            check(&Dielectric::new(1.5).with_roughness(grey(roughness)), true);
+            check(&Dielectric::new(1.5).with_roughness(grey(roughness)), false);
+        }
+    }
+
+    #[test]
+    fn thin_rough_weight_is_eval_over_pdf() {
+        for roughness in [0.1, 0.4, 0.9] {
+            let sheet = Dielectric::new(1.5)
+                .with_roughness(grey(roughness))
+                .with_thin_sheet(0.1)
+                .with_absorption(Colour::new(0.5, 0.7, 0.9), 1.0);
+            check(&sheet, true);
+            check(&sheet, false);
+        }
     }
 }
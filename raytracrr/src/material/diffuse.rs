@@ -24,11 +24,12 @@ impl Diffuse {
 }
 
 impl Scatter for Diffuse {
-    fn scatter(&self, r_in: &crate::ray::Ray, record: &crate::hit::hit_record::HitRecord) -> Option<(crate::vec::Colour, crate::ray::Ray)> {
-        None
-    }
-
+    // only emits, everything arriving is absorbed, so eval, sample and pdf keep their defaults
     fn emitted(&self, u: f64, v: f64, p: Vec3) -> Colour {
         self.emit.value(u, v, p)
     }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
 }
@@ -1,6 +1,7 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
-use crate::{texture::Texture, ray::Ray, hit::hit_record::HitRecord, vec::{Colour, Vec3}};
-use super::Scatter;
+use crate::{texture::Texture, hit::hit_record::HitRecord, sampler, vec::{Colour, Vec3}};
+use super::{Scatter, ScatterSample};
 
 pub struct Isotropic {
     albedo: Arc<dyn Texture>
@@ -16,13 +17,22 @@ impl Isotropic {
     }
 }
 
+// a phase function that scatters uniformly over the sphere of directions
 impl Scatter for Isotropic {
-    // picks a uniform random direction:
-    fn scatter(&self, r_in: &Ray, record: &HitRecord) -> Option<(Colour, Ray)> {
-        let scattered = Ray::new_(
-            record.p, Vec3::random_in_sphere(), r_in.time
-        );
-        let attenuation = self.albedo.value(record.u, record.v, record.p);
-        Some((attenuation, scattered))
+    fn eval(&self, record: &HitRecord, _wi: Vec3, _wo: Vec3) -> Colour {
+        self.albedo.value(record.u, record.v, record.p) / (4.0 * PI)
+    }
+
+    fn sample(&self, record: &HitRecord, _wo: Vec3, _uc: f64, u: (f64, f64)) -> Option<ScatterSample> {
+        Some(ScatterSample {
+            wi: sampler::sphere(u),
+            weight: self.albedo.value(record.u, record.v, record.p),
+            pdf: 1.0 / (4.0 * PI),
+            delta: false
+        })
+    }
+
+    fn pdf(&self, _record: &HitRecord, _wi: Vec3, _wo: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
     }
 }
@@ -1,9 +1,11 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
-use crate::material::{Scatter};
+use crate::material::{Scatter, ScatterSample};
+use crate::onb::Onb;
+use crate::sampler;
 use crate::texture::Texture;
 use crate::vec::{Colour, Vec3};
-use crate::ray::{Ray};
 use crate::hit::hit_record::{HitRecord};
 
 pub struct Matte {
@@ -22,20 +24,31 @@ impl Matte {
     }
 }
 
-// it can either scatter always and attenuate by its reflectance R
-// or it can scatter with no attenuation but absorb the fraction 1−R of the rays, 
-// or it could be a mixture of those strategies.
+// lambertian: light is scattered equally in every direction above the surface, the brdf is
+// albedo / pi. times the cosine that makes eval proportional to the cosine, so directions are
+// sampled with density cos / pi and every sample carries just the albedo.
 impl Scatter for Matte {
-    fn scatter(&self, r_in: &Ray, record: &HitRecord) -> Option<(Colour, Ray)> {
-        // If the random unit vector is exactly opposite the normal vector, 
-        // the two will sum to zero, which will result in a zero scatter direction vector.
-        let mut scatter_direction = record.normal + Vec3::random_in_sphere().normalized();
-        if scatter_direction.near_zero() {
-            // Catch degenerate scatter direction
-            scatter_direction = record.normal;
+    fn eval(&self, record: &HitRecord, wi: Vec3, _wo: Vec3) -> Colour {
+        let cosine = record.normal.dot(wi);
+        if cosine <= 0.0 {
+            return Colour::new(0.0, 0.0, 0.0);
         }
-        let attenuation = self.albedo.value(record.u, record.v, record.p);
-        let scattered = Ray::new_(record.p, scatter_direction, r_in.time);
-        return Some((attenuation, scattered));
+        cosine / PI * self.albedo.value(record.u, record.v, record.p)
+    }
+
+    fn sample(&self, record: &HitRecord, _wo: Vec3, _uc: f64, u: (f64, f64)) -> Option<ScatterSample> {
+        let local = sampler::cosine_hemisphere(u);
+
+        let wi = Onb::new(record.normal).local(local);
+        Some(ScatterSample {
+            wi,
+            weight: self.albedo.value(record.u, record.v, record.p),
+            pdf: local.z() / PI,
+            delta: false
+        })
+    }
+
+    fn pdf(&self, record: &HitRecord, wi: Vec3, _wo: Vec3) -> f64 {
+        (record.normal.dot(wi) / PI).max(0.0)
     }
 }
@@ -1,10 +1,11 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
-use crate::material::{Scatter};
+use crate::material::{Scatter, ScatterSample};
 use crate::texture::Texture;
 use crate::vec::{Colour, Vec3};
-use crate::ray::{Ray};
 use crate::hit::hit_record::{HitRecord};
+use crate::sampler;
 
 pub struct Metal {
     albedo: Arc<dyn Texture>,
@@ -22,21 +23,66 @@ impl Metal {
     pub fn new_arc(albedo: Arc<dyn Texture>, fuzz: f64) -> Arc<Metal> {
         Arc::new(Metal::new(albedo, fuzz))
     }
+
+    // the scattered direction points at a uniform random point in a ball of radius fuzz around
+    // the tip of the mirror direction. the density of a direction is the part of the ball's
+    // volume along it: integrating r^2 dr over the chord [t1, t2] the direction cuts through
+    // the ball, over the volume of the ball.
+    fn fuzz_pdf(&self, mirror: Vec3, wi: Vec3) -> f64 {
+        // |t wi - mirror| = fuzz
+        let b = wi.dot(mirror);
+        let discriminant = b * b - (1.0 - self.fuzz * self.fuzz);
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let t1 = (b - discriminant.sqrt()).max(0.0);
+        let t2 = b + discriminant.sqrt();
+        if t2 <= 0.0 {
+            return 0.0;
+        }
+        let volume = 4.0 / 3.0 * PI * self.fuzz.powi(3);
+        (t2.powi(3) - t1.powi(3)) / 3.0 / volume
+    }
 }
 
+// a mirror, blurred by fuzz. light scattered below the surface is absorbed.
 impl Scatter for Metal {
-    fn scatter(&self, r_in: &Ray, record: &HitRecord) -> Option<(Colour, Ray)> {
-        let reflected = r_in.direction().reflect(record.normal).normalized();
+    fn eval(&self, record: &HitRecord, wi: Vec3, wo: Vec3) -> Colour {
+        self.pdf(record, wi, wo) * self.albedo.value(record.u, record.v, record.p)
+    }
+
+    fn sample(&self, record: &HitRecord, wo: Vec3, uc: f64, u: (f64, f64)) -> Option<ScatterSample> {
+        let mirror = ((-1.0) * wo).reflect(record.normal).normalized();
         let attenuation = self.albedo.value(record.u, record.v, record.p);
-        let scattered = Ray::new_(
-            record.p, 
-            reflected + self.fuzz * Vec3::random_in_sphere(),
-            r_in.time
-        );
-        if scattered.direction().dot(record.normal) > 0.0 {
-            Some((attenuation, scattered))
-        } else {
-            None
+
+        if self.fuzz <= 0.0 {
+            return Some(ScatterSample {
+                wi: mirror,
+                weight: attenuation,
+                pdf: 1.0,
+                delta: true
+            });
+        }
+
+        // a point in the ball: the cube root makes every radius as likely as the shell's volume
+        let in_ball = uc.cbrt() * sampler::sphere(u);
+        let wi = (mirror + self.fuzz * in_ball).normalized();
+        if wi.dot(record.normal) <= 0.0 {
+            return None;
+        }
+        Some(ScatterSample {
+            wi,
+            weight: attenuation,
+            pdf: self.fuzz_pdf(mirror, wi),
+            delta: false
+        })
+    }
+
+    fn pdf(&self, record: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+        if self.fuzz <= 0.0 || wi.dot(record.normal) <= 0.0 {
+            return 0.0;
         }
+        let mirror = ((-1.0) * wo).reflect(record.normal).normalized();
+        self.fuzz_pdf(mirror, wi)
     }
 }
@@ -0,0 +1,86 @@
+use std::f64::consts::PI;
+
+use crate::sampler;
+use crate::vec::Vec3;
+
+// a rough surface as a great many tiny mirrors (microfacets) whose normals h spread around the
+// surface normal, with the ggx (trowbridge-reitz) distribution: a bright core and long tails,
+// like real metals and glass. alpha is how far they spread along the two directions of the
+// surface, the same for both unless the surface has a grain.
+// everything here is in the surface's own frame, with the normal along +z and the first alpha
+// along +x (onb::to_local)
+pub struct Microfacet {
+    alpha_x: f64,
+    alpha_y: f64
+}
+
+// below this the facets are as good as all lined up, and the surface is a plain mirror
+const SMOOTH: f64 = 1e-3;
+
+impl Microfacet {
+    // roughness goes from 0 (a mirror) to 1, squared so it looks about as rough as it says
+    pub fn new(roughness_x: f64, roughness_y: f64) -> Microfacet {
+        let alpha = |roughness: f64| roughness.clamp(0.0, 1.0).powi(2).max(1e-4);
+        Microfacet {
+            alpha_x: alpha(roughness_x),
+            alpha_y: alpha(roughness_y)
+        }
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < SMOOTH
+    }
+
+    // the density of facet normals h, per unit of area of the surface
+    pub fn d(&self, h: Vec3) -> f64 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let (x, y) = (h.x() / self.alpha_x, h.y() / self.alpha_y);
+        let t = x * x + y * y + h.z() * h.z();
+        1.0 / (PI * self.alpha_x * self.alpha_y * t * t)
+    }
+
+    // smith's lambda: how much of the surface seen from w is hidden behind other facets
+    fn lambda(&self, w: Vec3) -> f64 {
+        let (x, y) = (self.alpha_x * w.x(), self.alpha_y * w.y());
+        let tan2 = (x * x + y * y) / (w.z() * w.z());
+        ((1.0 + tan2).sqrt() - 1.0) / 2.0
+    }
+
+    // the share of the facets seen from w that aren't hidden
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // the share seen from both wo and wi
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // a facet normal, picked as often as it is seen from wo (heitz, "sampling the ggx
+    // distribution of visible normals"): stretched to make the facets a half sphere, a point
+    // on the disk it shows towards wo, lifted up onto it and stretched back
+    pub fn sample_visible(&self, wo: Vec3, u: (f64, f64)) -> Vec3 {
+        let v = Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).normalized();
+        let t1 = if v.z() < 0.9999 {
+            Vec3::new(-v.y(), v.x(), 0.0).normalized()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = v.cross(t1);
+
+        // the half of the disk behind the half sphere shrinks as wo leans over
+        let (p1, p2) = sampler::disk(u);
+        let s = (1.0 + v.z()) / 2.0;
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * p2;
+        let n = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * v;
+
+        Vec3::new(self.alpha_x * n.x(), self.alpha_y * n.y(), n.z().max(1e-6)).normalized()
+    }
+
+    // the density of sample_visible picking h
+    pub fn pdf_visible(&self, wo: Vec3, h: Vec3) -> f64 {
+        self.g1(wo) * wo.dot(h).max(0.0) * self.d(h) / wo.z()
+    }
+}
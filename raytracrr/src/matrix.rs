@@ -0,0 +1,239 @@
+use std::ops::Mul;
+
+use crate::vec::{Point3, Vec3};
+
+// an affine transform as a 4x4 matrix acting on column vectors: m[row][column], with the
+// translation in the last column and the last row always 0 0 0 1.
+// rotations follow the right hand rule: a positive angle about an axis turns counterclockwise
+// when the axis points at the viewer.
+//
+// transforms are built up in the order they happen to the object, each method applying one
+// more after the ones before:
+//
+//     Matrix4::scaling(Vec3::new(2.0, 1.0, 1.0)).rotate_y(30.0).translate(offset)
+//
+// first stretches along x, then turns about y and finally moves by offset.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4]
+}
+
+impl Matrix4 {
+    pub const IDENTITY: Matrix4 = Matrix4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ]
+    };
+
+    // the 3x3 linear part and the translation
+    pub fn new(linear: [[f64; 3]; 3], translation: Vec3) -> Matrix4 {
+        let mut m = Matrix4::IDENTITY.m;
+        for (row, linear) in m.iter_mut().zip(linear).take(3) {
+            row[..3].copy_from_slice(&linear);
+        }
+        for (i, row) in m.iter_mut().enumerate().take(3) {
+            row[3] = translation[i];
+        }
+        Matrix4 { m }
+    }
+
+    pub fn translation(offset: Vec3) -> Matrix4 {
+        Matrix4::new([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]], offset)
+    }
+
+    // non-uniform scaling along the axes, negative factors mirror
+    pub fn scaling(factors: Vec3) -> Matrix4 {
+        let Vec3 { e: [x, y, z] } = factors;
+        Matrix4::new([[x, 0.0, 0.0], [0.0, y, 0.0], [0.0, 0.0, z]], Vec3::default())
+    }
+
+    // about an axis through the origin (rodrigues' rotation formula)
+    pub fn rotation(axis: Vec3, degrees: f64) -> Matrix4 {
+        let Vec3 { e: [x, y, z] } = axis.normalized();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        Matrix4::new(
+            [
+                [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y],
+                [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x],
+                [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos]
+            ],
+            Vec3::default()
+        )
+    }
+
+    pub fn rotation_x(degrees: f64) -> Matrix4 {
+        Matrix4::rotation(Vec3::new(1.0, 0.0, 0.0), degrees)
+    }
+
+    pub fn rotation_y(degrees: f64) -> Matrix4 {
+        Matrix4::rotation(Vec3::new(0.0, 1.0, 0.0), degrees)
+    }
+
+    pub fn rotation_z(degrees: f64) -> Matrix4 {
+        Matrix4::rotation(Vec3::new(0.0, 0.0, 1.0), degrees)
+    }
+
+    // xy is how much x moves per unit of y, and so on
+    pub fn shearing(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Matrix4 {
+        Matrix4::new([[1.0, xy, xz], [yx, 1.0, yz], [zx, zy, 1.0]], Vec3::default())
+    }
+
+    // self, followed by next
+    pub fn then(self, next: Matrix4) -> Matrix4 {
+        next * self
+    }
+
+    pub fn translate(self, offset: Vec3) -> Matrix4 {
+        self.then(Matrix4::translation(offset))
+    }
+
+    pub fn scale(self, factors: Vec3) -> Matrix4 {
+        self.then(Matrix4::scaling(factors))
+    }
+
+    pub fn rotate(self, axis: Vec3, degrees: f64) -> Matrix4 {
+        self.then(Matrix4::rotation(axis, degrees))
+    }
+
+    pub fn rotate_x(self, degrees: f64) -> Matrix4 {
+        self.then(Matrix4::rotation_x(degrees))
+    }
+
+    pub fn rotate_y(self, degrees: f64) -> Matrix4 {
+        self.then(Matrix4::rotation_y(degrees))
+    }
+
+    pub fn rotate_z(self, degrees: f64) -> Matrix4 {
+        self.then(Matrix4::rotation_z(degrees))
+    }
+
+    pub fn shear(self, xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Matrix4 {
+        self.then(Matrix4::shearing(xy, xz, yx, yz, zx, zy))
+    }
+
+    // of the linear part: how much volumes grow, negative if the transform mirrors
+    pub fn determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    // None if the transform squashes space flat and can't be undone
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let m = &self.m;
+        // the inverse of the linear part is its adjugate over the determinant
+        let mut linear = [[0.0; 3]; 3];
+        for (i, row) in linear.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+                let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+                *value = (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / det;
+            }
+        }
+        // and the translation is undone after it
+        let inverse = Matrix4::new(linear, Vec3::default());
+        let translation = inverse.vector(Vec3::new(m[0][3], m[1][3], m[2][3]));
+        Some(Matrix4::new(linear, -1.0 * translation))
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Matrix4 { m }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.vector(p) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    // directions and offsets, which don't move with the translation
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+            m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+            m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2]
+        )
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Matrix4 {
+        Matrix4::IDENTITY
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // everything a scene might stack up: mirrored, sheared, squashed and moved
+    fn matrices() -> Vec<Matrix4> {
+        vec![
+            Matrix4::IDENTITY,
+            Matrix4::translation(Vec3::new(3.0, -2.0, 7.5)),
+            Matrix4::scaling(Vec3::new(2.0, 0.5, -3.0)),
+            Matrix4::rotation(Vec3::new(1.0, 2.0, -0.5), 37.0),
+            Matrix4::scaling(Vec3::new(2.0, 1.0, 1.0)).rotate_y(30.0).translate(Vec3::new(1.0, 2.0, 3.0)),
+            Matrix4::shearing(0.5, -0.2, 0.0, 1.5, 0.3, 0.0).rotate_x(-80.0).scale(Vec3::new(0.01, 40.0, 1.0)).translate(Vec3::new(-100.0, 0.0, 5.0))
+        ]
+    }
+
+    fn assert_close(a: Matrix4, b: Matrix4) {
+        for (row_a, row_b) in a.m.iter().zip(b.m) {
+            for (x, y) in row_a.iter().zip(row_b) {
+                assert!((x - y).abs() < 1e-9, "{:?} against {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        for m in matrices() {
+            let inverse = m.inverse().unwrap();
+            assert_close(m * inverse, Matrix4::IDENTITY);
+            assert_close(inverse * m, Matrix4::IDENTITY);
+            assert_close(inverse.inverse().unwrap(), m);
+        }
+    }
+
+    #[test]
+    fn flat_matrices_have_no_inverse() {
+        assert_eq!(Matrix4::scaling(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+        assert_eq!(Matrix4::shearing(1.0, 0.0, 1.0, 0.0, 0.0, 0.0).inverse(), None);
+    }
+
+    #[test]
+    fn transforms_happen_in_order() {
+        let m = Matrix4::scaling(Vec3::new(2.0, 1.0, 1.0)).rotate_z(90.0).translate(Vec3::new(0.0, 0.0, 5.0));
+        let p = m.point(Point3::new(1.0, 0.0, 0.0));
+        assert!((p - Point3::new(0.0, 2.0, 5.0)).length() < 1e-12, "{:?}", p);
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::hit::mesh::{Face, Mesh};
+use crate::material::Scatter;
+use crate::material::{dielectric::Dielectric, diffuse::Diffuse, matte::Matte, metal::Metal};
+use crate::texture::Texture;
+use crate::texture::{image::Image, solid::Solid};
+use crate::vec::{Colour, Point3, Vec3};
+use crate::Error;
+
+// wavefront obj models, with the materials from their mtl libraries.
+//
+// supported obj statements: v, vt, vn, f (polygons are split into a triangle fan, indices
+// may be negative to count back from the last vertex), usemtl and mtllib.
+// groups, smoothing groups, lines, points and anything else are skipped.
+//
+// mtl materials are mapped onto ours, the first rule that applies wins:
+//   Ke (emission) not black       -> Diffuse emitting Ke
+//   d < 1, Tr > 0 or illum 4/6/7/9 -> Dielectric with index Ni
+//   illum 3/5/8 or only Ks set     -> Metal with albedo Ks, fuzz from the Ns exponent
+//   anything else                 -> Matte with albedo Kd, or the image in map_Kd
+// faces before any usemtl get the default material passed to load.
+
+pub fn load(path: impl AsRef<Path>, default_material: Arc<dyn Scatter>) -> Result<Mesh, Error> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("can't read obj file {}: {}", path.display(), e))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse(&text, base_dir, default_material).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+// base_dir is where mtl libraries and their textures are looked up
+pub fn parse(text: &str, base_dir: &Path, default_material: Arc<dyn Scatter>) -> Result<Mesh, Error> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut faces = Vec::new();
+
+    let mut materials = vec![default_material];
+    let mut library: HashMap<String, Arc<dyn Scatter>> = HashMap::new();
+    let mut material_index: HashMap<String, u32> = HashMap::new();
+    let mut current_material = 0;
+
+    for (number, statement) in text.lines().enumerate() {
+        let statement = statement.split('#').next().unwrap_or_default();
+        let mut words = statement.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let args: Vec<&str> = words.collect();
+        let line = number + 1;
+
+        match keyword {
+            "v" => positions.push(vec3(&args, line)?),
+            "vn" => normals.push(vec3(&args, line)?),
+            "vt" => {
+                // v is optional for 1d textures
+                let u = float(args.first(), line)?;
+                let v = if args.len() > 1 { float(args.get(1), line)? } else { 0.0 };
+                uvs.push((u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(format!("line {}: a face needs at least 3 vertices", line).into());
+                }
+                let corners = args
+                    .iter()
+                    .map(|arg| corner(arg, positions.len(), uvs.len(), normals.len(), line))
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                for i in 1..corners.len() - 1 {
+                    let [a, b, c] = [corners[0], corners[i], corners[i + 1]];
+                    faces.push(Face {
+                        vertices: [a.0, b.0, c.0],
+                        uvs: match (a.1, b.1, c.1) {
+                            (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+                            _ => None
+                        },
+                        normals: match (a.2, b.2, c.2) {
+                            (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+                            _ => None
+                        },
+                        material: current_material
+                    });
+                }
+            }
+            "mtllib" => {
+                for name in args {
+                    let path = base_dir.join(name);
+                    let text = fs::read_to_string(&path)
+                        .map_err(|e| format!("line {}: can't read material library {}: {}", line, path.display(), e))?;
+                    let parsed = parse_mtl(&text, base_dir)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    library.extend(parsed);
+                }
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                current_material = match material_index.get(&name) {
+                    Some(&index) => index,
+                    None => {
+                        let material = library
+                            .get(&name)
+                            .ok_or_else(|| format!("line {}: unknown material '{}'", line, name))?;
+                        materials.push(Arc::clone(material));
+                        let index = materials.len() as u32 - 1;
+                        material_index.insert(name, index);
+                        index
+                    }
+                };
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Mesh::new(positions, normals, uvs, faces, materials))
+}
+
+// the parts of an mtl material we can use
+struct MtlDesc {
+    diffuse: Colour,
+    specular: Colour,
+    emission: Colour,
+    exponent: f64,
+    ior: f64,
+    dissolve: f64,
+    illum: u32,
+    diffuse_map: Option<Arc<dyn Texture>>
+}
+
+impl Default for MtlDesc {
+    fn default() -> MtlDesc {
+        MtlDesc {
+            diffuse: Colour::new(0.8, 0.8, 0.8),
+            specular: Colour::new(0.0, 0.0, 0.0),
+            emission: Colour::new(0.0, 0.0, 0.0),
+            exponent: 0.0,
+            ior: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+            diffuse_map: None
+        }
+    }
+}
+
+impl MtlDesc {
+    fn material(self) -> Arc<dyn Scatter> {
+        let is_black = |c: Colour| c.x() <= 0.0 && c.y() <= 0.0 && c.z() <= 0.0;
+
+        if !is_black(self.emission) {
+            Diffuse::new_arc(Solid::new_arc(self.emission))
+        } else if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            Dielectric::new_arc(self.ior)
+        } else if matches!(self.illum, 3 | 5 | 8) || (is_black(self.diffuse) && !is_black(self.specular)) {
+            // blinn-phong exponent to roughness, a mirror has a huge exponent
+            let fuzz = (2.0 / (self.exponent + 2.0)).sqrt().min(1.0);
+            Metal::new_arc(Solid::new_arc(self.specular), fuzz)
+        } else {
+            match self.diffuse_map {
+                Some(texture) => Matte::new_arc(texture),
+                None => Matte::new_arc(Solid::new_arc(self.diffuse))
+            }
+        }
+    }
+}
+
+fn parse_mtl(text: &str, base_dir: &Path) -> Result<HashMap<String, Arc<dyn Scatter>>, Error> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlDesc)> = None;
+
+    for (number, statement) in text.lines().enumerate() {
+        let statement = statement.split('#').next().unwrap_or_default();
+        let mut words = statement.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let args: Vec<&str> = words.collect();
+        let line = number + 1;
+
+        if keyword == "newmtl" {
+            if let Some((name, desc)) = current.take() {
+                materials.insert(name, desc.material());
+            }
+            current = Some((args.join(" "), MtlDesc::default()));
+            continue;
+        }
+
+        let desc = match current.as_mut() {
+            Some((_, desc)) => desc,
+            None => return Err(format!("line {}: '{}' before the first newmtl", line, keyword).into())
+        };
+        match keyword {
+            "Kd" => desc.diffuse = vec3(&args, line)?,
+            "Ks" => desc.specular = vec3(&args, line)?,
+            "Ke" => desc.emission = vec3(&args, line)?,
+            "Ns" => desc.exponent = float(args.first(), line)?,
+            "Ni" => desc.ior = float(args.first(), line)?,
+            "d" => desc.dissolve = float(args.first(), line)?,
+            "Tr" => desc.dissolve = 1.0 - float(args.first(), line)?,
+            "illum" => {
+                desc.illum = args
+                    .first()
+                    .and_then(|arg| arg.parse().ok())
+                    .ok_or_else(|| format!("line {}: expected an illumination model number", line))?;
+            }
+            "map_Kd" => {
+                // options like -s come before the file name, which is always last
+                let name = args
+                    .last()
+                    .ok_or_else(|| format!("line {}: map_Kd expects a file name", line))?;
+                let image = Image::new(base_dir.join(name)).map_err(|e| format!("line {}: {}", line, e))?;
+                desc.diffuse_map = Some(Arc::new(image));
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((name, desc)) = current {
+        materials.insert(name, desc.material());
+    }
+    Ok(materials)
+}
+
+// (vertex, uv, normal) indices of one face corner: "v", "v/vt", "v//vn" or "v/vt/vn"
+fn corner(arg: &str, positions: usize, uvs: usize, normals: usize, line: usize) -> Result<(u32, Option<u32>, Option<u32>), Error> {
+    let mut parts = arg.split('/');
+    let vertex = index(parts.next(), positions, "vertex", line)?
+        .ok_or_else(|| format!("line {}: face corner '{}' has no vertex", line, arg))?;
+    let uv = index(parts.next(), uvs, "texture coordinate", line)?;
+    let normal = index(parts.next(), normals, "normal", line)?;
+    Ok((vertex, uv, normal))
+}
+
+// obj indices start at 1, negative ones count back from the end
+fn index(part: Option<&str>, count: usize, what: &str, line: usize) -> Result<Option<u32>, Error> {
+    let part = match part {
+        Some(part) if !part.is_empty() => part,
+        _ => return Ok(None)
+    };
+    let i: i64 = part
+        .parse()
+        .map_err(|_| format!("line {}: invalid {} index '{}'", line, what, part))?;
+    let resolved = if i < 0 { count as i64 + i } else { i - 1 };
+    if i == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("line {}: {} index {} out of range (there are {})", line, what, i, count).into());
+    }
+    Ok(Some(resolved as u32))
+}
+
+fn float(arg: Option<&&str>, line: usize) -> Result<f64, Error> {
+    let arg = arg.ok_or_else(|| format!("line {}: missing number", line))?;
+    arg.parse().map_err(|_| format!("line {}: invalid number '{}'", line, arg).into())
+}
+
+fn vec3(args: &[&str], line: usize) -> Result<Point3, Error> {
+    if args.len() < 3 {
+        return Err(format!("line {}: expected 3 numbers", line).into());
+    }
+    Ok(Vec3::new(
+        float(args.first(), line)?,
+        float(args.get(1), line)?,
+        float(args.get(2), line)?
+    ))
+}
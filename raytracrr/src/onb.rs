@@ -0,0 +1,46 @@
+use crate::vec::Vec3;
+
+// orthonormal basis around w, for sampling directions in a frame where w is "up"
+// (a cone towards a light, a lobe around a normal) and turning them back into world space
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3
+}
+
+impl Onb {
+    pub fn new(w: Vec3) -> Onb {
+        let w = w.normalized();
+        // any vector not parallel to w will do to start the cross products
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(a).normalized();
+        let u = w.cross(v);
+        Onb { u, v, w }
+    }
+
+    // the basis around w with u as close to tangent as it can be, for surfaces that look
+    // different one way along them than the other (brushed metal). tangent can't be along w
+    pub fn new_tangent(w: Vec3, tangent: Vec3) -> Onb {
+        let w = w.normalized();
+        let u = tangent - tangent.dot(w) * w;
+        if u.length() <= 1e-6 * tangent.length() {
+            return Onb::new(w);
+        }
+        let u = u.normalized();
+        Onb { u, v: w.cross(u), w }
+    }
+
+    // a vector given in this basis, in world space
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    // a vector given in world space, in this basis
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
@@ -0,0 +1,129 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use image::codecs::hdr::HdrEncoder;
+use image::{ImageBuffer, Rgb};
+
+use crate::render::Framebuffer;
+use crate::vec::Colour;
+use crate::Error;
+
+// the framebuffer holds linear radiance, which can be far above 1.0 (the cornell light is 15).
+// hdr formats (.hdr, .pfm, .exr) store it as is, as 32-bit floats.
+// ldr formats (.ppm, .png) can only hold [0, 1], so values are clipped and gamma 2 encoded,
+// the same way format_color always did.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    Ppm,
+    Png8,
+    Png16,
+    Hdr, // radiance rgbe
+    Pfm,
+    Exr
+}
+
+impl ImageFormat {
+    // picks the format from the file extension, bit_depth only matters for png
+    pub fn from_path(path: &Path, bit_depth: u8) -> Result<ImageFormat, Error> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match (extension.as_str(), bit_depth) {
+            ("png", 8) => Ok(ImageFormat::Png8),
+            ("png", 16) => Ok(ImageFormat::Png16),
+            ("png", _) => Err(format!("png images are 8 or 16 bits per channel, not {}", bit_depth).into()),
+            ("ppm", _) => Ok(ImageFormat::Ppm),
+            ("hdr", _) => Ok(ImageFormat::Hdr),
+            ("pfm", _) => Ok(ImageFormat::Pfm),
+            ("exr", _) => Ok(ImageFormat::Exr),
+            _ => Err(format!(
+                "can't tell the image format of {} (use .png, .ppm, .hdr, .pfm or .exr)", path.display()
+            ).into())
+        }
+    }
+
+    pub fn is_hdr(self) -> bool {
+        matches!(self, ImageFormat::Hdr | ImageFormat::Pfm | ImageFormat::Exr)
+    }
+}
+
+pub fn save(image: &Framebuffer, path: &Path, format: ImageFormat) -> Result<(), Error> {
+    let result = match format {
+        ImageFormat::Ppm => write_ppm(image, path),
+        ImageFormat::Png8 => write_png8(image, path),
+        ImageFormat::Png16 => write_png16(image, path),
+        ImageFormat::Hdr => write_hdr(image, path),
+        ImageFormat::Pfm => write_pfm(image, path),
+        ImageFormat::Exr => write_exr(image, path)
+    };
+    result.map_err(|e| format!("can't write image {}: {}", path.display(), e).into())
+}
+
+// clipped to [0, 1] and gamma 2 encoded
+fn encode_ldr(colour: Colour) -> [f64; 3] {
+    colour.e.map(|c| c.max(0.0).sqrt().min(1.0))
+}
+
+fn write_ppm(image: &Framebuffer, path: &Path) -> Result<(), Error> {
+    let mut out = BufWriter::new(File::create(path)?);
+    image.write_ppm(&mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn write_png8(image: &Framebuffer, path: &Path) -> Result<(), Error> {
+    let buffer = ImageBuffer::from_fn(image.width() as u32, image.height() as u32, |x, y| {
+        // 256 * c clipped at 255 rounds like format_color
+        Rgb(encode_ldr(image.get(x as usize, y as usize)).map(|c| (256.0 * c).min(255.0) as u8))
+    });
+    buffer.save_with_format(path, image::ImageFormat::Png)?;
+    Ok(())
+}
+
+fn write_png16(image: &Framebuffer, path: &Path) -> Result<(), Error> {
+    let buffer: ImageBuffer<Rgb<u16>, Vec<u16>> = ImageBuffer::from_fn(image.width() as u32, image.height() as u32, |x, y| {
+        Rgb(encode_ldr(image.get(x as usize, y as usize)).map(|c| (c * 65535.0).round() as u16))
+    });
+    buffer.save_with_format(path, image::ImageFormat::Png)?;
+    Ok(())
+}
+
+fn write_hdr(image: &Framebuffer, path: &Path) -> Result<(), Error> {
+    let pixels: Vec<Rgb<f32>> = image
+        .pixels()
+        .iter()
+        .map(|c| Rgb(c.e.map(|c| c.max(0.0) as f32)))
+        .collect();
+    let mut out = BufWriter::new(File::create(path)?);
+    HdrEncoder::new(&mut out).encode(&pixels, image.width(), image.height())?;
+    out.flush()?;
+    Ok(())
+}
+
+// portable float map: a small text header and then little endian f32 rows, bottom row first
+fn write_pfm(image: &Framebuffer, path: &Path) -> Result<(), Error> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+    for y in (0..image.height()).rev() {
+        for x in 0..image.width() {
+            for c in image.get(x, y).e {
+                out.write_all(&(c as f32).to_le_bytes())?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn write_exr(image: &Framebuffer, path: &Path) -> Result<(), Error> {
+    exr::prelude::write_rgb_file(path, image.width(), image.height(), |x, y| {
+        let Colour { e: [r, g, b] } = image.get(x, y);
+        (r as f32, g as f32, b as f32)
+    })?;
+    Ok(())
+}
@@ -1,10 +1,10 @@
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
+use crate::random::{self, Random};
 use crate::vec::{Vec3};
 
 const POINT_COUNT: usize = 256;
 pub struct Perlin {
-    ran_dbl: [f64; POINT_COUNT], // not used
     ran_vec: [Vec3; POINT_COUNT],
     perm_x: [usize; POINT_COUNT],
     perm_y: [usize; POINT_COUNT],
@@ -12,13 +12,13 @@ pub struct Perlin {
 }
 
 impl Perlin {
-    pub fn new() -> Perlin {
+    // the same rng state always gives the same noise
+    pub fn new(rng: &mut Random) -> Perlin {
         Perlin { 
-            ran_dbl: random_double(),
-            ran_vec: rand_fill(), 
-            perm_x: generate_perlin_permute(), 
-            perm_y: generate_perlin_permute(), 
-            perm_z: generate_perlin_permute() 
+            ran_vec: rand_fill(rng), 
+            perm_x: generate_perlin_permute(rng), 
+            perm_y: generate_perlin_permute(rng), 
+            perm_z: generate_perlin_permute(rng) 
         }
     }
 
@@ -84,33 +84,31 @@ impl Perlin {
     }
 }
 
-fn generate_perlin_permute() -> [usize; POINT_COUNT] {
+impl Default for Perlin {
+    fn default() -> Perlin {
+        Perlin::new(&mut random::seeded(0))
+    }
+}
+
+fn generate_perlin_permute(rng: &mut Random) -> [usize; POINT_COUNT] {
     let mut p = [0; POINT_COUNT]; 
     for (i, p) in p.iter_mut().enumerate() {
         *p = i;
     }
     for i in (0..p.len()).rev() {
-        let target = thread_rng().gen_range(0..=i);
+        let target = rng.gen_range(0..=i);
         p.swap(i, target)
     }
     p
 }
 
-fn random_double() -> [f64; POINT_COUNT] {
-    let mut rand_dbl = [0.0; POINT_COUNT];
-    for i in (0..rand_dbl.len()) {
-        rand_dbl[i] = thread_rng().gen_range(0.0..=1.0);
-    }
-    rand_dbl
-}
-
 /**
  * generates a random array of vectors
  */
-fn rand_fill() -> [Vec3; POINT_COUNT] {
+fn rand_fill(rng: &mut Random) -> [Vec3; POINT_COUNT] {
     let mut rand_vec = [Default::default(); POINT_COUNT];
     for i in &mut rand_vec {
-        *i = Vec3::random(-1.0..1.0).normalized();
+        *i = Vec3::random(-1.0..1.0, rng).normalized();
     }
     rand_vec
 }
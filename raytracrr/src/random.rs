@@ -0,0 +1,41 @@
+use rand::SeedableRng;
+use rand_pcg::Pcg64Mcg;
+
+// every random number of a render comes from one of these, never from thread_rng, so the same
+// seeds always give the same image.
+// a pcg is small and quick to seed, which matters because the renderer starts a fresh one for
+// every sample of every pixel: stream(seed, &[x, y, sample]). the random numbers a sample uses
+// then only depend on which sample it is, not on the thread or tile that happened to render it.
+pub type Random = Pcg64Mcg;
+
+pub fn seeded(seed: u64) -> Random {
+    Random::seed_from_u64(seed)
+}
+
+// an independent stream for every different list of keys under the same seed
+pub fn stream(seed: u64, keys: &[u64]) -> Random {
+    seeded(hash(seed, keys))
+}
+
+pub fn hash(seed: u64, keys: &[u64]) -> u64 {
+    keys.iter().fold(mix(seed), |h, &key| mix(h ^ mix(key)))
+}
+
+// a number in [0, 1) that only depends on values, for the few places that need randomness
+// without an rng at hand
+pub fn hash_unit(values: &[f64]) -> f64 {
+    unit(values.iter().fold(mix(0), |h, v| mix(h ^ mix(v.to_bits()))))
+}
+
+// a hash as a number in [0, 1), from its top 53 bits
+pub fn unit(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// splitmix64's finaliser: every input bit flips about half of the output bits
+fn mix(x: u64) -> u64 {
+    let x = x.wrapping_add(0x9e3779b97f4a7c15);
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
@@ -0,0 +1,934 @@
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+
+use crate::camera::Camera;
+use crate::environment::{self, Environment};
+use crate::hit::Hit;
+use crate::hit::bvh::{BuildStats, Split, BVH};
+use crate::hit::hit_record::HitRecord;
+use crate::hit::world::World;
+use crate::sampler::{Sampler, SamplerKind};
+use crate::ray::Ray;
+use crate::vec::{Colour, Vec3};
+use crate::Error;
+
+// the image is cut into square tiles which are handed out to a pool of worker threads.
+// rayon's work stealing keeps every thread busy even though some tiles (glass, smoke)
+// cost far more than others (black background).
+// every tile renders into its own buffer and is copied into the framebuffer by position
+// afterwards, so the assembled image doesn't depend on the thread count or on which thread
+// happened to render which tile.
+// the random numbers don't either: every sample of every pixel gets its numbers from the
+// sampler, which derives them from the seed and the sample's pixel and number (see
+// sampler.rs), so the same settings give the same image, whatever the thread count and tile
+// size.
+
+#[derive(Clone)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: u64, // on average when sampling adaptively
+    pub adaptive: Option<Adaptive>, // None: every pixel gets samples_per_pixel
+    pub max_depth: u64,
+    pub threads: usize, // 0: one per logical cpu
+    pub tile_size: usize,
+    pub seed: u64, // every random number of the render follows from it
+    pub sampler: SamplerKind,
+    pub bvh: Option<Split>, // how to build the bvh over the world, None to go without
+    pub light_sampling: bool // sample emissive shapes directly, see ray_colour (punctual lights always are)
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            width: 400,
+            height: 400,
+            samples_per_pixel: 100,
+            adaptive: None,
+            max_depth: 50,
+            threads: 0,
+            tile_size: 32,
+            seed: 0,
+            sampler: SamplerKind::Sobol,
+            bvh: Some(Split::Sah),
+            light_sampling: true
+        }
+    }
+}
+
+// adaptive sampling: a pixel stops taking samples once its estimated error is below
+// threshold, so flat and empty parts of the image finish early. the image still takes
+// samples_per_pixel samples per pixel on average: once every pixel has had samples_per_pixel
+// or stopped, the samples the stopped ones didn't take are handed out again, min_samples at a
+// time to the pixels with the highest error first, none taking more than max_samples.
+// the error is the standard error of the mean of the samples' luminance, relative to the
+// mean. it's checked every min_samples samples, the first time after min_samples
+#[derive(Clone, Copy, Debug)]
+pub struct Adaptive {
+    pub min_samples: u64,
+    pub threshold: f64,
+    pub max_samples: u64
+}
+
+// running mean and variance of the luminance of a pixel's samples (welford's algorithm)
+#[derive(Clone, Default)]
+struct PixelError {
+    count: u64,
+    mean: f64,
+    m2: f64
+}
+
+impl PixelError {
+    fn add(&mut self, colour: Colour) {
+        let luminance = colour.luminance();
+        self.count += 1;
+        let delta = luminance - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    fn relative_error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let variance = self.m2 / (self.count - 1) as f64;
+        let standard_error = (variance / self.count as f64).sqrt();
+        // a tiny floor, so black pixels count as converged rather than dividing by zero
+        standard_error / self.mean.max(1e-4)
+    }
+}
+
+// final averaged (linear) colour of every pixel, row-major with the top row first,
+// and how many samples every pixel took
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Colour>,
+    samples: Vec<u64>
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Colour::default(); width * height],
+            samples: vec![0; width * height]
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Colour] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Colour {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, colour: Colour) {
+        self.pixels[y * self.width + x] = colour;
+    }
+
+    pub fn samples(&self, x: usize, y: usize) -> u64 {
+        self.samples[y * self.width + x]
+    }
+
+    pub fn set_samples(&mut self, x: usize, y: usize, samples: u64) {
+        self.samples[y * self.width + x] = samples;
+    }
+
+    pub fn total_samples(&self) -> u64 {
+        self.samples.iter().sum()
+    }
+
+    // the sample count of every pixel as a colour, from dark blue for the fewest samples in
+    // the image through green and yellow to red for the most
+    pub fn sample_heatmap(&self) -> Framebuffer {
+        const RAMP: [[f64; 3]; 5] = [
+            [0.0, 0.0, 0.3],
+            [0.0, 0.3, 1.0],
+            [0.0, 0.9, 0.3],
+            [1.0, 0.9, 0.0],
+            [1.0, 0.0, 0.0]
+        ];
+        let least = self.samples.iter().copied().min().unwrap_or(0);
+        let most = self.samples.iter().copied().max().unwrap_or(0);
+
+        let mut heatmap = Framebuffer::new(self.width, self.height);
+        heatmap.samples.clone_from(&self.samples);
+        for (pixel, &samples) in heatmap.pixels.iter_mut().zip(&self.samples) {
+            let t = if most > least {
+                (samples - least) as f64 / (most - least) as f64
+            } else {
+                0.0
+            };
+            let position = t * (RAMP.len() - 1) as f64;
+            let i = (position as usize).min(RAMP.len() - 2);
+            let f = position - i as f64;
+            let [r, g, b] = [0, 1, 2].map(|c| (1.0 - f) * RAMP[i][c] + f * RAMP[i + 1][c]);
+            // squared, so the ramp shows as written once the image is gamma encoded
+            *pixel = Colour::new(r * r, g * g, b * b);
+        }
+        heatmap
+    }
+
+    // plain text P3 ppm, gamma 2 encoded
+    pub fn write_ppm(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "P3")?;
+        writeln!(out, "{} {}", self.width, self.height)?;
+        writeln!(out, "255")?;
+        for pixel in &self.pixels {
+            writeln!(out, "{}", pixel.format_color(1))?;
+        }
+        Ok(())
+    }
+}
+
+// pixel rectangle [x0, x1) x [y0, y1) of the image, y growing downwards
+struct Tile {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize
+}
+
+fn split_tiles(width: usize, height: usize, tile_size: usize) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y0 in (0..height).step_by(tile_size) {
+        for x0 in (0..width).step_by(tile_size) {
+            tiles.push(Tile {
+                x0,
+                y0,
+                x1: (x0 + tile_size).min(width),
+                y1: (y0 + tile_size).min(height)
+            });
+        }
+    }
+    tiles
+}
+
+// light arrives at a surface two ways: by bouncing there off other surfaces, and straight
+// from a light. the bounces are followed by sampling a direction from the material, which
+// also finds light when a bounce happens to hit a light, but that takes many samples when the
+// light is small. so at every bounce off a material without a delta lobe, a point on a
+// random light is also picked and a shadow ray checks whether it is visible (next event
+// estimation).
+// both ways can find the same light, so their contributions are weighted with the power
+// heuristic (multiple importance sampling): each way gets most of the weight for the
+// directions it is good at finding, light sampling for small lights and material sampling for
+// big lights and shiny surfaces, and the weights of the two always sum to one.
+// mirrors and glass only ever find light by bouncing.
+// an environment that is a light (see Environment::is_light) is one more of the lights, its
+// light found by rays that leave the scene.
+// lights that are a point or a direction (see Hit::illuminate) can only be found by light
+// sampling, so whatever it finds of them counts in full.
+
+// every bounce takes the same six dimensions from sampler, whatever it ends up using, so the
+// nth bounce of every sample uses the same dimensions: one to pick a light, two for the point
+// on it, one to pick a lobe of the material and two for the direction
+pub fn ray_colour(r: &Ray, environment: &dyn Environment, world: &World, lights: &World, depth: u64, sampler: &mut dyn Sampler) -> Colour {
+    trace(r, environment, world, lights, depth, None, sampler)
+}
+
+// scattering_pdf: the density the last bounce picked r with, if that bounce also sampled the
+// lights, None if any light r hits counts in full
+#[allow(clippy::too_many_arguments)]
+fn trace(r: &Ray, environment: &dyn Environment, world: &World, lights: &World, depth: u64, scattering_pdf: Option<f64>, sampler: &mut dyn Sampler) -> Colour {
+    if depth == 0 {
+        // exceeded ray bounce limit, no light gathered
+        return Colour::new(0.0, 0.0, 0.0);
+    }
+
+    let record = match world.hit(r, 0.001..f64::INFINITY) {
+        Some(record) => record,
+        None => {
+            let mut background = environment.radiance(r.direction());
+            if let Some(pdf) = scattering_pdf {
+                background *= power_heuristic(pdf, lights_pdf(lights, r));
+            }
+            return background;
+        }
+    };
+
+    let mut emit = record.material.as_ref().emitted(record.u, record.v, record.p);
+    if let Some(pdf) = scattering_pdf {
+        emit *= power_heuristic(pdf, lights_pdf(lights, r));
+    }
+
+    // r went through the inside of what it hit to get there, everything seen from here comes
+    // back the same way
+    let inside = if record.front_face {
+        Colour::new(1.0, 1.0, 1.0)
+    } else {
+        record.material.transmittance(record.t * r.direction().length())
+    };
+
+    let u_light = sampler.get_1d();
+    let u_light_point = sampler.get_2d();
+    let u_lobe = sampler.get_1d();
+    let u_direction = sampler.get_2d();
+
+    let wo = (-1.0) * r.direction().normalized();
+    let sample = match record.material.sample(&record, wo, u_lobe, u_direction) {
+        Some(sample) => sample,
+        None => return inside * emit
+    };
+    let scattered = Ray::new_(record.p, sample.wi, r.time);
+
+    // a delta lobe can't be hit by a light sample, so whatever its ray finds counts in full
+    if sample.delta || lights.is_empty() {
+        return inside * (emit + sample.weight * trace(&scattered, environment, world, lights, depth - 1, None, sampler));
+    }
+
+    let direct = sample_lights(r, &record, wo, environment, world, lights, u_light, u_light_point);
+    inside * (emit + direct + sample.weight * trace(&scattered, environment, world, lights, depth - 1, Some(sample.pdf), sampler))
+}
+
+// light arriving straight from a random point on a random light, weighted against scattering
+#[allow(clippy::too_many_arguments)]
+fn sample_lights(
+    r_in: &Ray,
+    record: &HitRecord,
+    wo: Vec3,
+    environment: &dyn Environment,
+    world: &World,
+    lights: &World,
+    u_light: f64,
+    u_point: (f64, f64)
+) -> Colour {
+    let light = &lights[((u_light * lights.len() as f64) as usize).min(lights.len() - 1)];
+    if let Some(illumination) = light.illuminate(record.p, r_in.time) {
+        // picked one time in lights.len()
+        let shadow_ray = Ray::new_(record.p, illumination.direction, r_in.time);
+        if world.hit(&shadow_ray, 0.001..illumination.distance - 0.001).is_some() {
+            return Colour::new(0.0, 0.0, 0.0);
+        }
+        let eval = record.material.eval(record, illumination.direction, wo);
+        return lights.len() as f64 * eval * illumination.incoming;
+    }
+
+    let shadow_ray = Ray::new_(record.p, light.random(record.p, r_in.time, u_point), r_in.time);
+    let wi = shadow_ray.direction().normalized();
+
+    let light_pdf = lights_pdf(lights, &shadow_ray);
+    let scattering_pdf = record.material.pdf(record, wi, wo);
+    if light_pdf <= 0.0 || scattering_pdf <= 0.0 {
+        return Colour::new(0.0, 0.0, 0.0);
+    }
+
+    // whatever the shadow ray hits first, usually the light or something blocking it, or the
+    // environment if nothing is in the way
+    let incoming = match world.hit(&shadow_ray, 0.001..f64::INFINITY) {
+        Some(hit) => hit.material.emitted(hit.u, hit.v, hit.p),
+        None => environment.radiance(wi)
+    };
+    let weight = power_heuristic(light_pdf, scattering_pdf);
+    weight / light_pdf * record.material.eval(record, wi, wo) * incoming
+}
+
+// density of picking the direction of r by choosing one of the lights uniformly and sampling it
+fn lights_pdf(lights: &World, r: &Ray) -> f64 {
+    if lights.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = lights.iter().map(|light| light.pdf_value(r.origin(), r.direction(), r.time)).sum();
+    sum / lights.len() as f64
+}
+
+// weight of the sample taken with density pdf, when the other way would have found it with other
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let pdf = pdf * pdf;
+    let other = other * other;
+    if pdf + other == 0.0 {
+        return 0.0;
+    }
+    pdf / (pdf + other)
+}
+
+// takes the pixels of tile in state on to their targets (row-major, like the pixels) or until
+// they converge, returning their new state. samples are added to the sums in the same order however the
+// samples were split into passes, so the image doesn't depend on that either
+#[allow(clippy::too_many_arguments)]
+fn render_tile(
+    tile: &Tile,
+    state: &Accumulator,
+    targets: &[u64],
+    world: &World,
+    lights: &World,
+    environment: &dyn Environment,
+    camera: &Camera,
+    settings: &RenderSettings
+) -> Vec<PixelState> {
+    let mut sampler = settings.sampler.create(settings.seed, settings.samples_per_pixel);
+    let mut pixels = Vec::with_capacity((tile.x1 - tile.x0) * (tile.y1 - tile.y0));
+
+    for y in tile.y0..tile.y1 {
+        // framebuffer rows go top to bottom, the camera's v goes bottom to top
+        let j = settings.height - 1 - y;
+        for i in tile.x0..tile.x1 {
+            let mut pixel = state.pixels[y * state.width + i].clone();
+            let target = targets[y * state.width + i];
+
+            while !pixel.converged && pixel.samples < target {
+                sampler.start_pixel_sample(i as u64, y as u64, pixel.samples);
+                let (random_u, random_v) = sampler.get_2d();
+
+                let u = (i as f64 + random_u) / settings.width as f64;
+                let v = (j as f64 + random_v) / settings.height as f64;
+
+                let lens = sampler.get_2d();
+                let time = sampler.get_1d();
+                // black where the camera sees nothing
+                let colour = match camera.get_ray(u, v, lens, time) {
+                    Some(r) => camera.sensor() * ray_colour(&r, environment, world, lights, settings.max_depth, sampler.as_mut()),
+                    None => Colour::new(0.0, 0.0, 0.0)
+                };
+                pixel.sum += colour;
+                pixel.samples += 1;
+
+                if let Some(adaptive) = &settings.adaptive {
+                    pixel.error.add(colour);
+                    if pixel.samples.is_multiple_of(adaptive.min_samples) && pixel.error.relative_error() <= adaptive.threshold {
+                        pixel.converged = true;
+                    }
+                }
+            }
+
+            pixels.push(pixel);
+        }
+    }
+
+    pixels
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64(input: &mut impl Read) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(input)?))
+}
+
+// everything a render has gathered so far: the sum of every pixel's samples, how many there
+// are and, for adaptive sampling, how noisy they are. a render is a series of passes, each
+// taking every pixel on to some more samples
+#[derive(Clone)]
+pub struct Accumulator {
+    width: usize,
+    height: usize,
+    pixels: Vec<PixelState>,
+    passes: u64,
+    samples_per_pixel: u64 // every pixel has this many samples, or stopped early (or took more)
+}
+
+#[derive(Clone, Default)]
+struct PixelState {
+    sum: Colour,
+    samples: u64,
+    error: PixelError,
+    converged: bool
+}
+
+impl Accumulator {
+    pub fn new(width: usize, height: usize) -> Accumulator {
+        Accumulator {
+            width,
+            height,
+            pixels: vec![PixelState::default(); width * height],
+            passes: 0,
+            samples_per_pixel: 0
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn passes(&self) -> u64 {
+        self.passes
+    }
+
+    pub fn samples_per_pixel(&self) -> u64 {
+        self.samples_per_pixel
+    }
+
+    // every pixel's sums and counts exactly as they are (see checkpoint.rs), little endian
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        for n in [self.width as u64, self.height as u64, self.passes, self.samples_per_pixel] {
+            out.write_all(&n.to_le_bytes())?;
+        }
+        for pixel in &self.pixels {
+            for c in pixel.sum.e {
+                out.write_all(&c.to_le_bytes())?;
+            }
+            out.write_all(&pixel.samples.to_le_bytes())?;
+            out.write_all(&pixel.error.count.to_le_bytes())?;
+            out.write_all(&pixel.error.mean.to_le_bytes())?;
+            out.write_all(&pixel.error.m2.to_le_bytes())?;
+            out.write_all(&[pixel.converged as u8])?;
+        }
+        Ok(())
+    }
+
+    pub fn read(input: &mut impl Read) -> io::Result<Accumulator> {
+        let width = read_u64(input)? as usize;
+        let height = read_u64(input)? as usize;
+        let passes = read_u64(input)?;
+        let samples_per_pixel = read_u64(input)?;
+        let count = width.checked_mul(height)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("bad image size {}x{}", width, height)))?;
+
+        // a damaged size runs out of input long before it runs out of memory
+        let mut pixels = Vec::with_capacity(count.min(1 << 20));
+        for _ in 0..count {
+            let sum = Colour::new(read_f64(input)?, read_f64(input)?, read_f64(input)?);
+            let samples = read_u64(input)?;
+            let error = PixelError {
+                count: read_u64(input)?,
+                mean: read_f64(input)?,
+                m2: read_f64(input)?
+            };
+            let mut converged = [0];
+            input.read_exact(&mut converged)?;
+            pixels.push(PixelState { sum, samples, error, converged: converged[0] != 0 });
+        }
+
+        Ok(Accumulator { width, height, pixels, passes, samples_per_pixel })
+    }
+
+    // the image so far, black where a pixel has no samples yet
+    pub fn framebuffer(&self) -> Framebuffer {
+        let mut image = Framebuffer::new(self.width, self.height);
+        for (i, pixel) in self.pixels.iter().enumerate() {
+            if pixel.samples > 0 {
+                image.pixels[i] = pixel.sum / pixel.samples as f64;
+            }
+            image.samples[i] = pixel.samples;
+        }
+        image
+    }
+}
+
+// called after every finished tile with (tiles done, total tiles), possibly from several threads
+pub type Progress = Box<dyn Fn(usize, usize) + Send + Sync>;
+
+// renders a world as seen through a camera into a framebuffer
+pub struct Renderer {
+    world: World,
+    lights: World,
+    environment: Arc<dyn Environment>,
+    camera: Camera,
+    settings: RenderSettings,
+    bvh_stats: Option<BuildStats>,
+    progress: Option<Progress>
+}
+
+impl Renderer {
+    // the lights are the top level objects of world that are lights (see Hit::is_light),
+    // found before the world goes into the bvh. lights that are a point or a direction
+    // (Hit::is_punctual) can't be hit, they are only kept among the lights, like an environment.
+    // sampling them is the only way to find them, so they stay lights without light sampling.
+    // rays that miss everything see the camera's background, unless given an environment
+    pub fn new(world: World, camera: Camera, settings: RenderSettings) -> Renderer {
+        let (punctual, world): (World, World) = world.into_iter().partition(|object| object.is_punctual());
+        let lights = if settings.light_sampling {
+            world.iter().filter(|object| object.is_light()).chain(&punctual).cloned().collect()
+        } else {
+            punctual
+        };
+
+        // over the shutter interval of the camera
+        let (world, bvh_stats) = match settings.bvh {
+            Some(split) => {
+                let bvh = BVH::with_split(world, camera.shutter(), split);
+                let stats = bvh.stats().clone();
+                let world: World = vec![Arc::new(Box::new(bvh))];
+                (world, Some(stats))
+            }
+            None => (world, None)
+        };
+
+        Renderer {
+            world,
+            lights,
+            environment: Arc::new(environment::Uniform::new(camera.background)),
+            camera,
+            settings,
+            bvh_stats,
+            progress: None
+        }
+    }
+
+    // what rays that leave the scene see instead of the camera's background, one of the
+    // lights if it is one
+    pub fn with_environment(mut self, environment: Arc<dyn Environment>) -> Renderer {
+        if self.settings.light_sampling && environment.is_light() {
+            self.lights.push(Arc::new(Box::new(environment::Light::new(environment.clone()))));
+        }
+        self.environment = environment;
+        self
+    }
+
+    pub fn with_progress(mut self, progress: impl Fn(usize, usize) + Send + Sync + 'static) -> Renderer {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    pub fn bvh_stats(&self) -> Option<&BuildStats> {
+        self.bvh_stats.as_ref()
+    }
+
+    pub fn light_count(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn render(&self) -> Result<Framebuffer, Error> {
+        let mut state = Accumulator::new(self.settings.width, self.settings.height);
+        self.render_progressive(&mut state, self.settings.samples_per_pixel, None, |_| Ok(()))?;
+        Ok(state.framebuffer())
+    }
+
+    // renders into state in passes of pass_samples more samples per pixel, until every pixel
+    // has samples_per_pixel, calling on_pass after every pass. adaptive sampling then hands
+    // out the samples the converged pixels didn't take in passes of its own (see Adaptive).
+    // once time_limit is up the pass still running is dropped, so state is always left as the
+    // last complete pass made it
+    pub fn render_progressive(
+        &self,
+        state: &mut Accumulator,
+        pass_samples: u64,
+        time_limit: Option<Duration>,
+        mut on_pass: impl FnMut(&Accumulator) -> Result<(), Error>
+    ) -> Result<(), Error> {
+        let deadline = time_limit.map(|limit| Instant::now() + limit);
+        self.check_settings()?;
+        if pass_samples == 0 {
+            return Err("samples per pass must be positive".into());
+        }
+        if state.width != self.settings.width || state.height != self.settings.height {
+            return Err(format!(
+                "can't add a {}x{} render to a {}x{} one",
+                self.settings.width, self.settings.height, state.width, state.height
+            ).into());
+        }
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.settings.threads)
+            .build()
+            .map_err(|e| format!("can't start render threads: {}", e))?;
+
+        while state.samples_per_pixel < self.settings.samples_per_pixel {
+            let target = (state.samples_per_pixel + pass_samples).min(self.settings.samples_per_pixel);
+            if !self.render_pass(&pool, state, &vec![target; state.pixels.len()], deadline) {
+                return Ok(());
+            }
+            state.samples_per_pixel = target;
+            state.passes += 1;
+            on_pass(state)?;
+        }
+
+        if let Some(adaptive) = &self.settings.adaptive {
+            while let Some(targets) = redistribute(state, self.settings.samples_per_pixel, adaptive) {
+                if !self.render_pass(&pool, state, &targets, deadline) {
+                    return Ok(());
+                }
+                state.passes += 1;
+                on_pass(state)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_settings(&self) -> Result<(), Error> {
+        let settings = &self.settings;
+        if settings.width < 2 || settings.height < 2 {
+            return Err(format!("image must be at least 2x2 pixels, got {}x{}", settings.width, settings.height).into());
+        }
+        if settings.samples_per_pixel == 0 {
+            return Err("samples per pixel must be positive".into());
+        }
+        if let Some(adaptive) = &settings.adaptive {
+            if adaptive.min_samples == 0 || adaptive.min_samples > settings.samples_per_pixel {
+                return Err(format!(
+                    "minimum samples per pixel must be between 1 and {}, got {}",
+                    settings.samples_per_pixel, adaptive.min_samples
+                ).into());
+            }
+            if adaptive.threshold.is_nan() || adaptive.threshold <= 0.0 {
+                return Err(format!("adaptive threshold must be positive, got {}", adaptive.threshold).into());
+            }
+            if adaptive.max_samples < settings.samples_per_pixel {
+                return Err(format!(
+                    "maximum samples per pixel must be at least {}, got {}",
+                    settings.samples_per_pixel, adaptive.max_samples
+                ).into());
+            }
+        }
+        if settings.tile_size == 0 {
+            return Err("tile size must be positive".into());
+        }
+        Ok(())
+    }
+
+    // takes every pixel on to its target, false (and state untouched) if the deadline passed
+    // first
+    fn render_pass(&self, pool: &rayon::ThreadPool, state: &mut Accumulator, targets: &[u64], deadline: Option<Instant>) -> bool {
+        let settings = &self.settings;
+        let tiles = split_tiles(settings.width, settings.height, settings.tile_size);
+        let tiles_done = AtomicUsize::new(0);
+        let out_of_time = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+        let rendered: Option<Vec<Vec<PixelState>>> = pool.install(|| {
+            tiles
+                .par_iter()
+                .map(|tile| {
+                    // checked between tiles, a pass overshoots by at most one tile per thread
+                    if out_of_time() {
+                        return None;
+                    }
+                    let pixels = render_tile(
+                        tile, state, targets, &self.world, &self.lights, self.environment.as_ref(), &self.camera, settings
+                    );
+                    let done = tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+                    if let Some(progress) = &self.progress {
+                        progress(done, tiles.len());
+                    }
+                    Some(pixels)
+                })
+                .collect()
+        });
+        let rendered = match rendered {
+            Some(rendered) => rendered,
+            None => return false
+        };
+
+        for (tile, pixels) in tiles.iter().zip(rendered) {
+            let mut pixels = pixels.into_iter();
+            for y in tile.y0..tile.y1 {
+                for x in tile.x0..tile.x1 {
+                    state.pixels[y * state.width + x] = pixels.next().unwrap();
+                }
+            }
+        }
+        true
+    }
+}
+
+// the targets of the next pass handing out what's left of the samples_per_pixel budget of the
+// image, None once it is spent or no pixel can take more. the noisiest pixels get min_samples
+// more each, worst first, in an order that only depends on state, so the image doesn't
+// depend on the passes it was rendered in
+fn redistribute(state: &Accumulator, samples_per_pixel: u64, adaptive: &Adaptive) -> Option<Vec<u64>> {
+    let used: u64 = state.pixels.iter().map(|pixel| pixel.samples).sum();
+    let mut budget = (samples_per_pixel * state.pixels.len() as u64).saturating_sub(used);
+
+    let errors: Vec<f64> = state.pixels.iter().map(|pixel| pixel.error.relative_error()).collect();
+    let mut noisy: Vec<usize> = (0..state.pixels.len())
+        .filter(|&i| !state.pixels[i].converged && state.pixels[i].samples < adaptive.max_samples)
+        .collect();
+    // stable, so pixels with the same error go in image order
+    noisy.sort_by(|&a, &b| errors[b].total_cmp(&errors[a]));
+
+    let mut targets: Vec<u64> = state.pixels.iter().map(|pixel| pixel.samples).collect();
+    let mut any = false;
+    for i in noisy {
+        if budget == 0 {
+            break;
+        }
+        let more = adaptive.min_samples.min(adaptive.max_samples - targets[i]).min(budget);
+        targets[i] += more;
+        budget -= more;
+        any = true;
+    }
+    any.then_some(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hit::directional_light::DirectionalLight;
+    use crate::hit::point_light::PointLight;
+    use crate::hit::sphere::Sphere;
+    use crate::hit::spot_light::SpotLight;
+    use crate::material::diffuse::Diffuse;
+    use crate::material::matte::Matte;
+    use crate::scenes;
+    use crate::texture::solid::Solid;
+    use crate::vec::Point3;
+
+    fn cornell_box(settings: &RenderSettings) -> Renderer {
+        let scene = (scenes::find_scene("cornell_box").unwrap().build)(1.0, 0).unwrap();
+        Renderer::new(scene.world, scene.camera, settings.clone())
+    }
+
+    fn settings() -> RenderSettings {
+        RenderSettings {
+            width: 12,
+            height: 12,
+            samples_per_pixel: 8,
+            max_depth: 4,
+            threads: 1,
+            tile_size: 32,
+            ..RenderSettings::default()
+        }
+    }
+
+    // bit for bit, samples included
+    fn assert_same(a: &Framebuffer, b: &Framebuffer) {
+        assert_eq!((a.width(), a.height()), (b.width(), b.height()));
+        for y in 0..a.height() {
+            for x in 0..a.width() {
+                assert_eq!(a.get(x, y).e, b.get(x, y).e, "pixel {} {}", x, y);
+                assert_eq!(a.samples(x, y), b.samples(x, y), "samples of pixel {} {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn threads_and_tiles_dont_change_the_image() {
+        let image = cornell_box(&settings()).render().unwrap();
+        assert!(image.pixels().iter().any(|pixel| !pixel.near_zero()));
+
+        for (threads, tile_size) in [(2, 5), (3, 1), (4, 7)] {
+            let settings = RenderSettings { threads, tile_size, ..settings() };
+            assert_same(&image, &cornell_box(&settings).render().unwrap());
+        }
+    }
+
+    #[test]
+    fn passes_add_up_to_a_single_render() {
+        let adaptive = Some(Adaptive { min_samples: 2, threshold: 0.2, max_samples: 32 });
+        for settings in [settings(), RenderSettings { adaptive, ..settings() }] {
+            let renderer = cornell_box(&settings);
+            let image = renderer.render().unwrap();
+            // some pixels stop early
+            let all = (settings.width * settings.height) as u64 * settings.samples_per_pixel;
+            assert!(image.total_samples() <= all);
+            let fewest = (0..settings.height).flat_map(|y| (0..settings.width).map(move |x| (x, y)))
+                .map(|(x, y)| image.samples(x, y))
+                .min()
+                .unwrap();
+            assert_eq!(fewest < settings.samples_per_pixel, settings.adaptive.is_some());
+
+            // the passes handing out the samples of the pixels that stopped are the same
+            // whatever the size of the others
+            let mut redistributing = None;
+            for pass_samples in [1, 3] {
+                let mut state = Accumulator::new(settings.width, settings.height);
+                let mut passes = 0;
+                renderer.render_progressive(&mut state, pass_samples, None, |_| {
+                    passes += 1;
+                    Ok(())
+                }).unwrap();
+                let extra = passes - settings.samples_per_pixel.div_ceil(pass_samples);
+                assert_eq!(*redistributing.get_or_insert(extra), extra);
+                assert_eq!(state.passes(), passes);
+                assert_same(&image, &state.framebuffer());
+            }
+            assert_eq!(redistributing.unwrap() > 0, settings.adaptive.is_some());
+        }
+    }
+
+    // a ground lit only by lights that can't be hit
+    fn punctual_lights(settings: &RenderSettings) -> Renderer {
+        let grey = Matte::new_arc(Solid::new_arc(Colour::new(0.5, 0.5, 0.5)));
+        let world: World = vec![
+            Sphere::new_hittable(Point3::new(0.0, -1000.0, 0.0), 1000.0, grey),
+            Arc::new(Box::new(PointLight::new(Point3::new(-2.0, 3.0, 0.0), Colour::new(10.0, 10.0, 10.0)))),
+            Arc::new(Box::new(SpotLight::new(
+                Point3::new(2.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0), Colour::new(10.0, 10.0, 10.0), 20.0, 30.0
+            ))),
+            Arc::new(Box::new(DirectionalLight::new(Vec3::new(1.0, -1.0, 0.5), Colour::new(0.5, 0.5, 0.5))))
+        ];
+        let camera = Camera::new(
+            Point3::new(0.0, 4.0, -8.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            10.0,
+            Colour::new(0.0, 0.0, 0.0)
+        );
+        Renderer::new(world, camera, settings.clone())
+    }
+
+    #[test]
+    fn punctual_lights_shine_without_light_sampling() {
+        let image = punctual_lights(&settings()).render().unwrap();
+        assert!(image.pixels().iter().filter(|pixel| !pixel.near_zero()).count() > 50);
+
+        // nothing else is a light, so leaving light sampling out changes nothing at all
+        let settings = RenderSettings { light_sampling: false, ..settings() };
+        let renderer = punctual_lights(&settings);
+        assert_eq!(renderer.light_count(), 3);
+        assert_same(&image, &renderer.render().unwrap());
+    }
+
+    // a ball lit by a small light, in the dark: the pixels that see nothing stop right away
+    // and the ones on the ball, in and around its soft shadow, get what they leave
+    #[test]
+    fn adaptive_sampling_gives_noisy_pixels_more() {
+        let adaptive = Some(Adaptive { min_samples: 4, threshold: 0.01, max_samples: 256 });
+        let settings = RenderSettings { width: 16, height: 16, samples_per_pixel: 16, adaptive, ..settings() };
+        let grey = Matte::new_arc(Solid::new_arc(Colour::new(0.5, 0.5, 0.5)));
+        let light = Diffuse::new_arc(Solid::new_arc(Colour::new(20.0, 20.0, 20.0)));
+        let world: World = vec![
+            Sphere::new_hittable(Point3::new(0.0, 0.0, 0.0), 1.0, grey),
+            Sphere::new_hittable(Point3::new(-2.0, 2.0, -1.0), 0.3, light)
+        ];
+        let camera = Camera::new(
+            Point3::new(0.0, 0.0, -5.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            10.0,
+            Colour::new(0.0, 0.0, 0.0)
+        );
+        let image = Renderer::new(world, camera, settings.clone()).render().unwrap();
+
+        let all = (settings.width * settings.height) as u64 * settings.samples_per_pixel;
+        assert!(image.total_samples() <= all, "{} samples, {} to spend", image.total_samples(), all);
+        let (mut dark, mut more) = (0, 0);
+        for y in 0..settings.height {
+            for x in 0..settings.width {
+                let samples = image.samples(x, y);
+                assert!(samples <= 256);
+                if image.get(x, y).near_zero() {
+                    dark += 1;
+                    assert_eq!(samples, 4, "pixel {} {}", x, y);
+                } else if samples > settings.samples_per_pixel {
+                    more += 1;
+                }
+            }
+        }
+        assert!(dark > 50 && more > 20, "{} dark pixels, {} with more than the average", dark, more);
+        // and the samples of the dark pixels all went to others
+        assert!(image.total_samples() > all - settings.samples_per_pixel, "{} samples, {} to spend", image.total_samples(), all);
+    }
+}
@@ -0,0 +1,246 @@
+pub mod independent;
+pub mod stratified;
+pub mod halton;
+pub mod sobol;
+
+use std::f64::consts::PI;
+
+use crate::vec::Vec3;
+
+// where the "random" numbers of a pixel sample come from.
+// every sample of a pixel is a point in a many dimensional unit cube: the first two
+// dimensions place it inside the pixel, the next two on the lens, one picks the time, and
+// every bounce after that takes a fixed set more (see ray_colour). purely random points clump
+// together and leave holes, the other samplers spread the samples of a pixel evenly over
+// every dimension, so the same number of samples gets closer to the true pixel colour.
+//
+// dimensions are handed out in order: the nth call of get_1d or get_2d after
+// start_pixel_sample is always the same dimension of the same sample, so the result only
+// depends on the seed, the pixel and the sample index.
+pub trait Sampler {
+    fn start_pixel_sample(&mut self, x: u64, y: u64, index: u64);
+
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SamplerKind {
+    Independent, // every number uniform random, on its own
+    Stratified, // one sample per stratum of every dimension, jittered inside it
+    Halton, // the halton sequence, scrambled and shifted differently for every pixel
+    Sobol // the sobol sequence, owen scrambled differently for every pixel
+}
+
+impl SamplerKind {
+    // samples_per_pixel is how many samples the strata are made for, samples past it start
+    // over with new strata
+    pub fn create(self, seed: u64, samples_per_pixel: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(independent::Independent::new(seed)),
+            SamplerKind::Stratified => Box::new(stratified::Stratified::new(seed, samples_per_pixel)),
+            SamplerKind::Halton => Box::new(halton::Halton::new(seed)),
+            SamplerKind::Sobol => Box::new(sobol::Sobol::new(seed))
+        }
+    }
+}
+
+// turning uniform points in the unit square into points in other shapes, without clumping
+// evenly spread samples together
+
+// a point in the unit disk. concentric squares go to concentric circles, so neighbouring
+// samples stay neighbours
+pub fn disk(u: (f64, f64)) -> (f64, f64) {
+    let a = 2.0 * u.0 - 1.0;
+    let b = 2.0 * u.1 - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+// a point in the regular polygon with corners on the unit circle, one of them rotation
+// radians anticlockwise from straight up. u.0 picks one of the triangles from the middle to
+// an edge and what's left of it and u.1 place the point inside
+pub fn polygon(u: (f64, f64), sides: u32, rotation: f64) -> (f64, f64) {
+    let n = sides as f64;
+    let side = (u.0 * n).floor().min(n - 1.0);
+    let along = u.0 * n - side;
+    let corner = |k: f64| {
+        let angle = rotation + 2.0 * PI * k / n;
+        (-angle.sin(), angle.cos())
+    };
+    let (a, b) = (corner(side), corner(side + 1.0));
+    // sqrt so the triangle is covered evenly, its far end being wider
+    let r = along.sqrt();
+    (r * (a.0 + u.1 * (b.0 - a.0)), r * (a.1 + u.1 * (b.1 - a.1)))
+}
+
+// a direction on the unit sphere, every direction equally likely
+pub fn sphere(u: (f64, f64)) -> Vec3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// a direction around +z with density cos / pi: a point on the disk, lifted up onto the
+// hemisphere (malley's method)
+pub fn cosine_hemisphere(u: (f64, f64)) -> Vec3 {
+    let (x, y) = disk(u);
+    Vec3::new(x, y, (1.0 - x * x - y * y).max(0.0).sqrt())
+}
+
+// a density over [0, 1) made of n equal steps, each as high as its weight, for putting
+// samples where something is bright (an environment map's sun).
+// the steps are found by binary search of the running sum, and u is stretched over the
+// step it lands in, so evenly spread u stay evenly spread
+pub struct Distribution {
+    weights: Vec<f64>,
+    cdf: Vec<f64>, // cdf[i] is the chance of landing before step i, n + 1 of them
+    total: f64 // the integral of the weights over [0, 1)
+}
+
+impl Distribution {
+    // weights can't be negative. if they are all 0 every step is as likely
+    pub fn new(weights: Vec<f64>) -> Distribution {
+        let n = weights.len() as f64;
+        let mut cdf = Vec::with_capacity(weights.len() + 1);
+        cdf.push(0.0);
+        for weight in &weights {
+            cdf.push(cdf.last().unwrap() + weight / n);
+        }
+        let total = *cdf.last().unwrap();
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if total > 0.0 { *c / total } else { i as f64 / n };
+        }
+        Distribution { weights, cdf, total }
+    }
+
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    // a point of [0, 1) and the step it is in
+    pub fn sample(&self, u: f64) -> (f64, usize) {
+        let step = (self.cdf.partition_point(|c| *c <= u) - 1).min(self.len() - 1);
+        let width = self.cdf[step + 1] - self.cdf[step];
+        let along = if width > 0.0 { ((u - self.cdf[step]) / width).clamp(0.0, 1.0) } else { 0.5 };
+        ((step as f64 + along) / self.len() as f64, step)
+    }
+
+    // the density of sample at any point of step
+    pub fn pdf(&self, step: usize) -> f64 {
+        if self.total > 0.0 {
+            self.weights[step] / self.total
+        } else {
+            1.0
+        }
+    }
+}
+
+// the ith element of a random permutation of 0..n picked by seed, without building it
+// (kensler, correlated multi-jittered sampling): a hash that is invertible on the next power
+// of two, applied until it lands below n
+pub fn permute(mut i: u32, n: u32, seed: u32) -> u32 {
+    if n <= 1 {
+        return 0;
+    }
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Integrand = dyn Fn(&mut dyn Sampler) -> f64;
+
+    // root mean square error of estimating the integral of f over the unit cube, with
+    // samples_per_pixel samples in every one of many pixels
+    fn rmse(kind: SamplerKind, samples_per_pixel: u64, f: &Integrand, integral: f64) -> f64 {
+        let mut sampler = kind.create(3, samples_per_pixel);
+        let mut squared = 0.0;
+        let pixels = 32;
+        for y in 0..pixels {
+            for x in 0..pixels {
+                let mut sum = 0.0;
+                for index in 0..samples_per_pixel {
+                    sampler.start_pixel_sample(x, y, index);
+                    sum += f(sampler.as_mut());
+                }
+                let error = sum / samples_per_pixel as f64 - integral;
+                squared += error * error;
+            }
+        }
+        (squared / (pixels * pixels) as f64).sqrt()
+    }
+
+    #[test]
+    fn even_samplers_beat_independent_samples() {
+        // smooth over the pixel and the lens, then an edge: the disk of the lens
+        let smooth = |sampler: &mut dyn Sampler| {
+            let (x, y) = sampler.get_2d();
+            let (u, v) = sampler.get_2d();
+            x * y + u * u * v
+        };
+        let edge = |sampler: &mut dyn Sampler| {
+            sampler.get_2d();
+            let (u, v) = sampler.get_2d();
+            let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+            if a * a + b * b < 1.0 { 1.0 } else { 0.0 }
+        };
+
+        for samples_per_pixel in [16, 64] {
+            for (f, integral) in [(&smooth as &Integrand, 5.0 / 12.0), (&edge, PI / 4.0)] {
+                let independent = rmse(SamplerKind::Independent, samples_per_pixel, f, integral);
+                for kind in [SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol] {
+                    let error = rmse(kind, samples_per_pixel, f, integral);
+                    assert!(error < independent, "{:?} {} spp: {} against {}", kind, samples_per_pixel, error, independent);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::OnceLock;
+
+use rand::Rng;
+
+use crate::random::{self, Random};
+use super::{permute, Sampler};
+
+// the halton sequence: dimension d of point i is i written in the dth prime as base, with
+// its digits mirrored around the point (0.123 in base 10 for i = 321). every prefix of the
+// sequence is spread evenly over every dimension.
+// on their own the higher dimensions are badly correlated with each other (the first points
+// in bases 29 and 31 lie on a line), so every digit goes through a random permutation of the
+// digits of its base, different for every dimension and digit position. every pixel then
+// shifts the whole point set by its own random offset (wrapping around), otherwise every
+// pixel would get the same samples and the image would show the pattern.
+// past the last prime the dimensions are plain random.
+
+const DIMENSIONS: usize = 100;
+const PRECISION: u64 = 1 << 32; // digits past this don't move a sample noticeably
+
+pub struct Halton {
+    seed: u64,
+    pixel: (u64, u64),
+    index: u64,
+    dimension: usize,
+    rng: Random
+}
+
+impl Halton {
+    pub fn new(seed: u64) -> Halton {
+        Halton {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            rng: random::seeded(seed)
+        }
+    }
+
+    fn next(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        if dimension >= DIMENSIONS {
+            return self.rng.gen();
+        }
+
+        let shift = random::unit(random::hash(self.seed, &[self.pixel.0, self.pixel.1, dimension as u64]));
+        let value = radical_inverse(&digit_permutations()[dimension], self.index) + shift;
+        if value >= 1.0 {
+            value - 1.0
+        } else {
+            value
+        }
+    }
+}
+
+impl Sampler for Halton {
+    fn start_pixel_sample(&mut self, x: u64, y: u64, index: u64) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = random::stream(self.seed, &[x, y, index]);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.next()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.next(), self.next())
+    }
+}
+
+struct DigitPermutation {
+    base: u64,
+    digits: usize,
+    permutations: Vec<u16> // digits permutations of 0..base, one after the other
+}
+
+fn radical_inverse(permutation: &DigitPermutation, mut i: u64) -> f64 {
+    let base = permutation.base;
+    let inv_base = 1.0 / base as f64;
+    let mut scale = 1.0;
+    let mut value = 0.0;
+    // every digit counts, even the zeros past the last digit of i: they're permuted too
+    for digit in 0..permutation.digits {
+        scale *= inv_base;
+        let offset = digit * base as usize + (i % base) as usize;
+        value += permutation.permutations[offset] as f64 * scale;
+        i /= base;
+    }
+    value.min(1.0 - f64::EPSILON / 2.0)
+}
+
+// the same for every render, the per pixel shift is what the seed changes
+fn digit_permutations() -> &'static [DigitPermutation] {
+    static PERMUTATIONS: OnceLock<Vec<DigitPermutation>> = OnceLock::new();
+    PERMUTATIONS.get_or_init(|| {
+        primes(DIMENSIONS)
+            .into_iter()
+            .enumerate()
+            .map(|(dimension, base)| {
+                let mut digits = 0;
+                let mut reach = 1;
+                while reach < PRECISION {
+                    reach *= base;
+                    digits += 1;
+                }
+                let mut permutations = Vec::with_capacity(digits * base as usize);
+                for digit in 0..digits {
+                    let seed = random::hash(dimension as u64, &[digit as u64]) as u32;
+                    permutations.extend((0..base as u32).map(|d| permute(d, base as u32, seed) as u16));
+                }
+                DigitPermutation { base, digits, permutations }
+            })
+            .collect()
+    })
+}
+
+fn primes(count: usize) -> Vec<u64> {
+    let mut primes: Vec<u64> = Vec::with_capacity(count);
+    let mut n = 2;
+    while primes.len() < count {
+        if primes.iter().take_while(|&&p| p * p <= n).all(|&p| n % p != 0) {
+            primes.push(n);
+        }
+        n += 1;
+    }
+    primes
+}
@@ -0,0 +1,33 @@
+use rand::Rng;
+
+use crate::random::{self, Random};
+use super::Sampler;
+
+// plain uniform random numbers, a new stream for every pixel sample
+pub struct Independent {
+    seed: u64,
+    rng: Random
+}
+
+impl Independent {
+    pub fn new(seed: u64) -> Independent {
+        Independent {
+            seed,
+            rng: random::seeded(seed)
+        }
+    }
+}
+
+impl Sampler for Independent {
+    fn start_pixel_sample(&mut self, x: u64, y: u64, index: u64) {
+        self.rng = random::stream(self.seed, &[x, y, index]);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.gen(), self.rng.gen())
+    }
+}
@@ -0,0 +1,91 @@
+use super::Sampler;
+use crate::random;
+
+// the first two dimensions of the sobol sequence, owen scrambled (burley, practical hash-based
+// owen scrambling). those two are a (0, 2)-sequence: every power of two prefix puts exactly
+// one point into every cell of any grid of that many equal cells. owen scrambling randomly
+// swaps the halves of every interval at every level, which keeps that property but makes
+// the points as random as it can.
+// more dimensions are made by pairing up such 2d sets: every pair gets its own scramble and
+// its own shuffled order of the points, so pairs are well spread on their own and random
+// relative to each other. everything is seeded per pixel, so neighbouring pixels don't share
+// a pattern.
+pub struct Sobol {
+    seed: u64,
+    pixel_seed: u64,
+    index: u32,
+    dimension: u64
+}
+
+impl Sobol {
+    pub fn new(seed: u64) -> Sobol {
+        Sobol {
+            seed,
+            pixel_seed: seed,
+            index: 0,
+            dimension: 0
+        }
+    }
+
+    // the (shuffled) index of the current sample in the current dimension, and the seed the
+    // dimension's values are scrambled with
+    fn next_dimension(&mut self) -> (u32, u32) {
+        let hash = random::hash(self.pixel_seed, &[self.dimension]);
+        self.dimension += 1;
+        let index = nested_uniform_scramble(self.index, hash as u32);
+        (index, (hash >> 32) as u32)
+    }
+}
+
+impl Sampler for Sobol {
+    fn start_pixel_sample(&mut self, x: u64, y: u64, index: u64) {
+        self.pixel_seed = random::hash(self.seed, &[x, y]);
+        self.index = index as u32;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (index, seed) = self.next_dimension();
+        to_unit(nested_uniform_scramble(index.reverse_bits(), seed))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (index, seed) = self.next_dimension();
+        let second = random::hash(seed as u64, &[]) as u32;
+        (
+            to_unit(nested_uniform_scramble(index.reverse_bits(), seed)),
+            to_unit(nested_uniform_scramble(sobol_1(index), second))
+        )
+    }
+}
+
+// the second sobol dimension. the first is just the index with its bits reversed
+fn sobol_1(mut index: u32) -> u32 {
+    let mut v = 1 << 31;
+    let mut x = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            x ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    x
+}
+
+// owen scrambling of the bits of x, highest first: every bit is flipped depending on the
+// seed and all the bits above it. laine and karras' hash does that for the lowest bits
+// depending on the ones below, hence the reversing
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x.reverse_bits()
+}
+
+fn to_unit(x: u32) -> f64 {
+    x as f64 / (1u64 << 32) as f64
+}
@@ -0,0 +1,67 @@
+use rand::Rng;
+
+use crate::random::{self, Random};
+use super::{permute, Sampler};
+
+// every dimension is cut into as many strata as there are samples (a grid of about
+// sqrt(samples) by sqrt(samples) cells for 2d ones) and every sample gets its own stratum, at
+// a random point inside it. which sample gets which stratum is a different random permutation
+// for every pixel and dimension, so the dimensions don't line up with each other.
+// when the grid has more cells than samples a random subset of them is used.
+pub struct Stratified {
+    seed: u64,
+    samples: u32,
+    pixel: (u64, u64),
+    index: u64,
+    dimension: u64,
+    rng: Random
+}
+
+impl Stratified {
+    pub fn new(seed: u64, samples_per_pixel: u64) -> Stratified {
+        Stratified {
+            seed,
+            samples: samples_per_pixel.clamp(1, u32::MAX as u64) as u32,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            rng: random::seeded(seed)
+        }
+    }
+
+    // which of n cells the current sample gets in the current dimension
+    fn stratum(&mut self, n: u32) -> u32 {
+        let samples = self.samples as u64;
+        // samples past samples_per_pixel go through a new set of strata
+        let round = self.index / samples;
+        let i = (self.index % samples) as u32;
+        let seed = random::hash(self.seed, &[self.pixel.0, self.pixel.1, self.dimension, round]) as u32;
+        self.dimension += 1;
+        permute(i, n, seed)
+    }
+}
+
+impl Sampler for Stratified {
+    fn start_pixel_sample(&mut self, x: u64, y: u64, index: u64) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = random::stream(self.seed, &[x, y, index]);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let n = self.samples;
+        let stratum = self.stratum(n);
+        (stratum as f64 + self.rng.gen::<f64>()) / n as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let nx = (self.samples as f64).sqrt() as u32;
+        let ny = self.samples.div_ceil(nx);
+        let cell = self.stratum(nx * ny);
+        (
+            ((cell % nx) as f64 + self.rng.gen::<f64>()) / nx as f64,
+            ((cell / nx) as f64 + self.rng.gen::<f64>()) / ny as f64
+        )
+    }
+}
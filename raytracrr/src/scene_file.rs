@@ -0,0 +1,1016 @@
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use toml::{Spanned, Table, Value};
+
+use crate::animation::{Animation, CameraAnimation, Keyframe, Quaternion};
+use crate::camera::{Camera, Diaphragm, ProjectionKind, View};
+use crate::camera::exposure::{self, Exposure};
+use crate::environment::Environment;
+use crate::environment::map::EnvironmentMap;
+use crate::environment::sky::Sky;
+use crate::camera::fisheye::Mapping;
+use crate::hit::Hit;
+use crate::hit::animated::Animated;
+use crate::hit::block::Block;
+use crate::hit::constant_medium::ConstantMedium;
+use crate::hit::directional_light::DirectionalLight;
+use crate::hit::instance::Instance;
+use crate::hit::moving_sphere::MovingSphere;
+use crate::hit::point_light::PointLight;
+use crate::hit::quad::Quad;
+use crate::hit::rect::Rect;
+use crate::hit::sphere::Sphere;
+use crate::hit::spot_light::SpotLight;
+use crate::hit::transform::Transform;
+use crate::hit::triangle::Triangle;
+use crate::hit::world::World;
+use crate::material::Scatter;
+use crate::matrix::Matrix4;
+use crate::material::{dielectric::Dielectric, diffuse::Diffuse, isotropic::Isotropic, matte::Matte, metal::Metal};
+use crate::material::conductor::{Conductor, Preset};
+use crate::obj;
+use crate::random;
+use crate::scenes::Scene;
+use crate::texture::Texture;
+use crate::texture::{checker::Checker, image::Image, noise::Noise, solid::Solid};
+use crate::vec::{Colour, Vec3};
+use crate::Error;
+
+// scenes described in toml instead of rust, e.g.
+//
+//     background = [0.0, 0.0, 0.0]
+//
+//     [environment]                   # optional, what rays leaving the scene see instead
+//     type = "map"                    # an equirectangular .hdr or .exr, relative to the file
+//     path = "studio.hdr"
+//     rotation = 90                   # optional, degrees about the up axis
+//     intensity = 1                   # optional
+//     # or a clear sky and its sun, with radiance in cd/m² (see camera.exposure):
+//     # type = "sky", elevation = 30 (degrees, from 0 to 90), azimuth = 45 (optional,
+//     # degrees from -z towards +x), turbidity = 3 (optional, from 1.7 to 10, haze) and
+//     # intensity (optional)
+//
+//     [camera]
+//     lookfrom = [278, 278, -800]
+//     lookat = [278, 278, 0]
+//     vfov = 40
+//     shutter = [0, 1]                # optional, when the shutter opens and closes
+//     exposure = { f_stop = 8, shutter_speed = "1/125", iso = 100, compensation = 0 }
+//     # optional, scales radiance as a real camera would (lights in cd/m²). iso and
+//     # compensation (in stops) are optional, the shutter then opens at 0 for shutter_speed
+//     # seconds (a number, or "1/125") and can't be given as well
+//     white_balance = 3200            # optional, the light colour in kelvin that comes out white
+//     blades = 6                      # optional, the aperture is a polygon of that many sides
+//     blade_rotation = 15             # (3 or more) turned that many degrees, instead of round
+//     projection = { type = "fisheye", fov = 180, mapping = "equisolid" }
+//     # optional: perspective (the default), orthographic (height = 600, optional),
+//     # fisheye (fov and mapping optional, 180 and equidistant) or equirectangular
+//     stereo = 6.5                    # optional, eye distance of an over/under stereo pair
+//     animation = [                   # optional, keys of lookfrom, lookat, vup, vfov, aperture
+//         { time = 0 },               # and focus_dist, whatever a key leaves out is as above
+//         { time = 2, lookfrom = [0, 278, -800], vfov = 30 }
+//     ]
+//
+//     [textures.earth]
+//     type = "image"
+//     path = "earth.jpg"              # relative to the scene file
+//
+//     [materials.white]
+//     type = "matte"
+//     albedo = [0.73, 0.73, 0.73]     # a colour, a grey level, or the name of a texture
+//
+//     [materials.bottle]
+//     type = "dielectric"             # glass
+//     ir = 1.5
+//     roughness = 0.2                 # optional, a grey or a texture, from 0 (smooth) to 1
+//     absorption = { colour = [0.6, 0.9, 0.7], distance = 2 }
+//     # optional, coloured glass: the colour of white light that has gone distance through it
+//     # (optional, 1). thin = true (optional) makes a single surface a sheet of glass, like a
+//     # window, thickness thick (optional, 0) for the absorption
+//
+//     [materials.brushed]
+//     type = "conductor"              # a rough metal: gold, copper, aluminium or silver, or
+//     metal = "aluminium"             # eta = [r, g, b] and k = [r, g, b] instead
+//     roughness = 0.1                 # optional, a grey or a texture, from 0 (a mirror) to 1
+//     roughness_across = 0.4          # optional, the roughness across a grain going along
+//     grain = [1, 0, 0]               # grain, both or neither
+//
+//     [[objects]]
+//     type = "block"
+//     min = [0, 0, 0]
+//     max = [165, 330, 165]
+//     material = "white"
+//     transform = [{ rotate_y = 15 }, { translate = [265, 0, 295] }]
+//     # also: rotate_x, rotate_z, rotate = { axis = [1, 1, 0], angle = 30 },
+//     # scale = 2 or [1, 2, 1], shear = { xy = 0.5 } (x moves by 0.5 y, same for xz, yx, ...)
+//     # and matrix = [[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0]] (the top three rows)
+//     animation = [                   # optional, moves the object after the transform, time
+//         { time = 0 },               # in seconds as for frames and the shutter
+//         { time = 1, translate = [0, 50, 0], rotate = { axis = [0, 1, 0], angle = 90 }, scale = 1.5 }
+//     ]
+//
+//     [[objects]]                     # lights that are a point or a direction: only seen by
+//     type = "spot_light"             # what they light, not by the camera
+//     position = [278, 500, 278]
+//     direction = [0, -1, 0]
+//     intensity = 20000               # candela, and inner (optional, the same as outer) and
+//     outer = 30                      # outer degrees from direction, fading in between
+//     colour = [1, 1, 1]              # optional, and temperature = 3200 (optional, kelvin)
+//     # also: point_light (position, intensity) and directional_light (direction it shines
+//     # towards, illuminance in lux), with colour and temperature
+//
+//     [[objects]]
+//     type = "mesh"
+//     path = "bunny.obj"              # relative to the scene file, materials come from its mtl
+//     material = "white"              # optional, for faces without a usemtl
+//
+//     [[prototypes.crate]]            # objects built once and placed by instances
+//     type = "block"
+//     min = [0, 0, 0]
+//     max = [1, 1, 1]
+//     material = "white"
+//
+//     [[objects]]
+//     type = "instance"
+//     prototype = "crate"
+//     material = "red"                # optional, instead of the prototype's own materials
+//     transform = [{ scale = 100 }, { translate = [0, 0, 200] }]
+//
+// textures, materials and objects are parsed one table at a time so errors can name the
+// line and entry they come from (serde loses the position inside tagged tables).
+// see the scenes/ directory for the built-in scenes written in this format.
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    camera: CameraDesc,
+    #[serde(default)]
+    background: [f64; 3],
+    environment: Option<EnvironmentDesc>,
+    #[serde(default)]
+    textures: HashMap<String, Spanned<Table>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<Table>>,
+    #[serde(default)]
+    prototypes: HashMap<String, Vec<Spanned<Table>>>,
+    #[serde(default)]
+    objects: Vec<Spanned<Table>>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    lookfrom: [f64; 3],
+    lookat: [f64; 3],
+    #[serde(default = "default_vup")]
+    vup: [f64; 3],
+    vfov: f64,
+    #[serde(default)]
+    aperture: f64,
+    #[serde(default = "default_focus_dist")]
+    focus_dist: f64,
+    shutter: Option<[f64; 2]>,
+    exposure: Option<ExposureDesc>,
+    white_balance: Option<f64>,
+    #[serde(default)]
+    blades: u32,
+    #[serde(default)]
+    blade_rotation: f64,
+    #[serde(default)]
+    animation: Vec<CameraKeyDesc>,
+    #[serde(default)]
+    projection: ProjectionDesc,
+    stereo: Option<f64>
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ProjectionDesc {
+    #[default]
+    Perspective,
+    Orthographic { height: Option<f64> },
+    Fisheye {
+        #[serde(default = "default_fisheye_fov")]
+        fov: f64,
+        #[serde(default)]
+        mapping: MappingDesc
+    },
+    Equirectangular
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum MappingDesc {
+    #[default]
+    Equidistant,
+    Equisolid
+}
+
+fn default_fisheye_fov() -> f64 {
+    180.0
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum EnvironmentDesc {
+    Map {
+        path: PathBuf,
+        #[serde(default)]
+        rotation: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64
+    },
+    Sky {
+        elevation: f64,
+        #[serde(default)]
+        azimuth: f64,
+        #[serde(default = "default_turbidity")]
+        turbidity: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64
+    }
+}
+
+fn default_turbidity() -> f64 {
+    3.0
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExposureDesc {
+    f_stop: f64,
+    shutter_speed: ShutterSpeedDesc,
+    #[serde(default = "default_iso")]
+    iso: f64,
+    #[serde(default)]
+    compensation: f64
+}
+
+// seconds, or the fraction of a second cameras show ("1/125")
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ShutterSpeedDesc {
+    Seconds(f64),
+    Fraction(String)
+}
+
+impl ShutterSpeedDesc {
+    fn seconds(&self) -> Option<f64> {
+        match self {
+            ShutterSpeedDesc::Seconds(seconds) => Some(*seconds),
+            ShutterSpeedDesc::Fraction(fraction) => match fraction.split_once('/') {
+                Some((a, b)) => Some(a.trim().parse::<f64>().ok()? / b.trim().parse::<f64>().ok()?),
+                None => fraction.trim().parse().ok()
+            }
+        }
+    }
+}
+
+fn default_iso() -> f64 {
+    100.0
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraKeyDesc {
+    time: f64,
+    lookfrom: Option<[f64; 3]>,
+    lookat: Option<[f64; 3]>,
+    vup: Option<[f64; 3]>,
+    vfov: Option<f64>,
+    aperture: Option<f64>,
+    focus_dist: Option<f64>
+}
+
+fn default_vup() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_focus_dist() -> f64 {
+    10.0
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid { colour: [f64; 3] },
+    Checker { scale: f64, even: TextureRef, odd: TextureRef },
+    Noise {
+        scale: f64,
+        #[serde(default)]
+        seed: u64 // the same seed gives the same noise
+    },
+    Image { path: PathBuf }
+}
+
+// either an inline solid colour, a grey level or the name of an entry in [textures]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureRef {
+    Colour([f64; 3]),
+    Grey(f64),
+    Name(String)
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Matte { albedo: TextureRef },
+    Metal {
+        albedo: TextureRef,
+        #[serde(default)]
+        fuzz: f64
+    },
+    Dielectric {
+        ir: f64,
+        roughness: Option<TextureRef>,
+        absorption: Option<AbsorptionDesc>,
+        #[serde(default)]
+        thin: bool,
+        thickness: Option<f64> // of a thin sheet
+    },
+    Diffuse { emit: TextureRef },
+    Isotropic { albedo: TextureRef },
+    Conductor {
+        metal: Option<MetalDesc>, // or eta and k
+        eta: Option<[f64; 3]>,
+        k: Option<[f64; 3]>,
+        #[serde(default = "default_roughness")]
+        roughness: TextureRef,
+        roughness_across: Option<TextureRef>, // with grain
+        grain: Option<[f64; 3]>
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MetalDesc {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver
+}
+
+fn default_roughness() -> TextureRef {
+    TextureRef::Grey(0.0)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AbsorptionDesc {
+    colour: [f64; 3],
+    #[serde(default = "default_absorption_distance")]
+    distance: f64
+}
+
+fn default_absorption_distance() -> f64 {
+    1.0
+}
+
+fn default_light_colour() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+// a punctual light's colour times its brightness, and times the colour of a blackbody at
+// temperature if it has one
+fn light_colour(colour: [f64; 3], temperature: Option<f64>, brightness: f64, context: &str) -> Result<Colour, Error> {
+    let mut colour = brightness * vec3(colour);
+    if let Some(kelvin) = temperature {
+        if !exposure::KELVIN.contains(&kelvin) {
+            return Err(format!(
+                "{}.temperature: must be from {} to {} kelvin, got {}",
+                context, exposure::KELVIN.start(), exposure::KELVIN.end(), kelvin
+            ).into());
+        }
+        colour *= exposure::blackbody(kelvin);
+    }
+    Ok(colour)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RectAxis {
+    Xy,
+    Xz,
+    Yz
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere { center: [f64; 3], radius: f64, material: String },
+    MovingSphere {
+        center0: [f64; 3],
+        center1: [f64; 3],
+        radius: f64,
+        #[serde(default = "default_time")]
+        time: [f64; 2],
+        material: String
+    },
+    Rect { axis: RectAxis, u: [f64; 2], v: [f64; 2], k: f64, material: String },
+    Quad { q: [f64; 3], u: [f64; 3], v: [f64; 3], material: String },
+    Block { min: [f64; 3], max: [f64; 3], material: String },
+    Triangle { a: [f64; 3], b: [f64; 3], c: [f64; 3], material: String },
+    PointLight {
+        position: [f64; 3],
+        intensity: f64,
+        #[serde(default = "default_light_colour")]
+        colour: [f64; 3],
+        temperature: Option<f64>
+    },
+    SpotLight {
+        position: [f64; 3],
+        direction: [f64; 3],
+        intensity: f64,
+        inner: Option<f64>,
+        outer: f64,
+        #[serde(default = "default_light_colour")]
+        colour: [f64; 3],
+        temperature: Option<f64>
+    },
+    DirectionalLight {
+        direction: [f64; 3],
+        illuminance: f64,
+        #[serde(default = "default_light_colour")]
+        colour: [f64; 3],
+        temperature: Option<f64>
+    },
+    Mesh { path: PathBuf, material: Option<String> },
+    Instance { prototype: String, material: Option<String> },
+    ConstantMedium { boundary: Table, density: f64, albedo: TextureRef }
+}
+
+fn default_time() -> [f64; 2] {
+    [0.0, 1.0]
+}
+
+// applied in the order they are listed, angles in degrees and right handed
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TransformDesc {
+    RotateX(f64),
+    RotateY(f64),
+    RotateZ(f64),
+    Rotate(AxisAngle),
+    Translate([f64; 3]),
+    Scale(ScaleDesc),
+    Shear(ShearDesc),
+    Matrix([[f64; 4]; 3])
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AxisAngle {
+    axis: [f64; 3],
+    angle: f64
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDesc {
+    Uniform(f64),
+    Axes([f64; 3])
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ShearDesc {
+    xy: f64,
+    xz: f64,
+    yx: f64,
+    yz: f64,
+    zx: f64,
+    zy: f64
+}
+
+impl ScaleDesc {
+    fn factors(&self) -> Vec3 {
+        match self {
+            ScaleDesc::Uniform(factor) => Vec3::new(*factor, *factor, *factor),
+            ScaleDesc::Axes(factors) => vec3(*factors)
+        }
+    }
+}
+
+// every part left out stays as it is without the animation
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyDesc {
+    time: f64,
+    #[serde(default)]
+    translate: [f64; 3],
+    rotate: Option<AxisAngle>,
+    scale: Option<ScaleDesc>
+}
+
+impl KeyDesc {
+    fn keyframe(&self) -> Keyframe {
+        Keyframe::new(
+            self.time,
+            vec3(self.translate),
+            self.rotate.as_ref().map_or(Quaternion::IDENTITY, |r| Quaternion::rotation(vec3(r.axis), r.angle)),
+            self.scale.as_ref().map_or(Vec3::new(1.0, 1.0, 1.0), ScaleDesc::factors)
+        )
+    }
+}
+
+impl TransformDesc {
+    fn matrix(&self) -> Matrix4 {
+        match self {
+            TransformDesc::RotateX(angle) => Matrix4::rotation_x(*angle),
+            TransformDesc::RotateY(angle) => Matrix4::rotation_y(*angle),
+            TransformDesc::RotateZ(angle) => Matrix4::rotation_z(*angle),
+            TransformDesc::Rotate(AxisAngle { axis, angle }) => Matrix4::rotation(vec3(*axis), *angle),
+            TransformDesc::Translate(offset) => Matrix4::translation(vec3(*offset)),
+            TransformDesc::Scale(scale) => Matrix4::scaling(scale.factors()),
+            TransformDesc::Shear(s) => Matrix4::shearing(s.xy, s.xz, s.yx, s.yz, s.zx, s.zy),
+            TransformDesc::Matrix(rows) => {
+                let linear = rows.map(|row| [row[0], row[1], row[2]]);
+                Matrix4::new(linear, Vec3::new(rows[0][3], rows[1][3], rows[2][3]))
+            }
+        }
+    }
+}
+
+pub fn load(path: impl AsRef<Path>, aspect_ratio: f64) -> Result<Scene, Error> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("can't read scene file {}: {}", path.display(), e))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse(&text, base_dir, aspect_ratio).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+// base_dir is where relative paths (image textures, meshes) are looked up
+pub fn parse(text: &str, base_dir: &Path, aspect_ratio: f64) -> Result<Scene, Error> {
+    let file: SceneFile = toml::from_str(text)?;
+
+    let mut builder = Builder {
+        text,
+        base_dir,
+        texture_tables: file.textures,
+        textures: HashMap::new(),
+        pending: Vec::new(),
+        materials: HashMap::new(),
+        prototype_tables: file.prototypes,
+        prototypes: HashMap::new(),
+        pending_prototypes: Vec::new(),
+        shutter: 0.0..1.0
+    };
+
+    // sorted so the first error reported doesn't depend on hash order
+    let mut names: Vec<_> = file.materials.keys().cloned().collect();
+    names.sort();
+    for name in names {
+        let table = &file.materials[&name];
+        let context = format!("line {}: materials.{}", builder.line(table.span().start), name);
+        let desc: MaterialDesc = entry(table.get_ref(), &context)?;
+        let material = builder.material(desc, &context)?;
+        builder.materials.insert(name, material);
+    }
+
+    let desc = &file.camera;
+    let view = View {
+        lookfrom: vec3(desc.lookfrom),
+        lookat: vec3(desc.lookat),
+        vup: vec3(desc.vup),
+        vfov: desc.vfov,
+        aperture: desc.aperture,
+        focus_dist: desc.focus_dist
+    };
+    let projection = match desc.projection {
+        ProjectionDesc::Perspective => ProjectionKind::Perspective,
+        ProjectionDesc::Orthographic { height } => ProjectionKind::Orthographic { height },
+        ProjectionDesc::Fisheye { fov, mapping } => ProjectionKind::Fisheye {
+            fov,
+            mapping: match mapping {
+                MappingDesc::Equidistant => Mapping::Equidistant,
+                MappingDesc::Equisolid => Mapping::Equisolid
+            }
+        },
+        ProjectionDesc::Equirectangular => ProjectionKind::Equirectangular
+    };
+    let exposure = match &desc.exposure {
+        Some(exposure) => Some(camera_exposure(exposure, desc.shutter.is_some())?),
+        None => None
+    };
+    if let Some(kelvin) = desc.white_balance {
+        if !exposure::KELVIN.contains(&kelvin) {
+            return Err(format!(
+                "camera.white_balance: must be from {} to {} kelvin, got {}",
+                exposure::KELVIN.start(), exposure::KELVIN.end(), kelvin
+            ).into());
+        }
+    }
+    let shutter = desc.shutter.unwrap_or(default_time());
+    let camera = Camera::from_view(view, aspect_ratio, vec3(file.background))
+        .with_projection(projection)
+        .with_stereo(desc.stereo)
+        .with_diaphragm(Diaphragm { blades: desc.blades, rotation: desc.blade_rotation })
+        .with_shutter(shutter[0]..shutter[1])
+        .with_exposure(exposure)
+        .with_white_balance(desc.white_balance);
+    let camera_animation = if desc.animation.is_empty() {
+        None
+    } else {
+        let keys = desc.animation
+            .iter()
+            .map(|key| (key.time, View {
+                lookfrom: key.lookfrom.map_or(view.lookfrom, vec3),
+                lookat: key.lookat.map_or(view.lookat, vec3),
+                vup: key.vup.map_or(view.vup, vec3),
+                vfov: key.vfov.unwrap_or(view.vfov),
+                aperture: key.aperture.unwrap_or(view.aperture),
+                focus_dist: key.focus_dist.unwrap_or(view.focus_dist)
+            }))
+            .collect();
+        Some(CameraAnimation::keys(keys))
+    };
+
+    // prototypes are built for the camera's shutter, like the world's bvh
+    builder.shutter = camera.shutter();
+    let mut world = World::new();
+    for (i, table) in file.objects.iter().enumerate() {
+        let context = format!("line {}: objects[{}]", builder.line(table.span().start), i);
+        let object = builder.object(table.get_ref().clone(), &context)?;
+        world.push(Arc::new(object));
+    }
+
+    let environment: Option<Arc<dyn Environment>> = match file.environment {
+        Some(EnvironmentDesc::Map { path, rotation, intensity }) => Some(Arc::new(
+            EnvironmentMap::load(base_dir.join(path), rotation, intensity).map_err(|e| format!("environment: {}", e))?
+        )),
+        Some(EnvironmentDesc::Sky { elevation, azimuth, turbidity, intensity }) => {
+            if !(0.0..=90.0).contains(&elevation) {
+                return Err(format!("environment.elevation: must be from 0 to 90 degrees, got {}", elevation).into());
+            }
+            if !(1.7..=10.0).contains(&turbidity) {
+                return Err(format!("environment.turbidity: must be from 1.7 to 10, got {}", turbidity).into());
+            }
+            Some(Arc::new(Sky::new(elevation, azimuth, turbidity, intensity)))
+        }
+        None => None
+    };
+
+    Ok(Scene { world, camera, camera_animation, environment })
+}
+
+fn camera_exposure(desc: &ExposureDesc, shutter: bool) -> Result<Exposure, Error> {
+    if shutter {
+        return Err("camera.shutter: the shutter of a camera with an exposure is open for its shutter_speed".into());
+    }
+    let shutter_speed = desc.shutter_speed.seconds()
+        .filter(|seconds| *seconds > 0.0 && seconds.is_finite())
+        .ok_or("camera.exposure.shutter_speed: expected a positive number of seconds, or a fraction like \"1/125\"")?;
+    for (name, value) in [("f_stop", desc.f_stop), ("iso", desc.iso)] {
+        if !(value > 0.0 && value.is_finite()) {
+            return Err(format!("camera.exposure.{}: must be positive, got {}", name, value).into());
+        }
+    }
+    Ok(Exposure { compensation: desc.compensation, ..Exposure::new(desc.f_stop, shutter_speed, desc.iso) })
+}
+
+struct Builder<'a> {
+    text: &'a str,
+    base_dir: &'a Path,
+    texture_tables: HashMap<String, Spanned<Table>>,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    pending: Vec<String>, // textures being built, to catch checkers that contain themselves
+    materials: HashMap<String, Arc<dyn Scatter>>,
+    prototype_tables: HashMap<String, Vec<Spanned<Table>>>,
+    prototypes: HashMap<String, Arc<dyn Hit>>,
+    pending_prototypes: Vec<String>, // the same for prototypes with instances of themselves
+    shutter: Range<f64>
+}
+
+impl Builder<'_> {
+    fn line(&self, offset: usize) -> usize {
+        self.text[..offset].matches('\n').count() + 1
+    }
+
+    fn texture(&mut self, texture: &TextureRef, context: &str) -> Result<Arc<dyn Texture>, Error> {
+        let name = match texture {
+            TextureRef::Colour(colour) => return Ok(Solid::new_arc(vec3(*colour))),
+            TextureRef::Grey(grey) => return Ok(Solid::new_arc(Colour::new(*grey, *grey, *grey))),
+            TextureRef::Name(name) => name
+        };
+        if let Some(texture) = self.textures.get(name) {
+            return Ok(Arc::clone(texture));
+        }
+        if self.pending.contains(name) {
+            return Err(format!("{}: texture '{}' refers to itself", context, name).into());
+        }
+
+        let table = match self.texture_tables.get(name) {
+            Some(table) => table,
+            None => return Err(format!("{}: unknown texture '{}'", context, name).into())
+        };
+        let context = format!("line {}: textures.{}", self.line(table.span().start), name);
+        let desc: TextureDesc = entry(table.get_ref(), &context)?;
+
+        self.pending.push(name.clone());
+        let texture: Arc<dyn Texture> = match desc {
+            TextureDesc::Solid { colour } => Solid::new_arc(vec3(colour)),
+            TextureDesc::Checker { scale, even, odd } => {
+                let even = self.texture(&even, &context)?;
+                let odd = self.texture(&odd, &context)?;
+                Checker::new_arc(scale, even, odd)
+            }
+            TextureDesc::Noise { scale, seed } => Noise::new_arc(scale, &mut random::seeded(seed)),
+            TextureDesc::Image { path } => {
+                let image = Image::new(self.base_dir.join(path))
+                    .map_err(|e| format!("{}: {}", context, e))?;
+                Arc::new(image)
+            }
+        };
+        self.pending.pop();
+
+        self.textures.insert(name.clone(), Arc::clone(&texture));
+        Ok(texture)
+    }
+
+    fn material(&mut self, desc: MaterialDesc, context: &str) -> Result<Arc<dyn Scatter>, Error> {
+        Ok(match desc {
+            MaterialDesc::Matte { albedo } => Matte::new_arc(self.texture(&albedo, context)?),
+            MaterialDesc::Metal { albedo, fuzz } => Metal::new_arc(self.texture(&albedo, context)?, fuzz),
+            MaterialDesc::Dielectric { ir, roughness, absorption, thin, thickness } => {
+                let mut dielectric = Dielectric::new(ir);
+                if let Some(roughness) = roughness {
+                    dielectric = dielectric.with_roughness(self.texture(&roughness, context)?);
+                }
+                if let Some(AbsorptionDesc { colour, distance }) = absorption {
+                    if colour.iter().any(|c| *c <= 0.0 || *c > 1.0) {
+                        return Err(format!("{}.absorption.colour: must be above 0 and at most 1, got {:?}", context, colour).into());
+                    }
+                    if distance <= 0.0 {
+                        return Err(format!("{}.absorption.distance: must be positive, got {}", context, distance).into());
+                    }
+                    dielectric = dielectric.with_absorption(vec3(colour), distance);
+                }
+                match (thin, thickness) {
+                    (true, thickness) => dielectric = dielectric.with_thin_sheet(thickness.unwrap_or(0.0)),
+                    (false, Some(_)) => return Err(format!("{}: thickness is for thin sheets (thin = true)", context).into()),
+                    (false, None) => ()
+                }
+                Arc::new(dielectric)
+            }
+            MaterialDesc::Diffuse { emit } => Diffuse::new_arc(self.texture(&emit, context)?),
+            MaterialDesc::Isotropic { albedo } => Isotropic::new_arc(self.texture(&albedo, context)?),
+            MaterialDesc::Conductor { metal, eta, k, roughness, roughness_across, grain } => {
+                let (eta, k) = match (metal, eta, k) {
+                    (Some(metal), None, None) => match metal {
+                        MetalDesc::Gold => Preset::Gold,
+                        MetalDesc::Copper => Preset::Copper,
+                        MetalDesc::Aluminium => Preset::Aluminium,
+                        MetalDesc::Silver => Preset::Silver
+                    }.ior(),
+                    (None, Some(eta), Some(k)) => (vec3(eta), vec3(k)),
+                    _ => return Err(format!("{}: expected either metal, or both eta and k", context).into())
+                };
+                let conductor = Conductor::new(eta, k, self.texture(&roughness, context)?);
+                Arc::new(match (roughness_across, grain) {
+                    (Some(across), Some(grain)) => conductor.with_grain(vec3(grain), self.texture(&across, context)?),
+                    (None, None) => conductor,
+                    _ => return Err(format!("{}: roughness_across and grain go together", context).into())
+                })
+            }
+        })
+    }
+
+    fn named_material(&self, name: &str, context: &str) -> Result<Arc<dyn Scatter>, Error> {
+        self.materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("{}: unknown material '{}'", context, name).into())
+    }
+
+    // built the first time an instance refers to it, shared by all of them after that
+    fn prototype(&mut self, name: &str, context: &str) -> Result<Arc<dyn Hit>, Error> {
+        if let Some(prototype) = self.prototypes.get(name) {
+            return Ok(Arc::clone(prototype));
+        }
+        if self.pending_prototypes.iter().any(|pending| pending == name) {
+            return Err(format!("{}: prototype '{}' contains an instance of itself", context, name).into());
+        }
+        let tables = match self.prototype_tables.get(name) {
+            Some(tables) => tables.clone(),
+            None => return Err(format!("{}: unknown prototype '{}'", context, name).into())
+        };
+
+        self.pending_prototypes.push(name.to_string());
+        let mut objects = Vec::with_capacity(tables.len());
+        for (i, table) in tables.iter().enumerate() {
+            let context = format!("line {}: prototypes.{}[{}]", self.line(table.span().start), name, i);
+            objects.push(self.object(table.get_ref().clone(), &context)?);
+        }
+        self.pending_prototypes.pop();
+
+        let prototype: Arc<dyn Hit> = match objects.len() {
+            0 => return Err(format!("{}: prototype '{}' has no objects", context, name).into()),
+            1 => Arc::from(objects.pop().unwrap()),
+            _ => Instance::prototype(objects.into_iter().map(Arc::new).collect(), self.shutter.clone())
+        };
+        self.prototypes.insert(name.to_string(), Arc::clone(&prototype));
+        Ok(prototype)
+    }
+
+    fn object(&mut self, mut table: Table, context: &str) -> Result<Box<dyn Hit>, Error> {
+        let transforms: Vec<TransformDesc> = match table.remove("transform") {
+            Some(value) => from_value(value, &format!("{}.transform", context))?,
+            None => Vec::new()
+        };
+        let mut matrix = None;
+        if !transforms.is_empty() {
+            let composed = transforms
+                .iter()
+                .fold(Matrix4::IDENTITY, |matrix, transform| matrix.then(transform.matrix()));
+            if composed.inverse().is_none() {
+                return Err(format!("{}.transform: flattens the object, it can't be undone", context).into());
+            }
+            matrix = Some(composed);
+        }
+        let keys: Vec<KeyDesc> = match table.remove("animation") {
+            Some(value) => from_value(value, &format!("{}.animation", context))?,
+            None => Vec::new()
+        };
+
+        let desc: ObjectDesc = entry(&table, context)?;
+        let mut object: Box<dyn Hit> = match desc {
+            ObjectDesc::Sphere { center, radius, material } => Box::new(Sphere::new(
+                vec3(center), radius, self.named_material(&material, context)?
+            )),
+            ObjectDesc::MovingSphere { center0, center1, radius, time, material } => Box::new(MovingSphere::new(
+                vec3(center0), vec3(center1), radius, time[0]..time[1], self.named_material(&material, context)?
+            )),
+            ObjectDesc::Rect { axis, u, v, k, material } => {
+                let axis = match axis {
+                    RectAxis::Xy => 0,
+                    RectAxis::Xz => 1,
+                    RectAxis::Yz => 2
+                };
+                Box::new(Rect::new(u[0]..u[1], v[0]..v[1], k, axis, self.named_material(&material, context)?))
+            }
+            ObjectDesc::Quad { q, u, v, material } => Box::new(Quad::new(
+                vec3(q), vec3(u), vec3(v), self.named_material(&material, context)?
+            )),
+            ObjectDesc::Block { min, max, material } => Box::new(Block::new(
+                vec3(min), vec3(max), self.named_material(&material, context)?
+            )),
+            ObjectDesc::Triangle { a, b, c, material } => Box::new(Triangle::new(
+                vec3(a), vec3(b), vec3(c), self.named_material(&material, context)?
+            )),
+            ObjectDesc::PointLight { position, intensity, colour, temperature } => Box::new(PointLight::new(
+                vec3(position), light_colour(colour, temperature, intensity, context)?
+            )),
+            ObjectDesc::SpotLight { position, direction, intensity, inner, outer, colour, temperature } => {
+                if !(0.0..=180.0).contains(&outer) {
+                    return Err(format!("{}.outer: must be from 0 to 180 degrees, got {}", context, outer).into());
+                }
+                Box::new(SpotLight::new(
+                    vec3(position),
+                    vec3(direction),
+                    light_colour(colour, temperature, intensity, context)?,
+                    inner.unwrap_or(outer),
+                    outer
+                ))
+            }
+            ObjectDesc::DirectionalLight { direction, illuminance, colour, temperature } => Box::new(DirectionalLight::new(
+                vec3(direction), light_colour(colour, temperature, illuminance, context)?
+            )),
+            ObjectDesc::Mesh { path, material } => {
+                let material = match material {
+                    Some(name) => self.named_material(&name, context)?,
+                    None => Matte::new_arc(Solid::new_arc(Vec3::new(0.73, 0.73, 0.73)))
+                };
+                let mesh = obj::load(self.base_dir.join(path), material)
+                    .map_err(|e| format!("{}: {}", context, e))?;
+                Box::new(mesh)
+            }
+            ObjectDesc::Instance { prototype, material } => {
+                let prototype = self.prototype(&prototype, context)?;
+                let material = match material {
+                    Some(name) => Some(self.named_material(&name, context)?),
+                    None => None
+                };
+                Box::new(Instance::new(prototype, matrix.take().unwrap_or_default(), material))
+            }
+            ObjectDesc::ConstantMedium { boundary, density, albedo } => {
+                let boundary = self.object(boundary, &format!("{}.boundary", context))?;
+                let albedo = self.texture(&albedo, context)?;
+                Box::new(ConstantMedium::new(Arc::from(boundary), albedo, density))
+            }
+        };
+
+        // instances take the transform themselves
+        if let Some(matrix) = matrix {
+            object = Box::new(Transform::new(Arc::from(object), matrix));
+        }
+        if !keys.is_empty() {
+            let animation = Animation::new(keys.iter().map(KeyDesc::keyframe).collect());
+            object = Box::new(Animated::new(Arc::from(object), animation));
+        }
+
+        Ok(object)
+    }
+}
+
+fn entry<T: DeserializeOwned>(table: &Table, context: &str) -> Result<T, Error> {
+    from_value(Value::Table(table.clone()), context)
+}
+
+fn from_value<T: DeserializeOwned>(value: Value, context: &str) -> Result<T, Error> {
+    value
+        .try_into()
+        .map_err(|e: toml::de::Error| format!("{}: {}", context, e.message()).into())
+}
+
+fn vec3(e: [f64; 3]) -> Vec3 {
+    Vec3::new(e[0], e[1], e[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{RenderSettings, Renderer};
+    use crate::scenes;
+
+    fn scene_files() -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    fn error(text: &str) -> String {
+        match parse(text, Path::new(""), 1.0) {
+            Ok(_) => panic!("parsed:\n{}", text),
+            Err(e) => e.to_string()
+        }
+    }
+
+    const CAMERA: &str = "[camera]\nlookfrom = [0, 0, -10]\nlookat = [0, 0, 0]\nvfov = 40\n";
+
+    // each of scenes/ is a built-in scene written out, and builds the same scene
+    #[test]
+    fn scene_files_match_the_built_in_scenes() {
+        let paths = scene_files();
+        assert!(!paths.is_empty());
+        let settings = RenderSettings { width: 8, height: 8, samples_per_pixel: 4, max_depth: 4, threads: 1, ..RenderSettings::default() };
+        for path in paths {
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            let loaded = load(&path, 1.0).unwrap_or_else(|e| panic!("{}", e));
+            let built_in = (scenes::find_scene(name).unwrap_or_else(|| panic!("no built-in scene {}", name)).build)(1.0, 0).unwrap();
+            assert_eq!(loaded.world.len(), built_in.world.len(), "{}", name);
+
+            let loaded = Renderer::new(loaded.world, loaded.camera, settings.clone()).render().unwrap();
+            let built_in = Renderer::new(built_in.world, built_in.camera, settings.clone()).render().unwrap();
+            for (x, y) in [(0, 0), (4, 4), (2, 6), (7, 1), (5, 3)] {
+                let (a, b) = (loaded.get(x, y), built_in.get(x, y));
+                assert!((a - b).length() <= 1e-9 * b.length().max(1.0), "{} pixel {} {}: {:?} against {:?}", name, x, y, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_fields_name_their_line_and_key() {
+        let text = format!("{}\n[materials.red]\ntype = \"matte\"\nalbedo = [1, 0, 0]\ncolour = [1, 0, 0]\n", CAMERA);
+        let e = error(&text);
+        assert!(e.starts_with("line 6: materials.red: "), "{}", e);
+        assert!(e.contains("colour"), "{}", e);
+
+        let text = format!("{}\n[[objects]]\ntype = \"sphere\"\ncentre = [0, 0, 0]\nradius = 1\nmaterial = \"red\"\n", CAMERA);
+        let e = error(&text);
+        assert!(e.starts_with("line 6: objects[0]: "), "{}", e);
+        assert!(e.contains("centre"), "{}", e);
+    }
+
+    #[test]
+    fn type_errors_name_their_line_and_key() {
+        let text = format!(
+            "{}\n[materials.red]\ntype = \"matte\"\nalbedo = [1, 0, 0]\n\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = \"big\"\nmaterial = \"red\"\n",
+            CAMERA
+        );
+        let e = error(&text);
+        assert!(e.starts_with("line 10: objects[0]: "), "{}", e);
+        assert!(e.contains("string"), "{}", e);
+
+        let text = format!("{}\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"red\"\ntransform = [{{ scale = \"twice\" }}]\n", CAMERA);
+        let e = error(&text);
+        assert!(e.starts_with("line 6: objects[0].transform: "), "{}", e);
+
+        let e = error("[camera]\nlookfrom = [0, 0]\nlookat = [0, 0, 0]\nvfov = 40\n");
+        assert!(e.contains("line 2") && e.contains("lookfrom"), "{}", e);
+    }
+}
@@ -0,0 +1,585 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use rand::distributions::Uniform;
+use rand::Rng;
+
+use crate::animation::CameraAnimation;
+use crate::camera::Camera;
+use crate::environment::Environment;
+use crate::hit::block::Block;
+use crate::hit::Hit;
+use crate::hit::constant_medium::ConstantMedium;
+use crate::hit::instance::Instance;
+use crate::hit::moving_sphere::MovingSphere;
+use crate::hit::quad::Quad;
+use crate::hit::rect::Rect;
+use crate::hit::sphere::Sphere;
+use crate::hit::transform::Transform;
+use crate::hit::world::World;
+use crate::matrix::Matrix4;
+use crate::material::{matte::Matte, metal::Metal, dielectric::Dielectric, diffuse::Diffuse};
+use crate::random::{self, Random};
+use crate::texture::checker::Checker;
+use crate::texture::image::Image;
+use crate::texture::noise::Noise;
+use crate::texture::solid::Solid;
+use crate::vec::{Vec3, Colour, Point3};
+use crate::Error;
+
+pub struct Scene {
+    pub world: World,
+    pub camera: Camera,
+    pub camera_animation: Option<CameraAnimation>, // for rendering frames of an animation
+    pub environment: Option<Arc<dyn Environment>> // instead of the camera's background
+}
+
+// a built-in scene, selectable by name from the command line.
+// the camera's aspect ratio depends on the requested image size, so it's passed in.
+// scenes with random parts (sphere placement, noise) draw them from seed, the same seed always
+// builds the same scene
+pub struct SceneBuilder {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub build: fn(aspect_ratio: f64, seed: u64) -> Result<Scene, Error>
+}
+
+pub const SCENES: &[SceneBuilder] = &[
+    SceneBuilder {
+        name: "cornell_box",
+        description: "cornell box with two rotated blocks",
+        build: |aspect_ratio, _seed| Ok(Scene {
+            world: cornell_box(),
+            camera: cornell_camera(aspect_ratio, Colour::new(0.0, 0.0, 0.0)),
+            camera_animation: None,
+            environment: None
+        })
+    },
+    SceneBuilder {
+        name: "cornell_smoke",
+        description: "cornell box with the blocks replaced by smoke",
+        build: |aspect_ratio, _seed| Ok(Scene {
+            world: cornell_smoke(),
+            camera: cornell_camera(aspect_ratio, Colour::new(0.0, 0.0, 0.0)),
+            camera_animation: None,
+            environment: None
+        })
+    },
+    SceneBuilder {
+        name: "cornell_smoke_without_light",
+        description: "the smoke blocks on a checkered ground under a sky",
+        build: |aspect_ratio, _seed| Ok(Scene {
+            world: cornell_smoke_without_light(),
+            camera: cornell_camera(aspect_ratio, Colour::new(0.7, 0.8, 1.0)),
+            camera_animation: None,
+            environment: None
+        })
+    },
+    SceneBuilder {
+        name: "simple_light",
+        description: "perlin spheres lit by a single rectangle light",
+        build: |aspect_ratio, seed| Ok(Scene {
+            world: simple_light(&mut random::seeded(seed)),
+            camera: Camera::new(
+                Point3::new(26.0, 3.0, 6.0),
+                Point3::new(0.0, 2.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                20.0,
+                aspect_ratio,
+                0.0,
+                10.0,
+                Colour::new(0.0, 0.0, 0.0)
+            ),
+            camera_animation: None,
+            environment: None
+        })
+    },
+    SceneBuilder {
+        name: "lots_of_spheres",
+        description: "random matte, metal and glass spheres around a globe",
+        build: |aspect_ratio, seed| Ok(Scene {
+            world: lots_of_spheres(&mut random::seeded(seed))?,
+            camera: Camera::new(
+                Point3::new(13.0, 2.0, 3.0),
+                Point3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                20.0,
+                aspect_ratio,
+                0.1,
+                10.0,
+                Colour::new(0.7, 0.8, 1.0)
+            ),
+            camera_animation: None,
+            environment: None
+        })
+    },
+    SceneBuilder {
+        name: "final_scene",
+        description: "the book two final scene: textures, glass, smoke and motion blur",
+        build: |aspect_ratio, seed| Ok(Scene {
+            world: final_scene(&mut random::seeded(seed))?,
+            camera: Camera::new(
+                Point3::new(478.0, 278.0, -600.0),
+                Point3::new(278.0, 278.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                40.0,
+                aspect_ratio,
+                0.0,
+                10.0,
+                Colour::new(0.0, 0.0, 0.0)
+            ),
+            camera_animation: None,
+            environment: None
+        })
+    },
+    SceneBuilder {
+        name: "instances",
+        description: "instanced blocks and sphere clusters under the final scene's light",
+        build: |aspect_ratio, seed| {
+            let camera = Camera::new(
+                Point3::new(478.0, 278.0, -600.0),
+                Point3::new(278.0, 278.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                40.0,
+                aspect_ratio,
+                0.0,
+                10.0,
+                Colour::new(0.0, 0.0, 0.0)
+            );
+            Ok(Scene {
+                world: instances(&mut random::seeded(seed), camera.shutter())?,
+                camera,
+                camera_animation: None,
+                environment: None
+            })
+        }
+    }
+];
+
+pub fn find_scene(name: &str) -> Option<&'static SceneBuilder> {
+    SCENES.iter().find(|scene| scene.name == name)
+}
+
+fn cornell_camera(aspect_ratio: f64, background: Colour) -> Camera {
+    Camera::new(
+        Point3::new(278.0, 278.0, -800.0),
+        Point3::new(278.0, 278.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+        background
+    )
+}
+
+fn cornell_box() -> World {
+
+    let mut world = World::new();
+
+    let red = Matte::new_arc(Solid::new_arc(Colour::new(0.65, 0.05, 0.05)));
+    let white = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.73, 0.73, 0.73))));
+    let green = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.12, 0.45, 0.15))));
+    let light = Diffuse::new_arc(Arc::new(Solid::new(Colour::new(15.0, 15.0, 15.0))));
+    
+    let left = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 2, green);
+    let right = Rect::new(0.0..555.0, 0.0..555.0, 0.0, 2, red);
+    let light_rect = Rect::new(213.0..343.0, 113.0..332.0, 554.0, 1, light.clone());
+    let bottom = Rect::new(0.0..555.0, 0.0..555.0, 0.0, 1, white.clone());
+    let top = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 1, white.clone());
+    let back = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 0, white.clone());
+
+    let box1 = Block::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(165.0, 330.0, 165.0),
+        white.clone()
+    );
+    let box1_transform = Transform::new(
+        Arc::new(box1),
+        Matrix4::rotation_y(35.0).rotate_x(-25.0).rotate_z(15.0).translate(Vec3::new(265.0, 0.0, 295.0))
+    );
+
+    let box2 = Block::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(165.0, 165.0, 165.0),
+        white.clone(),
+    );
+    let box2_transform = Transform::new(
+        Arc::new(box2),
+        Matrix4::rotation_y(-18.0).translate(Vec3::new(130.0, 0.0, 65.0))
+    );
+        
+    world.push(Arc::new(Box::new(left)));
+    world.push(Arc::new(Box::new(right)));
+    world.push(Arc::new(Box::new(bottom)));
+    world.push(Arc::new(Box::new(top)));
+    world.push(Arc::new(Box::new(back)));
+    world.push(Arc::new(Box::new(light_rect)));
+    world.push(Arc::new(Box::new(box1_transform)));
+    world.push(Arc::new(Box::new(box2_transform)));
+
+    world
+}
+
+fn cornell_smoke() -> World {
+
+    let mut world = World::new();
+
+    let red = Matte::new_arc(Solid::new_arc(Colour::new(0.65, 0.05, 0.05)));
+    let white = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.73, 0.73, 0.73))));
+    let green = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.12, 0.45, 0.15))));
+    let light = Diffuse::new_arc(Arc::new(Solid::new(Colour::new(15.0, 15.0, 15.0))));
+    
+    let left = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 2, green);
+    let right = Rect::new(0.0..555.0, 0.0..555.0, 0.0, 2, red);
+    let light_rect = Rect::new(213.0..343.0, 113.0..332.0, 554.0, 1, light.clone());
+    let bottom = Rect::new(0.0..555.0, 0.0..555.0, 0.0, 1, white.clone());
+    let top = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 1, white.clone());
+    let back = Rect::new(0.0..555.0, 0.0..555.0, 555.0, 0, white.clone());
+
+    let box1 = Block::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(165.0, 330.0, 165.0),
+        white.clone()
+    );
+    let box1_transform = Transform::new_arc(
+        Arc::new(box1),
+        Matrix4::rotation_y(35.0).rotate_x(-25.0).rotate_z(15.0).translate(Vec3::new(265.0, 0.0, 295.0))
+    );
+
+    let box2 = Block::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(165.0, 165.0, 165.0),
+        white.clone(),
+    );
+    let box2_transform = Transform::new_arc(
+        Arc::new(box2),
+        Matrix4::rotation_y(-18.0).translate(Vec3::new(130.0, 0.0, 65.0))
+    );
+        
+    world.push(Arc::new(Box::new(left)));
+    world.push(Arc::new(Box::new(right)));
+    world.push(Arc::new(Box::new(bottom)));
+    world.push(Arc::new(Box::new(top)));
+    world.push(Arc::new(Box::new(back)));
+    world.push(Arc::new(Box::new(light_rect)));
+    world.push(ConstantMedium::new_arc(box1_transform, Solid::new_arc(Colour::new(1.0, 1.0, 1.0)), 0.06));
+    world.push(ConstantMedium::new_arc(box2_transform, Solid::new_arc(Colour::new(0.0, 1.0, 0.0)), 0.05));
+
+    world
+}
+
+fn cornell_smoke_without_light() -> World {
+
+    let mut world = World::new();
+
+    let white = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.73, 0.73, 0.73))));
+    let _green = Matte::new_arc(Arc::new(Solid::new(Colour::new(0.12, 0.45, 0.15))));
+
+    let box1 = Block::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(165.0, 330.0, 165.0),
+        white.clone()
+    );
+    let box1_transform = Transform::new_arc(
+        Arc::new(box1),
+        Matrix4::rotation_y(35.0).rotate_x(-25.0).rotate_z(15.0).translate(Vec3::new(265.0, 0.0, 295.0))
+    );
+
+    let box2 = Block::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(165.0, 165.0, 165.0),
+        white.clone(),
+    );
+    let box2_transform = Transform::new_arc(
+        Arc::new(box2),
+        Matrix4::rotation_y(-18.0).translate(Vec3::new(130.0, 0.0, 65.0))
+    );
+        
+    let checker = Checker::new_texture_arc(0.32, Colour::new(0.2, 0.3, 0.1),
+        Colour::new(0.9, 0.9, 0.9)
+    );
+    let mat_ground = Matte::new_arc(checker);
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, mat_ground.clone());
+
+    world.push(ConstantMedium::new_arc(box1_transform, Solid::new_arc(Colour::new(1.0, 1.0, 1.0)), 0.06));
+    world.push(ConstantMedium::new_arc(box2_transform, Solid::new_arc(Colour::new(0.0, 1.0, 0.0)), 0.01));
+    world.push(Arc::new(Box::new(ground_sphere)));
+
+    world
+}
+
+
+fn simple_light(rng: &mut Random) -> World {
+    let mut world = World::new();
+
+    let mat_perlin = Matte::new_arc(Noise::new_arc(4.0, rng));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, mat_perlin.clone());
+    let sphere_center = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, mat_perlin);
+
+    let difflight = Diffuse::new_arc(Solid::new_arc(Colour::new(4.0,4.0,4.0)));
+    let _light = Quad::new(
+        Point3::new(3.0,1.0,-1.0), 
+        Vec3::new(-7.0,0.0,0.0), 
+        Vec3::new(0.0,-2.0,0.0), 
+        difflight.clone()
+    );
+    let light = Rect::new(3.0..5.0, 1.0..3.0, -1.0, 0, difflight.clone());
+    let _lightball = Sphere::new(Point3::new(0.0, 7.0, 0.0), 2.0, difflight);
+
+    world.push(Arc::new(Box::new(ground_sphere)));
+    world.push(Arc::new(Box::new(light)));
+    // world.push(Arc::new(Box::new(lightball)));
+    world.push(Arc::new(Box::new(sphere_center)));
+
+    world
+}
+
+fn lots_of_spheres(rng: &mut Random) -> Result<World, Error> {
+    let mut world = World::new();
+
+    for a in -11..=11 {
+        for b in -11..=11 {
+            let choose_mat: f64 = rng.gen();
+            let center = Point3::new((a as f64) + rng.gen_range(0.0..0.9),
+                                     0.2,
+                                     (b as f64) + rng.gen_range(0.0..0.9));
+
+            if choose_mat < 0.8 {
+                // Diffuse
+                let albedo = Colour::random(0.0..1.0, rng) * Colour::random(0.0..1.0, rng);
+                let sphere_mat = Arc::new(
+                    Matte::new(
+                        Arc::new(
+                            Solid::new(albedo)
+                        )
+                    )
+                );
+                let _center1 = center + Vec3::new(0.0, rng.gen_range(0.0..0.3), 0.0);
+                let sphere = Sphere::new(
+                    center, 0.2, sphere_mat
+                );
+                // let sphere = MovingSphere::new(
+                //     center, 
+                //     center1,
+                //     0.2, 
+                //     sphere_mat);
+                world.push(Arc::new(Box::new(sphere)));
+            } else if choose_mat < 0.95 {
+                // Metal
+                let albedo = Colour::random(0.4..1.0, rng);
+                let fuzz = rng.gen_range(0.0..0.5);
+                let sphere_mat = Metal::new_arc(Solid::new_arc(albedo), fuzz);
+                let center1 = center + Vec3::new(0.0, rng.gen_range(0.0..0.5), 0.0);
+                let sphere = MovingSphere::new(
+                    center, 
+                    center1,
+                    0.2, 
+                    0.0..1.0,
+                    sphere_mat);
+                world.push(Arc::new(Box::new(sphere)));
+            } else {
+                // Glass
+                let sphere_mat = Dielectric::new_arc(1.5);
+                let center1 = center + Vec3::new(0.0, rng.gen_range(0.0..0.5), 0.0);
+                let sphere = MovingSphere::new(
+                    center, 
+                    center1,
+                    0.2, 
+                    0.0..1.0,
+                    sphere_mat);
+                world.push(Arc::new(Box::new(sphere)));
+            }
+        }
+    }
+
+    let checker = Checker::new_texture_arc(0.32, 
+            Colour::new(0.2, 0.3, 0.1),
+            Colour::new(0.9, 0.9, 0.9)
+    );
+    let mat_perlin = Matte::new_arc(Noise::new_arc(4.0, rng));
+    let _mat_ground = Matte::new_arc(checker);
+    let mat_center = Matte::new_arc(Arc::new(Image::new("earth.jpg")?));
+    let mat_left = Arc::new(Dielectric::new(1.5));
+    let _mat_right = Metal::new_arc(Solid::new_arc(Colour::new(0.8, 0.6, 0.2)), 0.0);
+
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, mat_perlin.clone());
+    let sphere_center = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, mat_center);
+    let sphere_left = Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, mat_left);
+    let sphere_right = Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, mat_perlin);
+
+    let earth_texture = Image::new("earth.jpg")?;
+    let earth_surface: Matte = Matte::new(Arc::new(earth_texture));
+    let globe = Sphere::new(Vec3::new(0.0, 0.0, 0.0), 2.0, Arc::new(earth_surface));
+
+    world.push(Arc::new(Box::new(globe)));
+    world.push(Arc::new(Box::new(ground_sphere)));
+    world.push(Arc::new(Box::new(sphere_center)));
+    world.push(Arc::new(Box::new(sphere_left)));
+    world.push(Arc::new(Box::new(sphere_right)));
+
+    Ok(world)
+}
+
+fn final_scene(rng: &mut Random) -> Result<World, Error> {
+    let mut world = World::new();
+
+    let ground = Matte::new_arc(Solid::new_arc(Colour::new(0.48, 0.83, 0.53)));
+
+    let block = Block::new_arc(
+        Vec3::new(-1000.0, 0.0, -1000.0),
+        Vec3::new(1000.0, 0.0, 1000.0),
+        ground,
+    );
+    world.push(block);
+
+    let light = Diffuse::new_arc(Solid::new_arc(Colour::new(7.0, 7.0, 7.0)));
+    world.push(Rect::new_arc(
+        123.0..423.0,
+        147.0..412.0,
+        554.0,
+        1,
+        light
+    ));
+
+    let centre1 = Vec3::new(400.0, 400.0, 200.0);
+    let centre2 = centre1 + Vec3::new(29.0, 0.0, 0.0);
+    let moving_sphere_material = Matte::new_arc(Solid::new_arc(Colour::new(0.7, 0.3, 0.1)));
+    world.push(MovingSphere::new_arc(
+        centre1,
+        centre2,
+        50.0,
+        0.0..1.0,
+        moving_sphere_material,
+    ));
+
+    // // glass
+    world.push(Arc::new(Box::new(Sphere::new(
+        Vec3::new(260.0, 150.0, 45.0),
+        50.0,
+        Dielectric::new_arc(1.5),
+    ))));
+
+    // matte lower right
+    world.push(Arc::new(Box::new(Sphere::new(
+        Vec3::new(0.0, 150.0, 145.0),
+        50.0,
+        Metal::new_arc(Solid::new_arc(Colour::new(0.8, 0.8, 0.9)), 1.0),
+    ))));
+
+    // 
+    let boundary = Sphere::new(
+        Vec3::new(360.0, 150.0, 145.0),
+        71.0,
+        Dielectric::new_arc(1.5),
+    );
+    world.push(Arc::new(Box::new(boundary)));
+    let boundary = Sphere::new_arc(
+        Vec3::new(360.0, 150.0, 145.0),
+        70.0,
+        Dielectric::new_arc(1.5),
+    );
+    world.push(ConstantMedium::new_arc(
+        boundary,
+        Solid::new_arc(Colour::new(0.2, 0.4, 0.9)),
+        0.2,
+    ));
+
+    let boundary = Sphere::new_arc(
+        Vec3::new(0.0, 0.0, 0.0), 
+        5000.0, 
+        Dielectric::new_arc(1.5)
+    );
+    world.push(ConstantMedium::new_arc(
+        boundary,
+        Solid::new_arc(Colour::new(1.0, 1.0, 1.0)),
+        0.0001
+    ));
+
+    let emat = Matte::new_arc(Arc::new(Image::new("earth.jpg")?));
+    world.push(Arc::new(Box::new(Sphere::new(
+        Vec3::new(400.0, 200.0, 400.0), 
+        100.0, 
+        emat)))
+    );
+
+    let pertext = Noise::new_arc(0.1, rng);
+    world.push(Arc::new(Box::new(Sphere::new(
+        Vec3::new(220.0, 280.0, 300.0),
+        80.0,
+        Matte::new_arc(pertext),
+    ))));
+
+    Ok(world)
+}
+
+// book two's ground of random height blocks and its cluster of spheres, as instances: every
+// block is the same unit block stretched into place, and the cluster is built once and hung
+// three times, the copies painted in materials of their own. the cluster's bvh is built over
+// the camera's shutter, as scene files do
+fn instances(rng: &mut Random, shutter: Range<f64>) -> Result<World, Error> {
+    let mut world = World::new();
+
+    let ground = Matte::new_arc(Solid::new_arc(Colour::new(0.48, 0.83, 0.53)));
+    let rand_dst = Uniform::from(1.0..=100.0);
+    const BOXES_PER_SIDE: u32 = 20;
+    const W: f64 = 100.0;
+
+    let unit_block: Arc<dyn Hit> = Arc::new(Block::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 1.0, 1.0),
+        ground,
+    ));
+    for i in 0..BOXES_PER_SIDE {
+        let x0 = -1000.0 + f64::from(i) * W;
+        for j in 0..BOXES_PER_SIDE {
+            let z0 = -1000.0 + f64::from(j) * W;
+            let y1 = rng.sample(rand_dst);
+            world.push(Instance::new_arc(
+                Arc::clone(&unit_block),
+                Matrix4::scaling(Vec3::new(W, y1, W)).translate(Vec3::new(x0, 0.0, z0)),
+                None,
+            ));
+        }
+    }
+
+    let light = Diffuse::new_arc(Solid::new_arc(Colour::new(7.0, 7.0, 7.0)));
+    world.push(Rect::new_arc(
+        123.0..423.0,
+        147.0..412.0,
+        554.0,
+        1,
+        light
+    ));
+
+    let mut spheres = World::new();
+    let white = Matte::new_arc(Solid::new_arc(Colour::new(0.73, 0.73, 0.73)));
+    const NS: u32 = 1000;
+    for _ in 0..NS {
+        spheres.push(Sphere::new_hittable(
+            Vec3::random(0.0..165.0, rng),
+            10.0,
+            white.clone(),
+        ));
+    }
+    let cluster = Instance::prototype(spheres, shutter);
+
+    world.push(Instance::new_arc(
+        Arc::clone(&cluster),
+        Matrix4::rotation_y(15.0).translate(Vec3::new(-100.0, 270.0, 395.0)),
+        None,
+    ));
+    world.push(Instance::new_arc(
+        Arc::clone(&cluster),
+        Matrix4::scaling(Vec3::new(0.6, 0.6, 0.6)).rotate_y(-30.0).translate(Vec3::new(150.0, 150.0, 100.0)),
+        Some(Metal::new_arc(Solid::new_arc(Colour::new(0.8, 0.6, 0.2)), 0.2)),
+    ));
+    world.push(Instance::new_arc(
+        cluster,
+        Matrix4::rotation_x(40.0).translate(Vec3::new(300.0, 250.0, 300.0)),
+        Some(Dielectric::new_arc(1.5)),
+    ));
+
+    Ok(world)
+}
+
@@ -0,0 +1,67 @@
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+
+use crate::animation::CameraAnimation;
+use crate::camera::Camera;
+
+// the frames of an animation. time is in seconds, frame f is the picture at f / fps and its
+// shutter is open for the first shutter fraction of the frame (0.5 is the film camera's 180
+// degree shutter), so whatever moves in the meantime is blurred. a camera with an exposure
+// keeps its own shutter speed instead.
+// the camera itself is placed once per frame, where it is halfway through the shutter.
+#[derive(Clone, Debug)]
+pub struct Sequence {
+    pub frames: RangeInclusive<u64>,
+    pub fps: f64,
+    pub shutter: f64
+}
+
+impl Sequence {
+    pub fn shutter(&self, camera: &Camera, frame: u64) -> Range<f64> {
+        let open = frame as f64 / self.fps;
+        open..open + self.open_for(camera)
+    }
+
+    // how long camera's shutter is open for every frame, in seconds
+    fn open_for(&self, camera: &Camera) -> f64 {
+        camera.exposure().map_or(self.shutter / self.fps, |exposure| exposure.shutter_speed)
+    }
+
+    // the scene's camera as it is for frame, moved by animation if it has one
+    pub fn camera(&self, camera: &Camera, animation: Option<&CameraAnimation>, frame: u64) -> Camera {
+        let shutter = self.shutter(camera, frame);
+        let camera = match animation {
+            Some(animation) => camera.with_view(animation.at((shutter.start + shutter.end) / 2.0)),
+            None => camera.clone()
+        };
+        camera.with_shutter(shutter)
+    }
+
+    // one turn of camera around what it looks at over the frames, the last frame one step
+    // short of the first so the sequence loops
+    pub fn turntable(&self, camera: &Camera) -> CameraAnimation {
+        let start = *self.frames.start() as f64 / self.fps;
+        let end = (*self.frames.end() + 1) as f64 / self.fps;
+        // the frames are placed halfway through their shutter, so is the turn
+        let offset = self.open_for(camera) / 2.0;
+        CameraAnimation::turntable(camera.view(), start + offset, end + offset)
+    }
+}
+
+// where frame goes: the last run of #s in the file name replaced by the frame number, padded
+// with zeros to as many digits (frame_####.png), or _0001 before the extension if there are none
+pub fn frame_path(path: &Path, frame: u64) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match name.rfind('#') {
+        Some(last) => {
+            let first = name[..last].rfind(|c| c != '#').map_or(0, |i| i + 1);
+            let digits = last + 1 - first;
+            format!("{}{:0digits$}{}", &name[..first], frame, &name[last + 1..], digits = digits)
+        }
+        None => match name.rfind('.') {
+            Some(dot) if dot > 0 => format!("{}_{:04}{}", &name[..dot], frame, &name[dot..]),
+            _ => format!("{}_{:04}", name, frame)
+        }
+    };
+    path.with_file_name(name)
+}
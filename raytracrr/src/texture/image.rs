@@ -37,7 +37,7 @@ impl Image {
 }
 
 impl Texture for Image {
-    fn value(&self, u: f64, v: f64, p: Vec3) -> Colour {
+    fn value(&self, u: f64, v: f64, _p: Vec3) -> Colour {
         let w = self.data.width();
         let h = self.data.height();
 
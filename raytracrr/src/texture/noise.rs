@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::{perlin::Perlin, vec::{Colour, Vec3}};
+use crate::{perlin::Perlin, random::Random, vec::{Colour, Vec3}};
 use super::Texture;
 
 pub struct Noise {
@@ -9,20 +9,20 @@ pub struct Noise {
 }
 
 impl Noise {
-    pub fn new(scale: f64) -> Noise {
+    pub fn new(scale: f64, rng: &mut Random) -> Noise {
         Noise {
-            perlin: Perlin::new(),
+            perlin: Perlin::new(rng),
             scale
         }
     }
 
-    pub fn new_arc(scale: f64) -> Arc<Noise> {
-        Arc::new(Noise::new(scale))
+    pub fn new_arc(scale: f64, rng: &mut Random) -> Arc<Noise> {
+        Arc::new(Noise::new(scale, rng))
     }
 }
 
 impl Texture for Noise {
-    fn value(&self, u: f64, v: f64, p: Vec3) -> Colour {
+    fn value(&self, _u: f64, _v: f64, p: Vec3) -> Colour {
         // cast the perlin output back to between 0 and 1.
         // Colour::new(1.0, 1.0, 1.0) * 0.5 * (1.0 + self.perlin.noise(p * self.scale))
         // Colour::new(1.0, 1.0, 1.0) * (self.perlin.turb(p * self.scale, 7))
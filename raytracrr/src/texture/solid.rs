@@ -20,7 +20,7 @@ impl Solid {
 }
 
 impl Texture for Solid {
-    fn value(&self, u: f64, v: f64, p: Vec3) -> Colour {
+    fn value(&self, _u: f64, _v: f64, _p: Vec3) -> Colour {
         self.colour_value
     }
 }
@@ -4,6 +4,8 @@ use std::fmt::Display;
 
 use rand::Rng;
 
+use crate::random::Random;
+
 #[derive(Clone, Copy, Default, Debug)]
 pub struct Vec3 {
     pub e: [f64; 3]
@@ -50,17 +52,16 @@ impl Vec3 {
     }
 
     // generating random vec3 in range
-    pub fn random(r: std::ops::Range<f64>) -> Vec3 {
-        let mut rng = rand::thread_rng();
+    pub fn random(r: std::ops::Range<f64>, rng: &mut Random) -> Vec3 {
         Vec3 {
             e: [rng.gen_range(r.clone()), rng.gen_range(r.clone()), rng.gen_range(r.clone())]
         }
     }
 
     // finding random point in unit sphere
-    pub fn random_in_sphere() -> Vec3 {
+    pub fn random_in_sphere(rng: &mut Random) -> Vec3 {
         loop {
-            let v = Vec3::random(-1.0..1.0);
+            let v = Vec3::random(-1.0..1.0, rng);
             if v.length() < 1.0 {
                 return v;
             }
@@ -85,6 +86,21 @@ impl Vec3 {
         format!("{} {} {}", ir, ig, ib)
     }
 
+    // how bright a linear colour looks, ignoring its hue
+    pub fn luminance(self) -> f64 {
+        0.2126 * self[0] + 0.7152 * self[1] + 0.0722 * self[2]
+    }
+
+    // the linear srgb colour of cie chromaticity (x, y) with that luminance
+    pub fn from_xyy(x: f64, y: f64, luminance: f64) -> Colour {
+        let (cx, cy, cz) = (x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+        Colour::new(
+            3.2406 * cx - 1.5372 * cy - 0.4986 * cz,
+            -0.9689 * cx + 1.8758 * cy + 0.0415 * cz,
+            0.0557 * cx - 0.2040 * cy + 1.0570 * cz
+        )
+    }
+
     // check if vector is near zero
     pub fn near_zero(self) -> bool {
         const EPS: f64 = 1.0e-8;
@@ -133,7 +149,7 @@ impl Add for Vec3 {
 }
 
 impl AddAssign for Vec3 {
-    fn add_assign(&mut self, rhs: Self) -> () {
+    fn add_assign(&mut self, rhs: Self) {
         *self = Vec3 {
             e: [self.e[0] + rhs.e[0], self.e[1] + rhs.e[1], self.e[2] + rhs.e[2]]
         }
@@ -150,7 +166,7 @@ impl Sub for Vec3 {
 }
 
 impl SubAssign for Vec3 {
-    fn sub_assign(&mut self, rhs: Self) -> () {
+    fn sub_assign(&mut self, rhs: Self) {
         *self = Vec3 {
             e: [self.e[0] - rhs.e[0], self.e[1] - rhs.e[1], self.e[2] - rhs.e[2]]
         }
@@ -167,7 +183,7 @@ impl Mul<f64> for Vec3 {
 }
 
 impl MulAssign<f64> for Vec3 {
-    fn mul_assign(&mut self, other: f64) -> () {
+    fn mul_assign(&mut self, other: f64) {
         *self = Vec3 {
             e: [self[0] * other, self[1] * other, self[2] * other]
         };
@@ -184,7 +200,7 @@ impl Mul<Vec3> for Vec3 {
 }
 
 impl MulAssign<Vec3> for Vec3 {
-    fn mul_assign(&mut self, other: Vec3) -> () {
+    fn mul_assign(&mut self, other: Vec3) {
         *self = Vec3 {
             e: [self[0] * other[0], self[1] * other[1], self[2] * other[2]]
         };
@@ -210,7 +226,7 @@ impl Div<f64> for Vec3 {
 }
 
 impl DivAssign<f64> for Vec3 {
-    fn div_assign(&mut self, other: f64) -> () {
+    fn div_assign(&mut self, other: f64) {
         *self = Vec3 {
             e: [self.e[0] / other, self.e[1] / other, self.e[2] / other]
         }